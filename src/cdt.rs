@@ -0,0 +1,858 @@
+use crate::math::{circumcenter, in_circle, orient};
+use crate::mesh::Mesh;
+use crate::triangulation::Triangulation;
+use ggez::graphics::Point2;
+
+use std::collections::{HashMap, VecDeque};
+
+// Constrained Delaunay triangulation. It starts as the Delaunay triangulation of the
+// points, then every constraint segment is forced into it: the triangles it crosses are
+// removed and both sides of the segment are retriangulated. A constraint crossing another
+// one gets a new vertex at the intersection. Edges that are not constrained stay locally
+// Delaunay, points inserted later are legalized with flips that never cross a constraint.
+//
+// The triangulation covers the convex hull, triangles are counter clockwise in a y-up
+// coordinate system.
+#[derive(Clone, Debug)]
+pub struct Cdt {
+    points: Vec<(f64, f64)>,
+    triangles: Vec<[usize; 3]>,
+    // neighbour across the edge opposite of vertex i
+    neighbours: Vec<[Option<usize>; 3]>,
+    constrained: Vec<[bool; 3]>,
+    // a triangle of every vertex, None for unused points
+    vertex_triangle: Vec<Option<usize>>,
+    // duplicates are not part of the triangulation, they map to their equal point
+    canonical: Vec<usize>,
+}
+
+impl Cdt {
+    pub fn new(points: &[Point2]) -> Self {
+        let mesh = Mesh::new(points.to_vec(), Triangulation::new(points).triangles());
+        let mut cdt = Cdt {
+            points: points
+                .iter()
+                .map(|p| (f64::from(p.x), f64::from(p.y)))
+                .collect(),
+            triangles: mesh.triangles().to_vec(),
+            neighbours: mesh.neighbours().to_vec(),
+            constrained: vec![[false; 3]; mesh.len()],
+            vertex_triangle: vec![None; points.len()],
+            canonical: (0..points.len()).collect(),
+        };
+        for (t, triangle) in cdt.triangles.iter().enumerate() {
+            for v in triangle {
+                cdt.vertex_triangle[*v] = Some(t);
+            }
+        }
+        let key = |p: Point2| (p.x.to_bits(), p.y.to_bits());
+        let used: HashMap<(u32, u32), usize> = (0..points.len())
+            .filter(|v| cdt.vertex_triangle[*v].is_some())
+            .map(|v| (key(points[v]), v))
+            .collect();
+        for v in 0..points.len() {
+            if let Some(u) = used.get(&key(points[v])) {
+                cdt.canonical[v] = *u;
+            }
+        }
+        cdt
+    }
+
+    pub fn with_constraints(points: &[Point2], constraints: &[(usize, usize)]) -> Self {
+        let mut cdt = Self::new(points);
+        for (a, b) in constraints {
+            cdt.insert_constraint(*a, *b);
+        }
+        cdt
+    }
+
+    // the points including the ones added by intersections and insert_point
+    pub fn points(&self) -> Vec<Point2> {
+        self.points
+            .iter()
+            .map(|p| Point2::new(p.0 as f32, p.1 as f32))
+            .collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.triangles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.triangles.is_empty()
+    }
+
+    // every constrained edge once
+    pub fn constrained_edges(&self) -> Vec<(usize, usize)> {
+        let mut edges = Vec::new();
+        for t in 0..self.triangles.len() {
+            for i in 0..3 {
+                let counted = self.neighbours[t][i].map_or(false, |u| u < t);
+                if self.constrained[t][i] && !counted {
+                    edges.push(self.edge(t, i));
+                }
+            }
+        }
+        edges
+    }
+
+    pub fn mesh(&self) -> Mesh {
+        self.mesh_with_holes(&[], false)
+    }
+
+    // without the triangles reachable from the hole points without crossing a constraint,
+    // with exterior also without the ones reachable from the convex hull
+    pub fn mesh_with_holes(&self, holes: &[Point2], exterior: bool) -> Mesh {
+        let removed = self.removed(holes, exterior);
+        let triangles = (0..self.triangles.len())
+            .filter(|t| !removed[*t])
+            .map(|t| self.triangles[t])
+            .collect();
+        Mesh::new(self.points(), triangles)
+    }
+
+    // Without holes given by points: a triangle is kept if every path to the outside of
+    // the convex hull crosses an odd number of constraints. The outermost closed loop of
+    // constraints bounds the domain, a loop inside of it a hole, a loop inside of that an
+    // island and so on. Constraints that do not close a loop can be walked around and
+    // change nothing.
+    pub fn mesh_even_odd(&self) -> Mesh {
+        let triangles = self
+            .depths()
+            .into_iter()
+            .enumerate()
+            .filter(|(_, depth)| depth % 2 == 1)
+            .map(|(t, _)| self.triangles[t])
+            .collect();
+        Mesh::new(self.points(), triangles)
+    }
+
+    // the fewest constraints crossed on the way from outside of the hull to every triangle
+    fn depths(&self) -> Vec<usize> {
+        let mut depths = vec![std::usize::MAX; self.triangles.len()];
+        // crossing an edge that is not constrained keeps the depth, those triangles go to
+        // the front so every depth is done before the next one starts
+        let mut queue = VecDeque::new();
+        for t in 0..self.triangles.len() {
+            for i in 0..3 {
+                if self.neighbours[t][i].is_none() {
+                    if self.constrained[t][i] {
+                        queue.push_back((t, 1));
+                    } else {
+                        queue.push_front((t, 0));
+                    }
+                }
+            }
+        }
+        while let Some((t, depth)) = queue.pop_front() {
+            if depths[t] <= depth {
+                continue;
+            }
+            depths[t] = depth;
+            for i in 0..3 {
+                if let Some(n) = self.neighbours[t][i] {
+                    if self.constrained[t][i] {
+                        queue.push_back((n, depth + 1));
+                    } else {
+                        queue.push_front((n, depth));
+                    }
+                }
+            }
+        }
+        depths
+    }
+
+    // the triangles left out by mesh_with_holes
+    fn removed(&self, holes: &[Point2], exterior: bool) -> Vec<bool> {
+        let mut removed = vec![false; self.triangles.len()];
+        let mut stack = Vec::new();
+        if exterior {
+            for t in 0..self.triangles.len() {
+                if (0..3).any(|i| self.neighbours[t][i].is_none() && !self.constrained[t][i]) {
+                    stack.push(t);
+                }
+            }
+        }
+        for hole in holes {
+            stack.extend(self.locate((f64::from(hole.x), f64::from(hole.y))));
+        }
+        while let Some(t) = stack.pop() {
+            if removed[t] {
+                continue;
+            }
+            removed[t] = true;
+            for i in 0..3 {
+                if !self.constrained[t][i] {
+                    stack.extend(self.neighbours[t][i]);
+                }
+            }
+        }
+        removed
+    }
+
+    // adds a point inside the convex hull and returns its index, None outside of it
+    pub fn insert_point(&mut self, point: Point2) -> Option<usize> {
+        let q = (f64::from(point.x), f64::from(point.y));
+        let t = self.locate(q)?;
+        Some(self.insert(t, q))
+    }
+
+    // adds q inside or on the boundary of triangle t
+    fn insert(&mut self, t: usize, q: (f64, f64)) -> usize {
+        let triangle = self.triangles[t];
+        if let Some(v) = triangle.iter().find(|v| self.points[**v] == q) {
+            return *v;
+        }
+        let p = self.add_point(q);
+        let on_edge =
+            (0..3).find(|i| self.orient(triangle[(i + 1) % 3], triangle[(i + 2) % 3], q) == 0.0);
+        match on_edge {
+            Some(i) => self.split_edge(t, i, p),
+            None => {
+                let [a, b, c] = triangle;
+                let new = self.replace(&[t], &[[p, b, c], [p, c, a], [p, a, b]], &[]);
+                self.legalize(p, new);
+            }
+        }
+        p
+    }
+
+    // forces the segment between two points into the triangulation
+    pub fn insert_constraint(&mut self, a: usize, b: usize) {
+        let (mut a, b) = (self.canonical[a], self.canonical[b]);
+        while a != b {
+            if self.vertex_triangle[a].is_none() || self.vertex_triangle[b].is_none() {
+                return;
+            }
+            a = self.constrain(a, b);
+        }
+    }
+
+    // Ruppert's Delaunay refinement of the triangles kept by mesh_with_holes. Segments
+    // with a vertex inside their diametral circle are split, then triangles with an angle
+    // below min_angle (in degrees) or an area above max_area get a vertex at their
+    // circumcenter, unless it lies behind or encroaches a segment, which is split instead.
+    // Segments at input vertices are split at powers of two from the vertex, so small
+    // angles between them do not start an endless cascade. Input angles below 60 degrees
+    // can still make the bound unreachable, at most limit points are added. Returns the
+    // number of added points.
+    pub fn refine(
+        &mut self,
+        holes: &[Point2],
+        exterior: bool,
+        min_angle: f64,
+        max_area: Option<f64>,
+        limit: usize,
+    ) -> usize {
+        let input = self.points.len();
+        // ratio of circumradius to shortest edge of a triangle with angle min_angle
+        let bound = 1.0 / (2.0 * min_angle.to_radians().sin());
+        let mut segments = self.constrained_edges();
+        if !exterior {
+            for t in 0..self.triangles.len() {
+                for i in 0..3 {
+                    if self.neighbours[t][i].is_none() && !self.constrained[t][i] {
+                        segments.push(self.edge(t, i));
+                    }
+                }
+            }
+        }
+
+        let mut added = 0;
+        while added < limit {
+            let mut k = 0;
+            while k < segments.len() && added < limit {
+                let (a, b) = segments[k];
+                if self.encroached(a, b) {
+                    self.split_segment(&mut segments, a, b, input);
+                    added += 1;
+                    // the new vertex may encroach segments already checked
+                    k = 0;
+                } else {
+                    k += 1;
+                }
+            }
+
+            let removed = self.removed(holes, exterior);
+            let bad: Vec<[usize; 3]> = (0..self.triangles.len())
+                .filter(|t| !removed[*t] && self.is_bad(*t, bound, max_area))
+                .map(|t| self.triangles[t])
+                .collect();
+            if bad.is_empty() {
+                break;
+            }
+            for triangle in bad {
+                if limit <= added {
+                    break;
+                }
+                // earlier insertions may have replaced the triangle already
+                let t = match self.find_triangle(triangle) {
+                    Some(t) if self.is_bad(t, bound, max_area) => t,
+                    _ => continue,
+                };
+                let c = self.circumcenter(t);
+                match self.walk(t, c) {
+                    Err((u, i)) => {
+                        let (a, b) = self.edge(u, i);
+                        self.split_segment(&mut segments, a, b, input);
+                        added += 1;
+                    }
+                    Ok(u) => {
+                        let encroached: Vec<(usize, usize)> = segments
+                            .iter()
+                            .filter(|(a, b)| {
+                                let (pa, pb) = (self.points[*a], self.points[*b]);
+                                (pa.0 - c.0) * (pb.0 - c.0) + (pa.1 - c.1) * (pb.1 - c.1) < 0.0
+                            })
+                            .cloned()
+                            .collect();
+                        if encroached.is_empty() {
+                            self.insert(u, c);
+                            added += 1;
+                        }
+                        for (a, b) in encroached {
+                            self.split_segment(&mut segments, a, b, input);
+                            added += 1;
+                        }
+                    }
+                }
+            }
+        }
+        added
+    }
+
+    // a vertex next to the segment lies inside its diametral circle
+    fn encroached(&self, a: usize, b: usize) -> bool {
+        let (pa, pb) = (self.points[a], self.points[b]);
+        self.triangles_around(a)
+            .into_iter()
+            .filter(|t| self.triangles[*t].contains(&b))
+            .any(|t| {
+                let v = self.points[self.triangles[t][3 - self.index(t, a) - self.index(t, b)]];
+                (pa.0 - v.0) * (pb.0 - v.0) + (pa.1 - v.1) * (pb.1 - v.1) < 0.0
+            })
+    }
+
+    // splits the segment edge from a to b, in the middle or at a power of two from an
+    // input vertex, and replaces it by its halves in segments
+    fn split_segment(
+        &mut self,
+        segments: &mut Vec<(usize, usize)>,
+        a: usize,
+        b: usize,
+        input: usize,
+    ) {
+        let (pa, pb) = (self.points[a], self.points[b]);
+        let length = (pb.0 - pa.0).hypot(pb.1 - pa.1);
+        let shell = 2f64.powf((length / 2.0).log2().round()) / length;
+        let s = match (a < input, b < input) {
+            (true, false) => shell,
+            (false, true) => 1.0 - shell,
+            _ => 0.5,
+        };
+        let q = (pa.0 + s * (pb.0 - pa.0), pa.1 + s * (pb.1 - pa.1));
+        let (t, i) = match self.triangles_around(a).into_iter().find_map(|t| {
+            (0..3)
+                .find(|i| self.edge(t, *i) == (a, b) || self.edge(t, *i) == (b, a))
+                .map(|i| (t, i))
+        }) {
+            Some(edge) => edge,
+            None => return,
+        };
+        let p = self.add_point(q);
+        self.split_edge(t, i, p);
+        if let Some(k) = segments.iter().position(|s| *s == (a, b) || *s == (b, a)) {
+            segments[k] = (a, p);
+            segments.push((p, b));
+        }
+    }
+
+    // the ratio of circumradius to shortest edge is above bound or the area too large
+    fn is_bad(&self, t: usize, bound: f64, max_area: Option<f64>) -> bool {
+        let [a, b, c] = self.triangles[t];
+        let (pa, pb, pc) = (self.points[a], self.points[b], self.points[c]);
+        let area = orient(pa, pb, pc) / 2.0;
+        if area <= 0.0 {
+            return false;
+        }
+        let lengths = [
+            (pb.0 - pc.0).hypot(pb.1 - pc.1),
+            (pc.0 - pa.0).hypot(pc.1 - pa.1),
+            (pa.0 - pb.0).hypot(pa.1 - pb.1),
+        ];
+        let radius = lengths[0] * lengths[1] * lengths[2] / (4.0 * area);
+        let shortest = lengths[0].min(lengths[1]).min(lengths[2]);
+        bound < radius / shortest || max_area.map_or(false, |max| max < area)
+    }
+
+    fn circumcenter(&self, t: usize) -> (f64, f64) {
+        let [a, b, c] = self.triangles[t];
+        circumcenter(self.points[a], self.points[b], self.points[c])
+    }
+
+    // the current slot of the triangle with these vertices
+    fn find_triangle(&self, [a, b, c]: [usize; 3]) -> Option<usize> {
+        self.triangles_around(a)
+            .into_iter()
+            .find(|t| self.triangles[*t].contains(&b) && self.triangles[*t].contains(&c))
+    }
+
+    // walks along the line from the center of t to q, the triangle containing q or the
+    // segment or convex hull edge in the way
+    fn walk(&self, mut t: usize, q: (f64, f64)) -> Result<usize, (usize, usize)> {
+        let [a, b, c] = self.triangles[t];
+        let (pa, pb, pc) = (self.points[a], self.points[b], self.points[c]);
+        let s = ((pa.0 + pb.0 + pc.0) / 3.0, (pa.1 + pb.1 + pc.1) / 3.0);
+        loop {
+            let behind = |i: &usize| {
+                let (a, b) = self.edge(t, *i);
+                self.orient(a, b, q) < 0.0
+            };
+            let crossed = |i: &usize| {
+                let (a, b) = self.edge(t, *i);
+                orient(s, q, self.points[a]) * orient(s, q, self.points[b]) <= 0.0
+            };
+            let exit = (0..3)
+                .find(|i| behind(i) && crossed(i))
+                .or_else(|| (0..3).find(behind));
+            match exit {
+                None => return Ok(t),
+                Some(i) if self.constrained[t][i] => return Err((t, i)),
+                Some(i) => match self.neighbours[t][i] {
+                    Some(u) => t = u,
+                    None => return Err((t, i)),
+                },
+            }
+        }
+    }
+
+    fn add_point(&mut self, q: (f64, f64)) -> usize {
+        self.points.push(q);
+        self.vertex_triangle.push(None);
+        self.canonical.push(self.points.len() - 1);
+        self.points.len() - 1
+    }
+
+    // constrains the segment from a towards b up to the first vertex on it, returns
+    // that vertex
+    fn constrain(&mut self, a: usize, b: usize) -> usize {
+        let around = self.triangles_around(a);
+        let (pa, pb) = (self.points[a], self.points[b]);
+        for t in &around {
+            let i = self.index(*t, a);
+            for k in 1..3 {
+                let v = self.triangles[*t][(i + k) % 3];
+                let pv = self.points[v];
+                let ahead = 0.0 < (pv.0 - pa.0) * (pb.0 - pa.0) + (pv.1 - pa.1) * (pb.1 - pa.1);
+                if v == b || (self.orient(a, v, pb) == 0.0 && ahead) {
+                    self.mark(a, v);
+                    return v;
+                }
+            }
+        }
+
+        // the triangle at a the segment leaves through the opposite edge
+        let start = around.into_iter().find(|t| {
+            let i = self.index(*t, a);
+            let (u, w) = (
+                self.triangles[*t][(i + 1) % 3],
+                self.triangles[*t][(i + 2) % 3],
+            );
+            0.0 < self.orient(a, u, pb) && self.orient(a, w, pb) < 0.0
+        });
+        let mut current = match start {
+            Some(t) => t,
+            // only reachable with inconsistent rounding, the segment is dropped
+            None => return b,
+        };
+        let i = self.index(current, a);
+        let (mut right, mut left) = (
+            self.triangles[current][(i + 1) % 3],
+            self.triangles[current][(i + 2) % 3],
+        );
+        let mut crossed = vec![current];
+        let (mut right_chain, mut left_chain) = (vec![right], vec![left]);
+        let end = loop {
+            let i = self.opposite(current, right, left);
+            if self.constrained[current][i] {
+                // the constraints cross, both are split at the intersection
+                let (pr, pl) = (self.points[right], self.points[left]);
+                let s = orient(pa, pb, pr) / (orient(pa, pb, pr) - orient(pa, pb, pl));
+                let q = (pr.0 + s * (pl.0 - pr.0), pr.1 + s * (pl.1 - pr.1));
+                let p = self.add_point(q);
+                self.split_edge(current, i, p);
+                self.insert_constraint(a, p);
+                return p;
+            }
+            let next = self.neighbours[current][i].unwrap();
+            let x = self.triangles[next][self.opposite(next, left, right)];
+            crossed.push(next);
+            if x == b {
+                break b;
+            }
+            let side = self.orient(a, b, self.points[x]);
+            if side == 0.0 {
+                break x;
+            } else if 0.0 < side {
+                left_chain.push(x);
+                left = x;
+            } else {
+                right_chain.push(x);
+                right = x;
+            }
+            current = next;
+        };
+
+        let mut new = Vec::with_capacity(crossed.len());
+        self.pseudo_polygon(a, end, &left_chain, &mut new);
+        self.pseudo_polygon(a, end, &right_chain, &mut new);
+        self.replace(&crossed, &new, &[(a, end)]);
+        end
+    }
+
+    // triangulates the polygon between the segment from p to q and the chain of vertices
+    // on one side of it, ordered from p to q
+    fn pseudo_polygon(&self, p: usize, q: usize, chain: &[usize], out: &mut Vec<[usize; 3]>) {
+        if chain.is_empty() {
+            return;
+        }
+        // the vertex whose circle with p and q contains no other vertex of the chain
+        let mut c = 0;
+        for v in 1..chain.len() {
+            if self.in_circle(self.oriented([p, q, chain[c]]), chain[v]) {
+                c = v;
+            }
+        }
+        self.pseudo_polygon(p, chain[c], &chain[..c], out);
+        self.pseudo_polygon(chain[c], q, &chain[c + 1..], out);
+        out.push(self.oriented([p, q, chain[c]]));
+    }
+
+    fn split_edge(&mut self, t: usize, i: usize, p: usize) {
+        let triangle = self.triangles[t];
+        let (a, b, c) = (triangle[i], triangle[(i + 1) % 3], triangle[(i + 2) % 3]);
+        let mut old = vec![t];
+        let mut new = vec![[p, a, b], [p, c, a]];
+        if let Some(u) = self.neighbours[t][i] {
+            let d = self.triangles[u][self.opposite(u, c, b)];
+            old.push(u);
+            new.push([p, d, c]);
+            new.push([p, b, d]);
+        }
+        let marks = if self.constrained[t][i] {
+            vec![(b, p), (p, c)]
+        } else {
+            Vec::new()
+        };
+        let new = self.replace(&old, &new, &marks);
+        self.legalize(p, new);
+    }
+
+    // flips the edges opposite of p while they are not Delaunay and not constrained
+    fn legalize(&mut self, p: usize, mut stack: Vec<usize>) {
+        while let Some(t) = stack.pop() {
+            let triangle = self.triangles[t];
+            let i = match triangle.iter().position(|v| *v == p) {
+                Some(i) => i,
+                None => continue,
+            };
+            if self.constrained[t][i] {
+                continue;
+            }
+            let u = match self.neighbours[t][i] {
+                Some(u) => u,
+                None => continue,
+            };
+            let (b, c) = (triangle[(i + 1) % 3], triangle[(i + 2) % 3]);
+            let d = self.triangles[u][self.opposite(u, c, b)];
+            if self.in_circle([p, b, c], d) {
+                stack.extend(self.replace(&[t, u], &[[p, b, d], [p, d, c]], &[]));
+            }
+        }
+    }
+
+    // replaces the old triangles with new ones covering the same region and returns
+    // their slots, the marked edges become constrained
+    fn replace(
+        &mut self,
+        old: &[usize],
+        new: &[[usize; 3]],
+        marks: &[(usize, usize)],
+    ) -> Vec<usize> {
+        // edges on the boundary of the region with the triangle outside of it
+        let mut boundary = HashMap::new();
+        for t in old {
+            for i in 0..3 {
+                let outside = self.neighbours[*t][i].filter(|u| !old.contains(u));
+                if outside.is_some() || self.neighbours[*t][i].is_none() {
+                    boundary.insert(self.edge(*t, i), (outside, self.constrained[*t][i]));
+                }
+            }
+        }
+        let mut slots = old.to_vec();
+        while slots.len() < new.len() {
+            self.triangles.push([0; 3]);
+            self.neighbours.push([None; 3]);
+            self.constrained.push([false; 3]);
+            slots.push(self.triangles.len() - 1);
+        }
+        let mut edges = HashMap::new();
+        for (k, triangle) in new.iter().enumerate() {
+            self.triangles[slots[k]] = *triangle;
+            for i in 0..3 {
+                edges.insert(self.edge(slots[k], i), slots[k]);
+            }
+        }
+
+        for t in &slots {
+            for i in 0..3 {
+                let (x, y) = self.edge(*t, i);
+                let marked = marks.contains(&(x, y)) || marks.contains(&(y, x));
+                if let Some(u) = edges.get(&(y, x)) {
+                    self.neighbours[*t][i] = Some(*u);
+                    self.constrained[*t][i] = marked;
+                } else {
+                    // halves of a split convex hull edge are not on the old boundary
+                    let (outside, constrained) =
+                        boundary.get(&(x, y)).cloned().unwrap_or((None, false));
+                    self.neighbours[*t][i] = outside;
+                    self.constrained[*t][i] = constrained || marked;
+                    if let Some(o) = outside {
+                        let j = self.opposite(o, y, x);
+                        self.neighbours[o][j] = Some(*t);
+                    }
+                }
+            }
+            for v in &self.triangles[*t] {
+                self.vertex_triangle[*v] = Some(*t);
+            }
+        }
+        slots
+    }
+
+    // walks towards q, None if it lies outside of the convex hull
+    fn locate(&self, q: (f64, f64)) -> Option<usize> {
+        let mut t = self.vertex_triangle.iter().rev().find_map(|t| *t)?;
+        // the first edge tried changes every step, so the walk cannot cycle
+        let mut step = 0;
+        'walk: loop {
+            step += 1;
+            for k in 0..3 {
+                let i = (k + step) % 3;
+                let (a, b) = self.edge(t, i);
+                if self.orient(a, b, q) < 0.0 {
+                    t = self.neighbours[t][i]?;
+                    continue 'walk;
+                }
+            }
+            return Some(t);
+        }
+    }
+
+    fn triangles_around(&self, v: usize) -> Vec<usize> {
+        let mut around = Vec::new();
+        let mut stack: Vec<usize> = self.vertex_triangle[v].into_iter().collect();
+        while let Some(t) = stack.pop() {
+            if around.contains(&t) || !self.triangles[t].contains(&v) {
+                continue;
+            }
+            around.push(t);
+            stack.extend(self.neighbours[t].iter().filter_map(|n| *n));
+        }
+        around
+    }
+
+    fn mark(&mut self, a: usize, b: usize) {
+        for t in self.triangles_around(a) {
+            for i in 0..3 {
+                let (x, y) = self.edge(t, i);
+                if (x, y) == (a, b) || (x, y) == (b, a) {
+                    self.constrained[t][i] = true;
+                }
+            }
+        }
+    }
+
+    // the edge opposite of vertex i, directed counter clockwise
+    fn edge(&self, t: usize, i: usize) -> (usize, usize) {
+        (
+            self.triangles[t][(i + 1) % 3],
+            self.triangles[t][(i + 2) % 3],
+        )
+    }
+
+    fn index(&self, t: usize, v: usize) -> usize {
+        self.triangles[t].iter().position(|x| *x == v).unwrap()
+    }
+
+    // the index of the vertex opposite of the edge from a to b
+    fn opposite(&self, t: usize, a: usize, b: usize) -> usize {
+        (0..3).find(|i| self.edge(t, *i) == (a, b)).unwrap()
+    }
+
+    fn orient(&self, a: usize, b: usize, q: (f64, f64)) -> f64 {
+        orient(self.points[a], self.points[b], q)
+    }
+
+    fn oriented(&self, [a, b, c]: [usize; 3]) -> [usize; 3] {
+        if self.orient(a, b, self.points[c]) < 0.0 {
+            [b, a, c]
+        } else {
+            [a, b, c]
+        }
+    }
+
+    // whether d lies strictly inside the circumcircle of the counter clockwise triangle
+    fn in_circle(&self, [a, b, c]: [usize; 3], d: usize) -> bool {
+        let p = &self.points;
+        in_circle(p[a], p[b], p[c], p[d])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{random_points, signed_area};
+
+    fn area(mesh: &Mesh) -> f64 {
+        (0..mesh.len())
+            .map(|t| signed_area(&mesh.triangle(t)))
+            .sum()
+    }
+
+    // the constraints are covered by constrained edges, every other edge is Delaunay
+    fn check(cdt: &Cdt, constraints: &[(usize, usize)]) {
+        let points = &cdt.points;
+        for (a, b) in constraints {
+            let (pa, pb) = (points[*a], points[*b]);
+            let length = (pb.0 - pa.0).hypot(pb.1 - pa.1);
+            let on_segment = |v: usize| {
+                let p = points[v];
+                orient(pa, pb, p).abs() <= 1e-6 * length * length
+                    && -1e-6 <= (p.0 - pa.0) * (pb.0 - pa.0) + (p.1 - pa.1) * (pb.1 - pa.1)
+                    && -1e-6 <= (p.0 - pb.0) * (pa.0 - pb.0) + (p.1 - pb.1) * (pa.1 - pb.1)
+            };
+            let covered: f64 = cdt
+                .constrained_edges()
+                .into_iter()
+                .filter(|(x, y)| on_segment(*x) && on_segment(*y))
+                .map(|(x, y)| (points[y].0 - points[x].0).hypot(points[y].1 - points[x].1))
+                .sum();
+            assert!((covered - length).abs() < 1e-6 * length, "{} {}", a, b);
+        }
+        for t in 0..cdt.len() {
+            let triangle = cdt.triangles[t];
+            assert!(0.0 < cdt.orient(triangle[0], triangle[1], points[triangle[2]]));
+            for i in 0..3 {
+                if let Some(u) = cdt.neighbours[t][i] {
+                    let (x, y) = cdt.edge(t, i);
+                    let j = cdt.opposite(u, y, x);
+                    assert_eq!(cdt.constrained[t][i], cdt.constrained[u][j]);
+                    if !cdt.constrained[t][i] {
+                        assert!(!strictly_inside(cdt, triangle, cdt.triangles[u][j]));
+                    }
+                }
+            }
+        }
+    }
+
+    // inside the circumcircle by more than rounding
+    fn strictly_inside(cdt: &Cdt, [a, b, c]: [usize; 3], d: usize) -> bool {
+        let p = |v: usize| cdt.points[v];
+        let (a, b, c, d) = (p(a), p(b), p(c), p(d));
+        let (b, c, d) = (
+            (b.0 - a.0, b.1 - a.1),
+            (c.0 - a.0, c.1 - a.1),
+            (d.0 - a.0, d.1 - a.1),
+        );
+        let det = 2.0 * (b.0 * c.1 - b.1 * c.0);
+        let (sb, sc) = (b.0 * b.0 + b.1 * b.1, c.0 * c.0 + c.1 * c.1);
+        let center = ((c.1 * sb - b.1 * sc) / det, (b.0 * sc - c.0 * sb) / det);
+        let radius = center.0.hypot(center.1);
+        (d.0 - center.0).hypot(d.1 - center.1) < radius * (1.0 - 1e-9)
+    }
+
+    #[test]
+    fn crossing_constraints_are_enforced() {
+        let points = random_points(300, 11);
+        // long chords crossing each other many times
+        let constraints: Vec<(usize, usize)> = (0..12).map(|i| (i, 150 + i)).collect();
+        let mut cdt = Cdt::with_constraints(&points, &constraints);
+        check(&cdt, &constraints);
+        assert!(points.len() < cdt.points.len());
+        let hull = area(&crate::triangulation::delaunay(&points));
+        assert!((area(&cdt.mesh()) - hull).abs() < 1e-6 * hull);
+
+        // points inserted later respect the constraints
+        for p in random_points(100, 12) {
+            cdt.insert_point(p);
+        }
+        check(&cdt, &constraints);
+        assert!((area(&cdt.mesh()) - hull).abs() < 1e-6 * hull);
+    }
+
+    #[test]
+    fn holes_are_removed() {
+        let mut points = vec![
+            Point2::new(0.0, 0.0),
+            Point2::new(100.0, 0.0),
+            Point2::new(100.0, 100.0),
+            Point2::new(0.0, 100.0),
+            Point2::new(40.0, 40.0),
+            Point2::new(60.0, 40.0),
+            Point2::new(60.0, 60.0),
+            Point2::new(40.0, 60.0),
+            // outside of the domain but inside the hull
+            Point2::new(50.0, 150.0),
+            // on the hole boundary
+            Point2::new(50.0, 40.0),
+        ];
+        points.extend(
+            random_points(50, 4)
+                .iter()
+                .map(|p| Point2::new(p.x / 10.0, p.y / 10.0)),
+        );
+        let constraints = [
+            (0, 1),
+            (1, 2),
+            (2, 3),
+            (3, 0),
+            (4, 5),
+            (5, 6),
+            (6, 7),
+            (7, 4),
+        ];
+        let cdt = Cdt::with_constraints(&points, &constraints);
+        check(&cdt, &constraints);
+        let domain = cdt.mesh_with_holes(&[Point2::new(50.0, 50.0)], true);
+        assert!((area(&domain) - (100.0 * 100.0 - 20.0 * 20.0)).abs() < 1e-3);
+        assert!((area(&cdt.mesh()) - (100.0 * 100.0 + 50.0 * 100.0 / 2.0)).abs() < 1e-3);
+        assert!((area(&cdt.mesh_even_odd()) - area(&domain)).abs() < 1e-3);
+    }
+
+    #[test]
+    fn nested_loops_alternate() {
+        // squares of size 100, 60 and 20 around the same center plus a loose segment
+        let mut points = Vec::new();
+        let mut constraints = Vec::new();
+        for half in &[50.0f32, 30.0, 10.0] {
+            let first = points.len();
+            for (x, y) in &[(-1.0f32, -1.0f32), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)] {
+                points.push(Point2::new(50.0 + x * half, 50.0 + y * half));
+            }
+            for i in 0..4 {
+                constraints.push((first + i, first + (i + 1) % 4));
+            }
+        }
+        points.push(Point2::new(5.0, 5.0));
+        points.push(Point2::new(15.0, 10.0));
+        constraints.push((12, 13));
+        points.push(Point2::new(50.0, 150.0));
+
+        let cdt = Cdt::with_constraints(&points, &constraints);
+        check(&cdt, &constraints);
+        let mesh = cdt.mesh_even_odd();
+        let expected = 100.0 * 100.0 - 60.0 * 60.0 + 20.0 * 20.0;
+        assert!((area(&mesh) - expected).abs() < 1e-3, "{}", area(&mesh));
+    }
+}
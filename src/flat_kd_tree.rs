@@ -0,0 +1,300 @@
+use crate::kd_tree::Orientation;
+
+// ranges with at most this many points are not split any further
+const BUCKET_SIZE: usize = 8;
+// enough for any tree that fits into memory
+const MAX_DEPTH: usize = 64;
+
+// Static 2d-tree stored in two flat arrays instead of boxed nodes.
+// The inner nodes use an implicit layout: node i has the children 2i+1 and 2i+2
+// and covers a range of `points` that follows from halving the parent range,
+// so neither child pointers nor point ranges have to be stored.
+#[derive(Clone, Debug)]
+pub struct FlatKdTree {
+    // points reordered so every leaf bucket is a contiguous range
+    points: Vec<(f32, f32)>,
+    // split value of every inner node, NaN for slots that are leaves
+    splits: Vec<f32>,
+    orientation_even: Orientation,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Cell {
+    node: usize,
+    lo: usize,
+    hi: usize,
+    orientation: Orientation,
+}
+
+impl FlatKdTree {
+    pub fn new(points: &[(f32, f32)]) -> Self {
+        Self::with_orientation_even(points, Orientation::Horizontal)
+    }
+
+    // like KdTree equal points are kept once, so both report the same points
+    pub fn with_orientation_even(points: &[(f32, f32)], orientation_even: Orientation) -> Self {
+        let mut points = points.to_vec();
+        points.sort_unstable_by(|a, b| a.partial_cmp(b).unwrap());
+        points.dedup();
+
+        // every level halves the range, the larger half has ceil(len / 2) points
+        let mut levels = 0;
+        let mut len = points.len();
+        while BUCKET_SIZE < len {
+            len -= len / 2;
+            levels += 1;
+        }
+        let mut splits = vec![std::f32::NAN; (1 << levels) - 1];
+
+        let len = points.len();
+        Self::construct(&mut points, &mut splits, 0, 0, len, orientation_even);
+        FlatKdTree {
+            points,
+            splits,
+            orientation_even,
+        }
+    }
+
+    fn construct(
+        points: &mut [(f32, f32)],
+        splits: &mut [f32],
+        node: usize,
+        lo: usize,
+        hi: usize,
+        orientation: Orientation,
+    ) {
+        if hi - lo <= BUCKET_SIZE {
+            return;
+        }
+        let range = &mut points[lo..hi];
+        match orientation {
+            Orientation::Horizontal => {
+                range.sort_unstable_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
+            }
+            Orientation::Vertical => {
+                range.sort_unstable_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+            }
+        }
+        let mid = lo + (hi - lo) / 2;
+        splits[node] = coord(points[mid], orientation);
+
+        Self::construct(points, splits, 2 * node + 1, lo, mid, orientation.other());
+        Self::construct(points, splits, 2 * node + 2, mid, hi, orientation.other());
+    }
+
+    pub fn len(&self) -> usize {
+        self.points.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.points.is_empty()
+    }
+
+    pub fn range_query(&self, min: (f32, f32), max: (f32, f32)) -> Vec<(f32, f32)> {
+        let mut v = Vec::new();
+        self.range_visit(min, max, |p| v.push(p));
+        v
+    }
+
+    // calls visit for every point inside of the rectangle without allocating
+    pub fn range_visit<F: FnMut((f32, f32))>(
+        &self,
+        min: (f32, f32),
+        max: (f32, f32),
+        mut visit: F,
+    ) {
+        for point in self.range_iter(min, max) {
+            visit(point);
+        }
+    }
+
+    pub fn range_iter(&self, min: (f32, f32), max: (f32, f32)) -> RangeIter {
+        let mut iter = RangeIter {
+            tree: self,
+            min,
+            max,
+            stack: [Cell {
+                node: 0,
+                lo: 0,
+                hi: 0,
+                orientation: self.orientation_even,
+            }; MAX_DEPTH],
+            stack_len: 0,
+            bucket: 0..0,
+        };
+        if !self.points.is_empty() {
+            iter.stack[0].hi = self.points.len();
+            iter.stack_len = 1;
+        }
+        iter
+    }
+}
+
+pub struct RangeIter<'a> {
+    tree: &'a FlatKdTree,
+    min: (f32, f32),
+    max: (f32, f32),
+    stack: [Cell; MAX_DEPTH],
+    stack_len: usize,
+    // remaining points of the leaf bucket that is currently reported
+    bucket: std::ops::Range<usize>,
+}
+
+impl<'a> Iterator for RangeIter<'a> {
+    type Item = (f32, f32);
+
+    fn next(&mut self) -> Option<(f32, f32)> {
+        loop {
+            while let Some(i) = self.bucket.next() {
+                let p = self.tree.points[i];
+                if self.min.0 <= p.0 && p.0 <= self.max.0 && self.min.1 <= p.1 && p.1 <= self.max.1
+                {
+                    return Some(p);
+                }
+            }
+
+            if self.stack_len == 0 {
+                return None;
+            }
+            self.stack_len -= 1;
+            let cell = self.stack[self.stack_len];
+
+            if cell.hi - cell.lo <= BUCKET_SIZE {
+                self.bucket = cell.lo..cell.hi;
+                continue;
+            }
+
+            let split = self.tree.splits[cell.node];
+            let mid = cell.lo + (cell.hi - cell.lo) / 2;
+            let (min, max) = (
+                coord(self.min, cell.orientation),
+                coord(self.max, cell.orientation),
+            );
+            let orientation = cell.orientation.other();
+            if split <= max {
+                //right is inside
+                self.stack[self.stack_len] = Cell {
+                    node: 2 * cell.node + 2,
+                    lo: mid,
+                    hi: cell.hi,
+                    orientation,
+                };
+                self.stack_len += 1;
+            }
+            if min <= split {
+                //left is inside
+                self.stack[self.stack_len] = Cell {
+                    node: 2 * cell.node + 1,
+                    lo: cell.lo,
+                    hi: mid,
+                    orientation,
+                };
+                self.stack_len += 1;
+            }
+        }
+    }
+}
+
+fn coord(point: (f32, f32), orientation: Orientation) -> f32 {
+    match orientation {
+        Orientation::Horizontal => point.1,
+        Orientation::Vertical => point.0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kd_tree::KdTree;
+    use crate::test_util::{random_coordinates, random_lattice, sorted};
+
+    #[test]
+    fn empty_tree_reports_nothing() {
+        let tree = FlatKdTree::new(&[]);
+        assert!(tree.is_empty());
+        assert_eq!(tree.range_query((0.0, 0.0), (10.0, 10.0)), Vec::new());
+    }
+
+    #[test]
+    fn range_query_matches_kd_tree() {
+        let points = random_coordinates(5000, 3);
+        let flat = FlatKdTree::new(&points);
+        let tree = KdTree::new(&points);
+        for window in random_coordinates(40, 5).chunks(2) {
+            let min = (window[0].0.min(window[1].0), window[0].1.min(window[1].1));
+            let max = (window[0].0.max(window[1].0), window[0].1.max(window[1].1));
+            assert_eq!(
+                sorted(flat.range_query(min, max)),
+                sorted(tree.range_query(min, max))
+            );
+        }
+    }
+
+    #[test]
+    fn equal_points_are_kept_once() {
+        let mut points = random_lattice(2000, 9);
+        points.extend_from_slice(&points[..500].to_vec());
+        let flat = FlatKdTree::new(&points);
+        let tree = KdTree::new(&points);
+        assert_eq!(flat.len(), tree.len());
+        for window in random_coordinates(40, 11).chunks(2) {
+            let min = (window[0].0.min(window[1].0), window[0].1.min(window[1].1));
+            let max = (window[0].0.max(window[1].0), window[0].1.max(window[1].1));
+            assert_eq!(
+                sorted(flat.range_query(min, max)),
+                sorted(tree.range_query(min, max))
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod benches {
+    use super::*;
+    use crate::kd_tree::KdTree;
+    use crate::test_util::random_coordinates;
+    use test::{black_box, Bencher};
+
+    const POINTS: usize = 100_000;
+
+    fn windows() -> Vec<((f32, f32), (f32, f32))> {
+        random_coordinates(200, 17)
+            .iter()
+            .map(|&(x, y)| ((x, y), (x + 50.0, y + 50.0)))
+            .collect()
+    }
+
+    #[bench]
+    fn range_query_boxed(b: &mut Bencher) {
+        let tree = KdTree::new(&random_coordinates(POINTS, 1));
+        let windows = windows();
+        b.iter(|| {
+            for &(min, max) in &windows {
+                black_box(tree.range_query(min, max).len());
+            }
+        });
+    }
+
+    #[bench]
+    fn range_query_flat(b: &mut Bencher) {
+        let tree = FlatKdTree::new(&random_coordinates(POINTS, 1));
+        let windows = windows();
+        b.iter(|| {
+            for &(min, max) in &windows {
+                black_box(tree.range_iter(min, max).count());
+            }
+        });
+    }
+
+    #[bench]
+    fn build_boxed(b: &mut Bencher) {
+        let points = random_coordinates(POINTS / 10, 1);
+        b.iter(|| black_box(KdTree::new(&points).len()));
+    }
+
+    #[bench]
+    fn build_flat(b: &mut Bencher) {
+        let points = random_coordinates(POINTS / 10, 1);
+        b.iter(|| black_box(FlatKdTree::new(&points).len()));
+    }
+}
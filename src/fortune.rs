@@ -0,0 +1,721 @@
+use crate::kd_tree::Bounds;
+use crate::math::circumcenter;
+use crate::voronoi;
+use ggez::graphics::Point2;
+
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+// Fortune's sweep line algorithm for the Voronoi diagram.
+// The sweep line moves in direction of increasing y, downwards on screen. Behind it the
+// diagram is final up to the beach line: for every x the parabola of the site that is
+// as far away from the sweep line as from the point on the parabola. The breakpoints
+// between the parabola arcs trace the Voronoi edges. A site event splits the arc above
+// the new site, a circle event removes an arc that shrank to a point, a Voronoi vertex.
+//
+// Events come from a heap and arcs are found by binary search, O(log n) each, but the
+// beach line is a Vec: inserting or removing an arc shifts the ones behind it, so the
+// sweep is O(n^2) in the worst case. For uniformly distributed sites the beach line holds
+// about sqrt(n) arcs, there the shifts are cheap next to the rest of the work.
+//
+// Like the scan line in intersection.rs the state between events can be inspected, so
+// the sweep can be animated step by step.
+#[derive(Clone, Debug)]
+pub struct Fortune {
+    points: Vec<Point2>,
+    sites: Vec<(f64, f64)>,
+    // site indices by y then x, of equal sites only the first
+    order: Vec<usize>,
+    next_site: usize,
+    events: BinaryHeap<CircleEvent>,
+    valid: Vec<bool>,
+    // the beach line from left to right
+    arcs: Vec<Arc>,
+    arc_ids: usize,
+    traces: Vec<Trace>,
+    vertices: Vec<(f64, f64)>,
+    sweep: f64,
+}
+
+#[derive(Clone, Debug)]
+struct Arc {
+    id: usize,
+    site: usize,
+    // the circle event that removes this arc
+    event: Option<usize>,
+    // edge and end traced by the breakpoint to the next arc
+    edge: Option<(usize, usize)>,
+}
+
+// a Voronoi edge while it is traced, ends without vertex go to infinity
+#[derive(Clone, Debug)]
+struct Trace {
+    sites: (usize, usize),
+    vertices: [Option<usize>; 2],
+    // edges between sites on the first row start at y = -infinity with end 0
+    first_row: bool,
+}
+
+#[derive(Clone, Debug)]
+struct CircleEvent {
+    // lowest point of the circle, reached by the sweep line when the arc vanishes
+    y: f64,
+    center: (f64, f64),
+    arc: usize,
+    id: usize,
+}
+
+impl PartialEq for CircleEvent {
+    fn eq(&self, other: &Self) -> bool {
+        self.y == other.y && self.center.0 == other.center.0
+    }
+}
+
+impl Eq for CircleEvent {}
+
+impl PartialOrd for CircleEvent {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for CircleEvent {
+    // reversed, the heap pops the topmost event first
+    fn cmp(&self, other: &Self) -> Ordering {
+        (other.y, other.center.0)
+            .partial_cmp(&(self.y, self.center.0))
+            .unwrap()
+    }
+}
+
+impl Fortune {
+    pub fn new(points: &[Point2]) -> Self {
+        let sites: Vec<(f64, f64)> = points
+            .iter()
+            .map(|p| (f64::from(p.x), f64::from(p.y)))
+            .collect();
+        let mut order: Vec<usize> = (0..sites.len()).collect();
+        order.sort_by(|a, b| {
+            let (a, b) = (sites[*a], sites[*b]);
+            (a.1, a.0).partial_cmp(&(b.1, b.0)).unwrap()
+        });
+        order.dedup_by(|a, b| sites[*a] == sites[*b]);
+
+        Fortune {
+            points: points.to_vec(),
+            sites,
+            order,
+            next_site: 0,
+            events: BinaryHeap::new(),
+            valid: Vec::new(),
+            arcs: Vec::new(),
+            arc_ids: 0,
+            traces: Vec::new(),
+            vertices: Vec::new(),
+            sweep: std::f64::NEG_INFINITY,
+        }
+    }
+
+    // the position of the sweep line, the y of the last event
+    pub fn sweep(&self) -> f32 {
+        self.sweep as f32
+    }
+
+    // the y of the next event, None when the sweep is done
+    pub fn next_event(&mut self) -> Option<f32> {
+        self.discard_invalid();
+        let site = self.order.get(self.next_site).map(|s| self.sites[*s].1);
+        let circle = self.events.peek().map(|e| e.y);
+        match (site, circle) {
+            (Some(site), Some(circle)) => Some(site.min(circle) as f32),
+            (site, circle) => site.or(circle).map(|y| y as f32),
+        }
+    }
+
+    pub fn is_done(&mut self) -> bool {
+        self.next_event().is_none()
+    }
+
+    // handles the next event, false when there is none
+    pub fn step(&mut self) -> bool {
+        self.discard_invalid();
+        let site = self.order.get(self.next_site).cloned();
+        let circle_first = match (site, self.events.peek()) {
+            (_, None) => false,
+            (None, Some(_)) => true,
+            (Some(site), Some(event)) => event.y <= self.sites[site].1,
+        };
+        if circle_first {
+            let event = self.events.pop().unwrap();
+            self.circle_event(event);
+        } else if let Some(site) = site {
+            self.next_site += 1;
+            self.site_event(site);
+        } else {
+            return false;
+        }
+        true
+    }
+
+    // runs the sweep to the end
+    pub fn finish(mut self) -> Diagram {
+        while self.step() {}
+        self.diagram()
+    }
+
+    fn discard_invalid(&mut self) {
+        while let Some(event) = self.events.peek() {
+            if self.valid[event.id] {
+                return;
+            }
+            self.events.pop();
+        }
+    }
+
+    fn site_event(&mut self, site: usize) {
+        let (x, y) = self.sites[site];
+        self.sweep = y;
+        if self.arcs.is_empty() {
+            self.push_arc(0, site, None);
+            return;
+        }
+
+        let i = self.locate(x, y);
+        let above = self.arcs[i].site;
+        if self.sites[above].1 == y {
+            // only sites of the first row are on the sweep line, their arcs are vertical
+            // lines ordered by x, so the new site comes last
+            let last = self.arcs.len() - 1;
+            let trace = self.add_trace((self.arcs[last].site, site), true);
+            self.arcs[last].edge = Some((trace, 1));
+            self.push_arc(last + 1, site, None);
+            return;
+        }
+
+        // the arc above is split, the new arc grows from a point between its halves
+        self.invalidate(i);
+        let trace = self.add_trace((above, site), false);
+        let right_edge = self.arcs[i].edge;
+        self.arcs[i].edge = Some((trace, 0));
+        self.push_arc(i + 1, site, Some((trace, 1)));
+        self.push_arc(i + 2, above, right_edge);
+        self.check_circle(i);
+        self.check_circle(i + 2);
+    }
+
+    fn circle_event(&mut self, event: CircleEvent) {
+        self.sweep = event.y;
+        let j = self.position(event.arc, event.center.0, event.y);
+        self.vertices.push(event.center);
+        let vertex = self.vertices.len() - 1;
+
+        // both breakpoints of the vanishing arc end in the vertex, a new edge starts there
+        for edge in &[self.arcs[j - 1].edge, self.arcs[j].edge] {
+            if let Some((trace, end)) = *edge {
+                self.traces[trace].vertices[end] = Some(vertex);
+            }
+        }
+        self.invalidate(j - 1);
+        self.invalidate(j);
+        self.invalidate(j + 1);
+        let trace = self.add_trace((self.arcs[j - 1].site, self.arcs[j + 1].site), false);
+        self.traces[trace].vertices[0] = Some(vertex);
+        self.arcs[j - 1].edge = Some((trace, 1));
+        self.arcs.remove(j);
+        self.check_circle(j - 1);
+        self.check_circle(j);
+    }
+
+    fn push_arc(&mut self, i: usize, site: usize, edge: Option<(usize, usize)>) {
+        self.arcs.insert(
+            i,
+            Arc {
+                id: self.arc_ids,
+                site,
+                event: None,
+                edge,
+            },
+        );
+        self.arc_ids += 1;
+    }
+
+    fn add_trace(&mut self, sites: (usize, usize), first_row: bool) -> usize {
+        self.traces.push(Trace {
+            sites,
+            vertices: [None; 2],
+            first_row,
+        });
+        self.traces.len() - 1
+    }
+
+    fn invalidate(&mut self, i: usize) {
+        if let Some(event) = self.arcs[i].event.take() {
+            self.valid[event] = false;
+        }
+    }
+
+    // adds the circle event of arc i if its breakpoints converge
+    fn check_circle(&mut self, i: usize) {
+        if i == 0 || self.arcs.len() <= i + 1 {
+            return;
+        }
+        let (a, b, c) = (
+            self.sites[self.arcs[i - 1].site],
+            self.sites[self.arcs[i].site],
+            self.sites[self.arcs[i + 1].site],
+        );
+        if a == c || (b.0 - a.0) * (c.1 - a.1) - (b.1 - a.1) * (c.0 - a.0) <= 0.0 {
+            return;
+        }
+        let center = circumcenter(a, b, c);
+        let radius = (a.0 - center.0).hypot(a.1 - center.1);
+        let id = self.valid.len();
+        self.valid.push(true);
+        self.arcs[i].event = Some(id);
+        self.events.push(CircleEvent {
+            y: center.1 + radius,
+            center,
+            arc: self.arcs[i].id,
+            id,
+        });
+    }
+
+    // index of the arc above x
+    fn locate(&self, x: f64, directrix: f64) -> usize {
+        let (mut low, mut high) = (0, self.arcs.len() - 1);
+        while low < high {
+            let middle = (low + high) / 2;
+            if self.breakpoint(middle, directrix) < x {
+                low = middle + 1;
+            } else {
+                high = middle;
+            }
+        }
+        low
+    }
+
+    // index of the arc with the id, it is at x or close to it. Arcs shrunk to a point
+    // share their breakpoints, then the search goes on from there, in the worst case
+    // across the whole beach line.
+    fn position(&self, id: usize, x: f64, directrix: f64) -> usize {
+        let start = self.locate(x, directrix);
+        (0..self.arcs.len())
+            .flat_map(|k| vec![start + k, start.wrapping_sub(k)])
+            .find(|i| self.arcs.get(*i).map_or(false, |arc| arc.id == id))
+            .unwrap()
+    }
+
+    // x of the breakpoint between arc i and i + 1
+    fn breakpoint(&self, i: usize, directrix: f64) -> f64 {
+        let (p, q) = (
+            self.sites[self.arcs[i].site],
+            self.sites[self.arcs[i + 1].site],
+        );
+        if p.1 == q.1 {
+            return (p.0 + q.0) / 2.0;
+        }
+        if p.1 == directrix {
+            return p.0;
+        }
+        if q.1 == directrix {
+            return q.0;
+        }
+        // the parabolas intersect twice, the site closer to the sweep line has the
+        // narrower parabola, which is on top between the intersections
+        let (dp, dq) = (directrix - p.1, directrix - q.1);
+        let a = dp - dq;
+        let b = 2.0 * (dq * p.0 - dp * q.0);
+        let c = dp * q.0 * q.0 - dq * p.0 * p.0 + dp * dq * (p.1 - q.1);
+        let root = -0.5 * (b + b.signum() * (b * b - 4.0 * a * c).max(0.0).sqrt());
+        let (x1, x2) = (root / a, c / root);
+        if q.1 < p.1 {
+            x1.max(x2)
+        } else {
+            x1.min(x2)
+        }
+    }
+
+    fn breakpoint_point(&self, i: usize, directrix: f64) -> (f64, f64) {
+        let x = self.breakpoint(i, directrix);
+        let (p, q) = (
+            self.sites[self.arcs[i].site],
+            self.sites[self.arcs[i + 1].site],
+        );
+        if p.1 != directrix {
+            (x, parabola(p, directrix, x))
+        } else if q.1 != directrix {
+            (x, parabola(q, directrix, x))
+        } else {
+            (x, std::f64::NEG_INFINITY)
+        }
+    }
+
+    // the arcs of the beach line as site and x range, the directrix must not be above
+    // the sweep line
+    pub fn beach_line(&self, directrix: f32) -> Vec<(usize, f32, f32)> {
+        let directrix = f64::from(directrix).max(self.sweep);
+        (0..self.arcs.len())
+            .map(|i| {
+                let from = if i == 0 {
+                    std::f64::NEG_INFINITY
+                } else {
+                    self.breakpoint(i - 1, directrix)
+                };
+                let to = if i + 1 == self.arcs.len() {
+                    std::f64::INFINITY
+                } else {
+                    self.breakpoint(i, directrix)
+                };
+                (self.arcs[i].site, from as f32, to as f32)
+            })
+            .collect()
+    }
+
+    // the parabola of site for the directrix at x
+    pub fn parabola(&self, site: usize, directrix: f32, x: f32) -> f32 {
+        parabola(self.sites[site], f64::from(directrix), f64::from(x)) as f32
+    }
+
+    // the edges found so far, open ones end at their breakpoint on the beach line for
+    // the directrix, edges of the first row start far above
+    pub fn edges(&self, directrix: f32) -> Vec<(Point2, Point2)> {
+        let directrix = f64::from(directrix).max(self.sweep);
+        let mut ends: Vec<[Option<(f64, f64)>; 2]> = self
+            .traces
+            .iter()
+            .map(|trace| {
+                let vertex = |end: usize| trace.vertices[end].map(|v| self.vertices[v]);
+                let mut ends = [vertex(0), vertex(1)];
+                if trace.first_row && ends[0].is_none() {
+                    let (p, q) = (self.sites[trace.sites.0], self.sites[trace.sites.1]);
+                    ends[0] = Some(((p.0 + q.0) / 2.0, p.1 - 1e5));
+                }
+                ends
+            })
+            .collect();
+        for i in 0..self.arcs.len().saturating_sub(1) {
+            if let Some((trace, end)) = self.arcs[i].edge {
+                let point = self.breakpoint_point(i, directrix);
+                if point.1.is_finite() {
+                    ends[trace][end] = Some(point);
+                }
+            }
+        }
+        ends.into_iter()
+            .filter_map(|ends| match ends {
+                [Some(a), Some(b)] => Some((
+                    Point2::new(a.0 as f32, a.1 as f32),
+                    Point2::new(b.0 as f32, b.1 as f32),
+                )),
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub fn vertices(&self) -> Vec<Point2> {
+        self.vertices
+            .iter()
+            .map(|v| Point2::new(v.0 as f32, v.1 as f32))
+            .collect()
+    }
+
+    // center and radius of the pending circle events
+    pub fn circle_events(&self) -> Vec<(Point2, f32)> {
+        self.events
+            .iter()
+            .filter(|e| self.valid[e.id])
+            .map(|e| {
+                let center = Point2::new(e.center.0 as f32, e.center.1 as f32);
+                (center, (e.y - e.center.1) as f32)
+            })
+            .collect()
+    }
+
+    fn diagram(&self) -> Diagram {
+        // directions of the ends that go to infinity, breakpoints that are still on the
+        // beach line move away from their vertex from here on
+        let mut rays: Vec<[Option<(f64, f64)>; 2]> = vec![[None; 2]; self.traces.len()];
+        for i in 0..self.arcs.len().saturating_sub(1) {
+            if let Some((trace, end)) = self.arcs[i].edge {
+                let (a, b) = (
+                    self.breakpoint_point(i, self.sweep + 1.0),
+                    self.breakpoint_point(i, self.sweep + 2.0),
+                );
+                rays[trace][end] = Some(if a.1.is_finite() {
+                    (b.0 - a.0, b.1 - a.1)
+                } else {
+                    (0.0, 1.0)
+                });
+            }
+        }
+
+        let mut half_edges = Vec::with_capacity(2 * self.traces.len());
+        for (t, trace) in self.traces.iter().enumerate() {
+            if trace.first_row {
+                rays[t][0] = Some((0.0, -1.0));
+            }
+            let (p, q) = (self.sites[trace.sites.0], self.sites[trace.sites.1]);
+            let middle = ((p.0 + q.0) / 2.0, (p.1 + q.1) / 2.0);
+            // along direction the site p is on the left, counter clockwise in a y-up system
+            let direction = (p.1 - q.1, q.0 - p.0);
+            let parameter = |end: usize| match (trace.vertices[end], rays[t][end]) {
+                (Some(v), _) => {
+                    let v = self.vertices[v];
+                    (v.0 - middle.0) * direction.0 + (v.1 - middle.1) * direction.1
+                }
+                (None, Some(ray)) if 0.0 < ray.0 * direction.0 + ray.1 * direction.1 => {
+                    std::f64::INFINITY
+                }
+                _ => std::f64::NEG_INFINITY,
+            };
+            let (from, to) = if parameter(0) <= parameter(1) {
+                (trace.vertices[0], trace.vertices[1])
+            } else {
+                (trace.vertices[1], trace.vertices[0])
+            };
+            let length = direction.0.hypot(direction.1);
+            let unit = ((direction.0 / length) as f32, (direction.1 / length) as f32);
+            let e = half_edges.len();
+            half_edges.push(HalfEdge {
+                site: trace.sites.0,
+                origin: from,
+                twin: e + 1,
+                next: None,
+                prev: None,
+                direction: unit,
+            });
+            half_edges.push(HalfEdge {
+                site: trace.sites.1,
+                origin: to,
+                twin: e,
+                next: None,
+                prev: None,
+                direction: (-unit.0, -unit.1),
+            });
+        }
+
+        let mut cells = vec![Vec::new(); self.sites.len()];
+        for (e, half_edge) in half_edges.iter().enumerate() {
+            cells[half_edge.site].push(e);
+        }
+        for cell in &mut cells {
+            for &e in cell.iter() {
+                let destination = half_edges[half_edges[e].twin].origin;
+                if destination.is_none() {
+                    continue;
+                }
+                if let Some(&next) = cell.iter().find(|f| half_edges[**f].origin == destination) {
+                    half_edges[e].next = Some(next);
+                    half_edges[next].prev = Some(e);
+                }
+            }
+            // counter clockwise from the first edge coming from infinity
+            let start = cell
+                .iter()
+                .cloned()
+                .find(|e| half_edges[*e].prev.is_none())
+                .or_else(|| cell.first().cloned());
+            let mut ordered = Vec::with_capacity(cell.len());
+            let mut e = start;
+            while let Some(current) = e {
+                ordered.push(current);
+                e = half_edges[current].next.filter(|next| Some(*next) != start);
+            }
+            // cells of collinear sites are bounded by parallel lines without vertices
+            for e in cell.iter() {
+                if !ordered.contains(e) {
+                    ordered.push(*e);
+                }
+            }
+            *cell = ordered;
+        }
+
+        Diagram {
+            sites: self.points.clone(),
+            vertices: self.vertices(),
+            half_edges,
+            cells,
+        }
+    }
+}
+
+// Voronoi diagram as doubly connected edge list, every edge is split into a half-edge
+// for each of its sites
+#[derive(Clone, Debug)]
+pub struct Diagram {
+    sites: Vec<Point2>,
+    vertices: Vec<Point2>,
+    half_edges: Vec<HalfEdge>,
+    // the half-edges around every site counter clockwise, empty for duplicates
+    cells: Vec<Vec<usize>>,
+}
+
+// runs counter clockwise in a y-up system around its site, None for ends at infinity
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HalfEdge {
+    pub site: usize,
+    pub origin: Option<usize>,
+    pub twin: usize,
+    pub next: Option<usize>,
+    pub prev: Option<usize>,
+    pub direction: (f32, f32),
+}
+
+impl Diagram {
+    pub fn sites(&self) -> &[Point2] {
+        &self.sites
+    }
+
+    pub fn vertices(&self) -> &[Point2] {
+        &self.vertices
+    }
+
+    pub fn half_edges(&self) -> &[HalfEdge] {
+        &self.half_edges
+    }
+
+    pub fn destination(&self, e: usize) -> Option<usize> {
+        self.half_edges[self.half_edges[e].twin].origin
+    }
+
+    pub fn cell_edges(&self, site: usize) -> &[usize] {
+        &self.cells[site]
+    }
+
+    // the cell of the site clipped to the bounds
+    pub fn cell(&self, site: usize, bounds: &Bounds) -> Vec<Point2> {
+        let neighbours: Vec<usize> = self.cells[site]
+            .iter()
+            .map(|e| self.half_edges[self.half_edges[*e].twin].site)
+            .collect();
+        voronoi::clip_cell(&self.sites, site, &neighbours, bounds)
+    }
+}
+
+pub fn fortune(points: &[Point2]) -> Diagram {
+    Fortune::new(points).finish()
+}
+
+fn parabola(site: (f64, f64), directrix: f64, x: f64) -> f64 {
+    ((x - site.0) * (x - site.0) + site.1 * site.1 - directrix * directrix)
+        / (2.0 * (site.1 - directrix))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{random_points, signed_area};
+    use crate::triangulation;
+
+    // the links are consistent and the clipped cells tile the bounds
+    fn check_diagram(diagram: &Diagram) {
+        for (e, half_edge) in diagram.half_edges().iter().enumerate() {
+            assert_eq!(diagram.half_edges()[half_edge.twin].twin, e);
+            if let Some(next) = half_edge.next {
+                assert_eq!(diagram.half_edges()[next].prev, Some(e));
+                assert_eq!(diagram.half_edges()[next].origin, diagram.destination(e));
+                assert_eq!(diagram.half_edges()[next].site, half_edge.site);
+            }
+        }
+        let bounds = Bounds {
+            min: (-100.0, -100.0),
+            max: (1100.0, 1100.0),
+        };
+        let total: f64 = (0..diagram.sites().len())
+            .map(|site| signed_area(&diagram.cell(site, &bounds)))
+            .sum();
+        assert!((total - 1200.0 * 1200.0).abs() < 10.0, "{}", total);
+    }
+
+    #[test]
+    fn dual_of_delaunay() {
+        let points = random_points(300, 5);
+        let diagram = fortune(&points);
+        check_diagram(&diagram);
+
+        let mesh = triangulation::delaunay(&points);
+        let mut expected: Vec<(usize, usize)> = mesh
+            .edges()
+            .into_iter()
+            .map(|e| {
+                let (a, b) = (mesh.origin(e), mesh.destination(e));
+                (a.min(b), a.max(b))
+            })
+            .collect();
+        expected.sort();
+        let mut edges: Vec<(usize, usize)> = diagram
+            .half_edges()
+            .iter()
+            .filter(|h| h.site < diagram.half_edges()[h.twin].site)
+            .map(|h| (h.site, diagram.half_edges()[h.twin].site))
+            .collect();
+        edges.sort();
+        assert_eq!(edges, expected);
+        assert_eq!(diagram.vertices().len(), mesh.len());
+
+        // vertices are equally far from the sites of their cells
+        for h in diagram.half_edges() {
+            if let Some(v) = h.origin {
+                let (v, s) = (diagram.vertices()[v], diagram.sites()[h.site]);
+                let other = diagram.sites()[diagram.half_edges()[h.twin].site];
+                let d = (v.x - s.x).hypot(v.y - s.y);
+                assert!((d - (v.x - other.x).hypot(v.y - other.y)).abs() < 1e-2 * d.max(1.0));
+            }
+        }
+        // inner cells are closed
+        let hull = crate::convex_hull::grahams_scan(&points);
+        for (site, p) in points.iter().enumerate() {
+            if !hull.contains(p) {
+                let cell = diagram.cell_edges(site);
+                assert!(cell.iter().all(|e| diagram.half_edges()[*e].next.is_some()));
+            }
+        }
+    }
+
+    #[test]
+    fn degenerate_sites() {
+        // cocircular and collinear everywhere, one row on the first sweep position
+        let grid: Vec<Point2> = (0..49)
+            .map(|i| Point2::new((i % 7) as f32 * 150.0, (i / 7) as f32 * 150.0))
+            .collect();
+        check_diagram(&fortune(&grid));
+
+        let line: Vec<Point2> = (0..5)
+            .map(|i| Point2::new(i as f32 * 200.0 + 50.0, i as f32 * 100.0))
+            .collect();
+        let diagram = fortune(&line);
+        assert!(diagram.vertices().is_empty());
+        assert_eq!(diagram.half_edges().len(), 8);
+        check_diagram(&diagram);
+
+        let mut duplicates = random_points(40, 9);
+        duplicates.extend(duplicates.clone());
+        let diagram = fortune(&duplicates);
+        assert!(diagram.cell_edges(45).is_empty());
+        check_diagram(&diagram);
+    }
+
+    #[test]
+    fn sweep_moves_down() {
+        let points = random_points(100, 1);
+        let mut sweep = Fortune::new(&points);
+        let mut last = std::f32::NEG_INFINITY;
+        let mut events = 0;
+        while let Some(y) = sweep.next_event() {
+            assert!(sweep.step());
+            assert_eq!(sweep.sweep(), y);
+            assert!(last <= y);
+            last = y;
+            events += 1;
+            // until the next event the beach line covers every x from left to right
+            let next = sweep.next_event().unwrap_or(y + 2.0);
+            let arcs = sweep.beach_line((y + next) / 2.0);
+            assert!(arcs
+                .windows(2)
+                .all(|w| w[0].2 == w[1].1 && w[0].1 <= w[0].2));
+        }
+        assert!(!sweep.step());
+        // a site event per site and a circle event per vertex
+        assert_eq!(events, 100 + sweep.vertices().len());
+    }
+}
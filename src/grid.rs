@@ -0,0 +1,272 @@
+use crate::kd_tree::Bounds;
+
+use std::collections::HashMap;
+
+// Uniform grid: points are hashed into square cells of a fixed size,
+// only cells that contain points are stored.
+#[derive(Clone, Debug)]
+pub struct UniformGrid {
+    cell_size: f32,
+    cells: HashMap<(i32, i32), Vec<(f32, f32)>>,
+    len: usize,
+}
+
+impl UniformGrid {
+    pub fn new(cell_size: f32) -> Self {
+        assert!(0.0 < cell_size, "grid cells need a positive size");
+        UniformGrid {
+            cell_size,
+            cells: HashMap::new(),
+            len: 0,
+        }
+    }
+
+    // picks a cell size that puts about two points into every cell
+    pub fn with_points(points: &[(f32, f32)]) -> Self {
+        let cell_size = if points.len() < 2 {
+            32.0
+        } else {
+            let bounds = points[1..]
+                .iter()
+                .fold(Bounds::point(points[0]), |b, p| b.union(&Bounds::point(*p)));
+            let area =
+                (bounds.max.0 - bounds.min.0).max(1.0) * (bounds.max.1 - bounds.min.1).max(1.0);
+            (2.0 * area / points.len() as f32).sqrt()
+        };
+        let mut grid = Self::new(cell_size);
+        for point in points {
+            grid.insert(*point);
+        }
+        grid
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    // Coordinates beyond the i32 range of cells share the outermost cells, the exact tests
+    // on the points keep queries correct. The casts themselves would be undefined.
+    fn cell(&self, point: (f32, f32)) -> (i32, i32) {
+        let index = |v: f32| {
+            let v = (v / self.cell_size).floor();
+            if v.is_nan() {
+                0
+            } else if v <= std::i32::MIN as f32 {
+                std::i32::MIN
+            } else if std::i32::MAX as f32 <= v {
+                std::i32::MAX
+            } else {
+                v as i32
+            }
+        };
+        (index(point.0), index(point.1))
+    }
+
+    fn cell_bounds(&self, cell: (i32, i32)) -> Bounds {
+        let min = (
+            cell.0 as f32 * self.cell_size,
+            cell.1 as f32 * self.cell_size,
+        );
+        Bounds {
+            min,
+            max: (min.0 + self.cell_size, min.1 + self.cell_size),
+        }
+    }
+
+    // inserts point, returns false if the point was already part of the grid or is not
+    // finite
+    pub fn insert(&mut self, point: (f32, f32)) -> bool {
+        if !point.0.is_finite() || !point.1.is_finite() {
+            return false;
+        }
+        let cell = self.cell(point);
+        let points = self.cells.entry(cell).or_insert_with(Vec::new);
+        if points.contains(&point) {
+            return false;
+        }
+        points.push(point);
+        self.len += 1;
+        true
+    }
+
+    // removes point, returns false if the point was not part of the grid
+    pub fn remove(&mut self, point: (f32, f32)) -> bool {
+        let cell = self.cell(point);
+        let removed = match self.cells.get_mut(&cell) {
+            Some(points) => match points.iter().position(|p| *p == point) {
+                Some(i) => {
+                    points.swap_remove(i);
+                    true
+                }
+                None => false,
+            },
+            None => false,
+        };
+        if removed {
+            self.len -= 1;
+            if self.cells[&cell].is_empty() {
+                self.cells.remove(&cell);
+            }
+        }
+        removed
+    }
+
+    pub fn range_query(&self, min: (f32, f32), max: (f32, f32)) -> Vec<(f32, f32)> {
+        let query = Bounds { min, max };
+        let mut v = Vec::new();
+        // also false for nan
+        if !(min.0 <= max.0 && min.1 <= max.1) {
+            return v;
+        }
+        let (lo, hi) = (self.cell(min), self.cell(max));
+        // a large query touches fewer stored cells than cells in its range
+        let width = (i64::from(hi.0) - i64::from(lo.0) + 1) as u64;
+        let height = (i64::from(hi.1) - i64::from(lo.1) + 1) as u64;
+        if (self.cells.len() as u64) < width.saturating_mul(height) {
+            for (cell, points) in &self.cells {
+                if lo.0 <= cell.0 && cell.0 <= hi.0 && lo.1 <= cell.1 && cell.1 <= hi.1 {
+                    v.extend(points.iter().filter(|p| query.contains_point(**p)));
+                }
+            }
+        } else {
+            for x in lo.0..=hi.0 {
+                for y in lo.1..=hi.1 {
+                    if let Some(points) = self.cells.get(&(x, y)) {
+                        v.extend(points.iter().filter(|p| query.contains_point(**p)));
+                    }
+                }
+            }
+        }
+        v
+    }
+
+    pub fn nearest(&self, point: (f32, f32)) -> Option<(f32, f32)> {
+        if self.is_empty() || !point.0.is_finite() || !point.1.is_finite() {
+            return None;
+        }
+        // search rings of cells around the cell of point until no closer point can follow
+        let center = self.cell(point);
+        let mut best: Option<((f32, f32), f32)> = None;
+        let mut ring = 0;
+        // once the rings cover more cells than are stored, as far away from the points,
+        // looking at every stored cell is cheaper
+        while (((2 * ring + 1) * (2 * ring + 1)) as usize) < self.cells.len() {
+            for cell in ring_cells(center, ring) {
+                if let Some(points) = self.cells.get(&cell) {
+                    best = closest(points, point, best);
+                }
+            }
+            // everything outside of the searched rings is at least this far away
+            let searched = Bounds {
+                min: self
+                    .cell_bounds((center.0.saturating_sub(ring), center.1.saturating_sub(ring)))
+                    .min,
+                max: self
+                    .cell_bounds((center.0.saturating_add(ring), center.1.saturating_add(ring)))
+                    .max,
+            };
+            let outside = (point.0 - searched.min.0)
+                .min(searched.max.0 - point.0)
+                .min(point.1 - searched.min.1)
+                .min(searched.max.1 - point.1);
+            if let Some((p, distance)) = best {
+                // point lies outside of its clamped cell if it is beyond the cell range
+                if 0.0 <= outside && distance <= outside * outside {
+                    return Some(p);
+                }
+            }
+            ring += 1;
+        }
+        self.cells
+            .values()
+            .fold(best, |best, points| closest(points, point, best))
+            .map(|(p, _)| p)
+    }
+
+    // bounds of all non empty cells
+    pub fn cells(&self) -> Vec<Bounds> {
+        self.cells
+            .keys()
+            .map(|cell| self.cell_bounds(*cell))
+            .collect()
+    }
+}
+
+fn closest(
+    points: &[(f32, f32)],
+    point: (f32, f32),
+    best: Option<((f32, f32), f32)>,
+) -> Option<((f32, f32), f32)> {
+    points.iter().fold(best, |best, p| {
+        let distance = Bounds::point(*p).distance_squared(point);
+        match best {
+            Some((_, best_distance)) if best_distance <= distance => best,
+            _ => Some((*p, distance)),
+        }
+    })
+}
+
+// cells with a chebyshev distance of exactly ring to center, at the end of the cell range
+// some of them repeat
+fn ring_cells(center: (i32, i32), ring: i32) -> Vec<(i32, i32)> {
+    if ring == 0 {
+        return vec![center];
+    }
+    let mut cells = Vec::with_capacity(8 * ring as usize);
+    for i in -ring..=ring {
+        cells.push((center.0.saturating_add(i), center.1.saturating_sub(ring)));
+        cells.push((center.0.saturating_add(i), center.1.saturating_add(ring)));
+    }
+    for i in -ring + 1..ring {
+        cells.push((center.0.saturating_sub(ring), center.1.saturating_add(i)));
+        cells.push((center.0.saturating_add(ring), center.1.saturating_add(i)));
+    }
+    cells
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn far_and_degenerate_queries() {
+        let points: Vec<(f32, f32)> = (0..200)
+            .map(|i| (((i * 37) % 101) as f32, ((i * 53) % 97) as f32))
+            .collect();
+        let mut grid = UniformGrid::new(10.0);
+        for point in &points {
+            grid.insert(*point);
+        }
+        assert!(!grid.insert((std::f32::NAN, 0.0)));
+        assert!(!grid.insert((0.0, std::f32::INFINITY)));
+        assert_eq!(grid.len(), points.len());
+
+        // far from the data, beyond the cells representable in i32 as well
+        for q in &[(1e7, 1e7), (-3e6, 50.0), (1e30, -1e30)] {
+            let expected = points
+                .iter()
+                .map(|p| Bounds::point(*p).distance_squared(*q))
+                .fold(std::f32::INFINITY, f32::min);
+            let nearest = grid.nearest(*q).unwrap();
+            assert_eq!(Bounds::point(nearest).distance_squared(*q), expected);
+        }
+        assert_eq!(grid.nearest((std::f32::NAN, 0.0)), None);
+
+        assert!(grid.range_query((50.0, 50.0), (10.0, 60.0)).is_empty());
+        assert!(grid
+            .range_query((std::f32::NAN, 0.0), (10.0, 60.0))
+            .is_empty());
+        assert_eq!(
+            grid.range_query((-1e30, -1e30), (1e30, 1e30)).len(),
+            points.len()
+        );
+        assert_eq!(
+            grid.range_query((-1e12, 20.0), (30.0, 1e12)).len(),
+            points.iter().filter(|p| p.0 <= 30.0 && 20.0 <= p.1).count()
+        );
+    }
+}
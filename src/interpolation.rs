@@ -0,0 +1,325 @@
+use crate::kd_tree::Bounds;
+use crate::math;
+use crate::mesh::Mesh;
+use crate::triangulation::Triangulation;
+use ggez::graphics::Point2;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Interpolation {
+    // barycentric within the triangle, continuous but with kinks along the edges
+    Linear,
+    // Sibson's natural neighbour coordinates, smooth except at the data points
+    NaturalNeighbour,
+}
+
+// Scalar values at the vertices of a Delaunay triangulation, interpolated inside of its
+// convex hull. Outside of it there is nothing to interpolate between and every method
+// returns None. Of equal points the value of the one in the triangulation is used.
+#[derive(Clone, Debug)]
+pub struct Field {
+    triangulation: Triangulation,
+    mesh: Mesh,
+    values: Vec<f64>,
+    // per vertex, the area weighted average of the gradients of the triangles around it
+    gradients: Vec<(f64, f64)>,
+}
+
+impl Field {
+    pub fn new(points: &[Point2], values: &[f32]) -> Self {
+        Self::with_triangulation(points, values, Triangulation::new(points))
+    }
+
+    pub fn with_seed(points: &[Point2], values: &[f32], seed: u64) -> Self {
+        Self::with_triangulation(points, values, Triangulation::with_seed(points, seed))
+    }
+
+    fn with_triangulation(points: &[Point2], values: &[f32], triangulation: Triangulation) -> Self {
+        assert_eq!(points.len(), values.len(), "every point needs a value");
+        let mut field = Field {
+            mesh: Mesh::new(points.to_vec(), triangulation.triangles()),
+            triangulation,
+            values: values.iter().map(|v| f64::from(*v)).collect(),
+            gradients: vec![(0.0, 0.0); points.len()],
+        };
+        let mut areas = vec![0.0; points.len()];
+        for t in 0..field.mesh.len() {
+            let (gradient, area) = field.triangle_gradient(t);
+            for v in &field.mesh.triangles()[t] {
+                field.gradients[*v].0 += area * gradient.0;
+                field.gradients[*v].1 += area * gradient.1;
+                areas[*v] += area;
+            }
+        }
+        for (gradient, area) in field.gradients.iter_mut().zip(areas) {
+            if 0.0 < area {
+                *gradient = (gradient.0 / area, gradient.1 / area);
+            }
+        }
+        field
+    }
+
+    pub fn mesh(&self) -> &Mesh {
+        &self.mesh
+    }
+
+    pub fn values(&self) -> Vec<f32> {
+        self.values.iter().map(|v| *v as f32).collect()
+    }
+
+    pub fn value(&self, q: Point2, method: Interpolation) -> Option<f32> {
+        let t = self.triangulation.locate(q)?;
+        Some(self.value_in(t, q, method) as f32)
+    }
+
+    // the estimated gradient of vertex v, zero for points not in the triangulation
+    pub fn vertex_gradient(&self, v: usize) -> (f32, f32) {
+        (self.gradients[v].0 as f32, self.gradients[v].1 as f32)
+    }
+
+    // the vertex gradients interpolated linearly, unlike the gradient of the linear
+    // interpolation this one is continuous
+    pub fn gradient(&self, q: Point2) -> Option<(f32, f32)> {
+        let t = self.triangulation.locate(q)?;
+        let weights = self.barycentric(t, q);
+        let mut gradient = (0.0, 0.0);
+        for (v, w) in self.mesh.triangles()[t].iter().zip(&weights) {
+            gradient.0 += w * self.gradients[*v].0;
+            gradient.1 += w * self.gradients[*v].1;
+        }
+        Some((gradient.0 as f32, gradient.1 as f32))
+    }
+
+    // values at the centers of a grid of cells over bounds, row by row from bounds.min.
+    // Neighbouring samples are located by walking from the previous one.
+    pub fn grid(
+        &self,
+        bounds: &Bounds,
+        columns: usize,
+        rows: usize,
+        method: Interpolation,
+    ) -> Vec<Option<f32>> {
+        let (width, height) = (
+            (bounds.max.0 - bounds.min.0) / columns as f32,
+            (bounds.max.1 - bounds.min.1) / rows as f32,
+        );
+        let mut hint = None;
+        let mut grid = Vec::with_capacity(columns * rows);
+        for row in 0..rows {
+            for column in 0..columns {
+                let q = Point2::new(
+                    bounds.min.0 + (column as f32 + 0.5) * width,
+                    bounds.min.1 + (row as f32 + 0.5) * height,
+                );
+                // a walk leaving the convex hull only proves q outside of it
+                let t = match hint {
+                    Some(hint) => self.mesh.walk(hint, q),
+                    None => self.triangulation.locate(q),
+                };
+                if t.is_some() {
+                    hint = t;
+                }
+                grid.push(t.map(|t| self.value_in(t, q, method) as f32));
+            }
+        }
+        grid
+    }
+
+    fn value_in(&self, t: usize, q: Point2, method: Interpolation) -> f64 {
+        match method {
+            Interpolation::Linear => {
+                let weights = self.barycentric(t, q);
+                self.mesh.triangles()[t]
+                    .iter()
+                    .zip(&weights)
+                    .map(|(v, w)| w * self.values[*v])
+                    .sum()
+            }
+            Interpolation::NaturalNeighbour => self.natural_neighbour(t, q),
+        }
+    }
+
+    // weights of the vertices of triangle t
+    fn barycentric(&self, t: usize, q: Point2) -> [f64; 3] {
+        let (w1, w2) = math::point_relative_to_triangle(q, &self.mesh.triangle(t));
+        let (w1, w2) = (f64::from(w1), f64::from(w2));
+        [w2, w1, 1.0 - w1 - w2]
+    }
+
+    // Sibson's interpolation, the weight of a vertex is the area its Voronoi cell would
+    // lose to q if q were inserted. The triangles whose circumcircle contains q would be
+    // replaced, their vertices are the natural neighbours of q. The area taken from one
+    // of them is the convex polygon of the circumcenters of its replaced triangles and
+    // the circumcenters of q with its two edges on the boundary of the replaced region.
+    fn natural_neighbour(&self, t: usize, q: Point2) -> f64 {
+        let q = (f64::from(q.x), f64::from(q.y));
+        let triangle = self.mesh.triangles()[t];
+        if let Some(v) = triangle.iter().find(|v| self.position(**v) == q) {
+            return self.values[*v];
+        }
+        // on the convex hull the natural neighbours are the ends of the edge
+        let on_hull = (0..3).any(|i| {
+            let (a, b) = (triangle[(i + 1) % 3], triangle[(i + 2) % 3]);
+            self.mesh.neighbours()[t][i].is_none()
+                && math::orient(self.position(a), self.position(b), q) == 0.0
+        });
+        if on_hull {
+            return self.value_in(
+                t,
+                Point2::new(q.0 as f32, q.1 as f32),
+                Interpolation::Linear,
+            );
+        }
+
+        let mut replaced = vec![t];
+        let mut stack = vec![t];
+        while let Some(t) = stack.pop() {
+            for n in self.mesh.neighbours()[t].iter().filter_map(|n| *n) {
+                if !replaced.contains(&n) && self.in_circumcircle(n, q) {
+                    replaced.push(n);
+                    stack.push(n);
+                }
+            }
+        }
+
+        let mut corners: Vec<(usize, Vec<(f64, f64)>)> = Vec::new();
+        let mut add = |v: usize, p: (f64, f64)| match corners.iter_mut().find(|(u, _)| *u == v) {
+            Some((_, list)) => list.push(p),
+            None => corners.push((v, vec![p])),
+        };
+        for t in &replaced {
+            let triangle = self.mesh.triangles()[*t];
+            let [a, b, c] = [
+                self.position(triangle[0]),
+                self.position(triangle[1]),
+                self.position(triangle[2]),
+            ];
+            let center = math::circumcenter(a, b, c);
+            for i in 0..3 {
+                add(triangle[i], center);
+                let outside = self.mesh.neighbours()[*t][i].filter(|n| replaced.contains(n));
+                if outside.is_none() {
+                    let (a, b) = (triangle[(i + 1) % 3], triangle[(i + 2) % 3]);
+                    let center = math::circumcenter(q, self.position(a), self.position(b));
+                    add(a, center);
+                    add(b, center);
+                }
+            }
+        }
+
+        let (mut total, mut weighted) = (0.0, 0.0);
+        for (v, polygon) in corners {
+            let area = convex_area(polygon);
+            total += area;
+            weighted += area * self.values[v];
+        }
+        weighted / total
+    }
+
+    fn in_circumcircle(&self, t: usize, q: (f64, f64)) -> bool {
+        let [a, b, c] = self.mesh.triangles()[t];
+        math::in_circle(self.position(a), self.position(b), self.position(c), q)
+    }
+
+    // gradient of the plane through the values of triangle t and the area of t
+    fn triangle_gradient(&self, t: usize) -> ((f64, f64), f64) {
+        let [a, b, c] = self.mesh.triangles()[t];
+        let (pa, pb, pc) = (self.position(a), self.position(b), self.position(c));
+        let (fa, fb, fc) = (self.values[a], self.values[b], self.values[c]);
+        let det = math::orient(pa, pb, pc);
+        let gradient = (
+            ((fb - fa) * (pc.1 - pa.1) - (fc - fa) * (pb.1 - pa.1)) / det,
+            ((fc - fa) * (pb.0 - pa.0) - (fb - fa) * (pc.0 - pa.0)) / det,
+        );
+        (gradient, det / 2.0)
+    }
+
+    fn position(&self, v: usize) -> (f64, f64) {
+        let p = self.mesh.points()[v];
+        (f64::from(p.x), f64::from(p.y))
+    }
+}
+
+// the area of the convex hull of the corners, given in any order
+fn convex_area(mut corners: Vec<(f64, f64)>) -> f64 {
+    let n = corners.len() as f64;
+    let center = corners
+        .iter()
+        .fold((0.0, 0.0), |c, p| (c.0 + p.0 / n, c.1 + p.1 / n));
+    let angle = |p: &(f64, f64)| (p.1 - center.1).atan2(p.0 - center.0);
+    corners.sort_by(|a, b| angle(a).partial_cmp(&angle(b)).unwrap());
+    (0..corners.len())
+        .map(|i| {
+            let (a, b) = (corners[i], corners[(i + 1) % corners.len()]);
+            a.0 * b.1 - b.0 * a.1
+        })
+        .sum::<f64>()
+        / 2.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::random_points;
+
+    fn plane(p: Point2) -> f32 {
+        3.0 * p.x - 2.0 * p.y + 7.0
+    }
+
+    #[test]
+    fn linear_functions_are_reproduced() {
+        let points = random_points(300, 9);
+        let values: Vec<f32> = points.iter().map(|p| plane(*p)).collect();
+        let field = Field::with_seed(&points, &values, 1);
+        let close = |a: f32, b: f32| (a - b).abs() <= 1e-3 * b.abs().max(1.0);
+        for q in random_points(200, 10)
+            .into_iter()
+            .chain(points.iter().cloned())
+        {
+            let linear = field.value(q, Interpolation::Linear);
+            let natural = field.value(q, Interpolation::NaturalNeighbour);
+            assert_eq!(linear.is_some(), natural.is_some());
+            if let (Some(linear), Some(natural)) = (linear, natural) {
+                assert!(close(linear, plane(q)), "{} {}", linear, plane(q));
+                assert!(close(natural, plane(q)), "{} {} {:?}", natural, plane(q), q);
+                let (dx, dy) = field.gradient(q).unwrap();
+                assert!(close(dx, 3.0) && close(dy, -2.0), "{} {}", dx, dy);
+            }
+        }
+        for (v, p) in points.iter().enumerate() {
+            assert_eq!(
+                field.value(*p, Interpolation::NaturalNeighbour),
+                Some(values[v])
+            );
+        }
+        assert_eq!(
+            field.value(Point2::new(-1.0, -1.0), Interpolation::Linear),
+            None
+        );
+    }
+
+    #[test]
+    fn grids_match_single_points() {
+        let points = random_points(100, 4);
+        let values: Vec<f32> = points.iter().map(|p| (p.x / 100.0).sin() * p.y).collect();
+        let field = Field::new(&points, &values);
+        let bounds = Bounds {
+            min: (-50.0, -50.0),
+            max: (1050.0, 1050.0),
+        };
+        for method in &[Interpolation::Linear, Interpolation::NaturalNeighbour] {
+            let grid = field.grid(&bounds, 30, 20, *method);
+            assert_eq!(grid.len(), 600);
+            for (i, sample) in grid.iter().enumerate() {
+                let q = Point2::new(
+                    -50.0 + (i % 30) as f32 * 1100.0 / 30.0 + 550.0 / 30.0,
+                    -50.0 + (i / 30) as f32 * 1100.0 / 20.0 + 550.0 / 20.0,
+                );
+                let single = field.value(q, *method);
+                assert_eq!(sample.is_some(), single.is_some());
+                if let (Some(a), Some(b)) = (sample, single) {
+                    assert!((a - b).abs() < 1e-2, "{} {}", a, b);
+                }
+            }
+        }
+    }
+}
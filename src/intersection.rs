@@ -1,5 +1,8 @@
 use ggez::graphics::Point2;
 
+use crate::kd_tree::Bounds;
+use crate::rtree::RTree;
+
 use std::cmp::Ordering;
 use std::collections::BTreeMap;
 use std::collections::BinaryHeap;
@@ -148,6 +151,84 @@ pub fn iso_scan_line(lines: &[(Point2, Point2)]) -> (Vec<(Point2, Point2)>, Vec<
     )
 }
 
+// Intersections of arbitrary segments. Candidate pairs are found by querying an
+// r-tree of the segment bounding boxes, only those are tested exactly.
+pub fn segment_intersections(lines: &[(Point2, Point2)]) -> (Vec<(Point2, Point2)>, Vec<Point2>) {
+    debug!("segment_intersections");
+    let tree = RTree::bulk_load(
+        lines
+            .iter()
+            .enumerate()
+            .map(|(id, line)| (segment_bounds(*line), id))
+            .collect(),
+        16,
+    );
+
+    let mut intersecting_lines = BTreeMap::new();
+    let mut intersection_points = Vec::new();
+    for (id, line) in lines.iter().enumerate() {
+        for &other in tree.intersecting(&segment_bounds(*line)) {
+            // every pair only once
+            if other <= id {
+                continue;
+            }
+            if let Some(point) = segment_intersection(*line, lines[other]) {
+                intersection_points.push(point);
+                intersecting_lines.insert(id, *line);
+                intersecting_lines.insert(other, lines[other]);
+            }
+        }
+    }
+
+    (
+        intersecting_lines.values().cloned().collect(),
+        intersection_points,
+    )
+}
+
+fn segment_bounds(line: (Point2, Point2)) -> Bounds {
+    Bounds {
+        min: (line.0.x.min(line.1.x), line.0.y.min(line.1.y)),
+        max: (line.0.x.max(line.1.x), line.0.y.max(line.1.y)),
+    }
+}
+
+// intersection point of two segments, for overlapping collinear segments
+// the first endpoint lying on the other segment
+fn segment_intersection(a: (Point2, Point2), b: (Point2, Point2)) -> Option<Point2> {
+    let cross =
+        |o: Point2, p: Point2, q: Point2| (p.x - o.x) * (q.y - o.y) - (p.y - o.y) * (q.x - o.x);
+    let d1 = cross(b.0, b.1, a.0);
+    let d2 = cross(b.0, b.1, a.1);
+    let d3 = cross(a.0, a.1, b.0);
+    let d4 = cross(a.0, a.1, b.1);
+
+    if d1 * d2 < 0.0 && d3 * d4 < 0.0 {
+        // proper crossing
+        let t = d1 / (d1 - d2);
+        return Some(Point2::new(
+            a.0.x + t * (a.1.x - a.0.x),
+            a.0.y + t * (a.1.y - a.0.y),
+        ));
+    }
+
+    // touching or collinear, one of the endpoints lies on the other segment
+    let on_segment = |p: Point2, s: (Point2, Point2)| segment_bounds(s).contains_point((p.x, p.y));
+    if d1 == 0.0 && on_segment(a.0, b) {
+        return Some(a.0);
+    }
+    if d2 == 0.0 && on_segment(a.1, b) {
+        return Some(a.1);
+    }
+    if d3 == 0.0 && on_segment(b.0, a) {
+        return Some(b.0);
+    }
+    if d4 == 0.0 && on_segment(b.1, a) {
+        return Some(b.1);
+    }
+    None
+}
+
 fn line_is_horizontal(points: (Point2, Point2)) -> bool {
     if points.0.y == points.1.y {
         true
@@ -155,3 +236,88 @@ fn line_is_horizontal(points: (Point2, Point2)) -> bool {
         false
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn segment_intersections_match_brute_force() {
+        let lines: Vec<(Point2, Point2)> = (0..120)
+            .map(|i| {
+                let p = Point2::new(((i * 37) % 101) as f32, ((i * 53) % 97) as f32);
+                let q = Point2::new(
+                    p.x + ((i * 7) % 23) as f32 - 11.0,
+                    p.y + ((i * 11) % 19) as f32,
+                );
+                (p, q)
+            })
+            .collect();
+        let (intersecting, points) = segment_intersections(&lines);
+
+        let mut expected_points = 0;
+        let mut expected_lines = Vec::new();
+        for i in 0..lines.len() {
+            for j in i + 1..lines.len() {
+                if segment_intersection(lines[i], lines[j]).is_some() {
+                    expected_points += 1;
+                    for k in &[i, j] {
+                        if !expected_lines.contains(k) {
+                            expected_lines.push(*k);
+                        }
+                    }
+                }
+            }
+        }
+        assert!(0 < expected_points);
+        assert_eq!(points.len(), expected_points);
+        assert_eq!(intersecting.len(), expected_lines.len());
+    }
+
+    #[test]
+    fn iso_oriented_segments_agree_with_the_scan_line() {
+        // like the segments of LineState, horizontal ones on even y with odd ends and
+        // vertical ones on even x with odd ends, so no two of them just touch
+        let lines: Vec<(Point2, Point2)> = (0..80)
+            .map(|i| {
+                let (a, b) = ((i * 2) as f32, ((i * 29) % 61 * 2 + 1) as f32);
+                let length = ((i * 13) % 17 * 2 + 4) as f32;
+                if i % 2 == 0 {
+                    (Point2::new(b, a), Point2::new(b + length, a))
+                } else {
+                    (Point2::new(a, b), Point2::new(a, b + length))
+                }
+            })
+            .collect();
+        let sorted = |mut points: Vec<Point2>| {
+            points.sort_by(|a, b| (a.x, a.y).partial_cmp(&(b.x, b.y)).unwrap());
+            points
+        };
+
+        let mut expected = Vec::new();
+        for i in 0..lines.len() {
+            for j in i + 1..lines.len() {
+                expected.extend(segment_intersection(lines[i], lines[j]));
+            }
+        }
+        assert!(0 < expected.len());
+        let (tree_lines, tree_points) = segment_intersections(&lines);
+        let (scan_lines, scan_points) = iso_scan_line(&lines);
+        assert_eq!(sorted(tree_points), sorted(expected.clone()));
+        assert_eq!(sorted(scan_points), sorted(expected));
+        assert_eq!(tree_lines, scan_lines);
+    }
+
+    #[test]
+    fn crossing_and_touching_segments() {
+        let a = (Point2::new(0.0, 0.0), Point2::new(4.0, 4.0));
+        let b = (Point2::new(0.0, 4.0), Point2::new(4.0, 0.0));
+        assert_eq!(segment_intersection(a, b), Some(Point2::new(2.0, 2.0)));
+
+        let c = (Point2::new(4.0, 4.0), Point2::new(6.0, 0.0));
+        assert_eq!(segment_intersection(a, c), Some(Point2::new(4.0, 4.0)));
+
+        let d = (Point2::new(5.0, 5.0), Point2::new(6.0, 6.0));
+        assert_eq!(segment_intersection(a, d), None);
+    }
+}
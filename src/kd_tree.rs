@@ -1,41 +1,458 @@
+use crate::shape::{Classification, Shape};
+
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::sync::Arc;
+use std::thread;
+
+// a subtree is rebuilt once one of its children holds more than this share of its points
+const ALPHA: f32 = 0.7;
+
+// smaller subtrees are not worth a thread of their own
+const PARALLEL_MIN_LEN: usize = 4096;
+
 #[derive(Clone, Debug)]
-pub struct KdTree(pub Box<Node>);
+pub struct KdTree {
+    root: Option<Box<Node>>,
+    orientation_even: Orientation,
+    // number of points at the last full rebuild, deletions shrinking the tree
+    // below ALPHA of it trigger another full rebuild
+    max_len: usize,
+}
 
 impl KdTree {
+    // The tree holds a set of points, like insert which ignores points already in it: of
+    // equal input points only the first one is kept, len and range counts see it once.
     pub fn new(points: &[(f32, f32)]) -> Self {
         Self::with_orientation_even(&points, Orientation::Horizontal)
     }
 
     pub fn with_orientation_even(points: &[(f32, f32)], orientation_even: Orientation) -> Self {
-        if points.len() == 0 {
-            panic!("Tried to construct kd-tree with 0 points");
+        let entries: Vec<((f32, f32), f32)> = points.iter().map(|p| (*p, 0.0)).collect();
+        Self::from_entries(&entries, orientation_even)
+    }
+
+    // every point carries a payload that is summed up by range_aggregate, of equal points
+    // the first one keeps its payload
+    pub fn with_payloads(entries: &[((f32, f32), f32)]) -> Self {
+        Self::from_entries(entries, Orientation::Horizontal)
+    }
+
+    fn from_entries(entries: &[((f32, f32), f32)], orientation_even: Orientation) -> Self {
+        Self::from_root(Self::build(entries, orientation_even), orientation_even)
+    }
+
+    // takes over a tree built elsewhere, the caller keeps the keys consistent with the leaves
+    pub fn from_root(root: Option<Box<Node>>, orientation_even: Orientation) -> Self {
+        let max_len = root.as_ref().map_or(0, |node| node.size());
+        KdTree {
+            root,
+            orientation_even,
+            max_len,
+        }
+    }
+
+    pub fn orientation_even(&self) -> Orientation {
+        self.orientation_even
+    }
+
+    pub fn root(&self) -> Option<&Node> {
+        self.root.as_ref().map(|node| &**node)
+    }
+
+    pub fn len(&self) -> usize {
+        self.root.as_ref().map_or(0, |node| node.size())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+
+    pub fn points(&self) -> Vec<(f32, f32)> {
+        self.entries().into_iter().map(|(point, _)| point).collect()
+    }
+
+    pub fn entries(&self) -> Vec<((f32, f32), f32)> {
+        let mut entries = Vec::with_capacity(self.len());
+        if let Some(root) = &self.root {
+            Self::collect(root, &mut entries);
+        }
+        entries
+    }
+
+    // inserts point, returns false if the point was already part of the tree
+    pub fn insert(&mut self, point: (f32, f32)) -> bool {
+        self.insert_with_payload(point, 0.0)
+    }
+
+    pub fn insert_with_payload(&mut self, point: (f32, f32), payload: f32) -> bool {
+        // the build puts points equal to a key on either side, while the insertion path
+        // only goes right on ties, so look for the point on both sides first
+        if let Some(root) = &self.root {
+            if Self::find(root, point) {
+                return false;
+            }
         }
+        let inserted = match self.root.take() {
+            None => {
+                self.root = Some(Node::leaf(point, payload));
+                true
+            }
+            Some(root) => {
+                let (root, inserted) =
+                    Self::insert_node(root, (point, payload), self.orientation_even);
+                self.root = Some(root);
+                inserted
+            }
+        };
 
-        // sorted lists of references/pointers to points
-        let mut pre_sorted_x: Vec<&(f32, f32)> = points.iter().map(|p| p).collect();
-        pre_sorted_x.sort_by(|a, b| {
-            a.0.partial_cmp(&b.0)
-                .unwrap()
-                .then_with(|| a.1.partial_cmp(&b.1).unwrap())
+        if inserted {
+            // rebuild the topmost subtree on the insertion path that got out of balance
+            if let Some(root) = self.root.take() {
+                self.root = Some(Self::rebalance_path(root, point));
+            }
+            self.max_len = self.max_len.max(self.len());
+        }
+        inserted
+    }
+
+    // removes point, returns false if the point was not part of the tree
+    pub fn remove(&mut self, point: (f32, f32)) -> bool {
+        let removed = match self.root.take() {
+            None => false,
+            Some(root) => {
+                let (root, removed) = Self::remove_node(root, point);
+                self.root = root;
+                removed
+            }
+        };
+
+        if removed && (self.len() as f32) < ALPHA * self.max_len as f32 {
+            let entries = self.entries();
+            self.root = Self::build(&entries, self.orientation_even);
+            self.max_len = self.len();
+        }
+        removed
+    }
+
+    fn build(entries: &[((f32, f32), f32)], orientation: Orientation) -> Option<Box<Node>> {
+        // sorted lists of references/pointers to points, the sorts are stable so dedup keeps
+        // the same first occurrence in both
+        let mut pre_sorted_x: Vec<&((f32, f32), f32)> = entries.iter().map(|p| p).collect();
+        pre_sorted_x.sort_by(|a, b| x_order(a, b));
+        pre_sorted_x.dedup_by(|a, b| a.0 == b.0);
+        let mut pre_sorted_y: Vec<&((f32, f32), f32)> = entries.iter().map(|p| p).collect();
+        pre_sorted_y.sort_by(|a, b| y_order(a, b));
+        pre_sorted_y.dedup_by(|a, b| a.0 == b.0);
+
+        Self::construct_balanced_2d_tree(&pre_sorted_x[..], &pre_sorted_y[..], orientation)
+    }
+
+    // builds the same tree as new, the upper levels of the recursion are split across
+    // up to threads threads
+    pub fn with_threads(points: &[(f32, f32)], threads: usize) -> Self {
+        let mut x: Vec<((f32, f32), f32)> = points.iter().map(|p| (*p, 0.0)).collect();
+        let mut y = x.clone();
+        let sort_y = thread::spawn(move || {
+            y.sort_by(y_order);
+            y.dedup_by(|a, b| a.0 == b.0);
+            y
         });
-        let mut pre_sorted_y: Vec<&(f32, f32)> = points.iter().map(|p| p).collect();
-        pre_sorted_y.sort_by(|a, b| {
-            a.1.partial_cmp(&b.1)
-                .unwrap()
-                .then_with(|| a.0.partial_cmp(&b.0).unwrap())
+        x.sort_by(x_order);
+        x.dedup_by(|a, b| a.0 == b.0);
+        let y = sort_y.join().unwrap();
+
+        let root = Self::construct_parallel(x, y, Orientation::Horizontal, threads.max(1));
+        Self::from_root(root, Orientation::Horizontal)
+    }
+
+    // same partitioning as construct_balanced_2d_tree on owned lists, so one half can be
+    // moved to another thread
+    fn construct_parallel(
+        mut x: Vec<((f32, f32), f32)>,
+        mut y: Vec<((f32, f32), f32)>,
+        orientation: Orientation,
+        threads: usize,
+    ) -> Option<Box<Node>> {
+        if threads == 1 || x.len() < PARALLEL_MIN_LEN {
+            let x: Vec<&((f32, f32), f32)> = x.iter().collect();
+            let y: Vec<&((f32, f32), f32)> = y.iter().collect();
+            return Self::construct_balanced_2d_tree(&x[..], &y[..], orientation);
+        }
+        let median = x.len() / 2;
+        let (key, left, right) = match orientation {
+            Orientation::Horizontal => {
+                let key = y[median].0;
+                let y_right = y.split_off(median);
+                let (x_left, x_right) = x
+                    .into_iter()
+                    .partition(|(p, _)| p.1 < key.1 || (p.1 == key.1 && p.0 < key.0));
+                (key.1, (x_left, y), (x_right, y_right))
+            }
+            Orientation::Vertical => {
+                let key = x[median].0;
+                let x_right = x.split_off(median);
+                let (y_left, y_right) = y
+                    .into_iter()
+                    .partition(|(p, _)| p.0 < key.0 || (p.0 == key.0 && p.1 < key.1));
+                (key.0, (x, y_left), (x_right, y_right))
+            }
+        };
+
+        let other = orientation.other();
+        let left_threads = threads / 2;
+        let left =
+            thread::spawn(move || Self::construct_parallel(left.0, left.1, other, left_threads));
+        let right = Self::construct_parallel(right.0, right.1, other, threads - left_threads);
+        Some(Node::knot(
+            Key {
+                value: key,
+                orientation,
+            },
+            left.join().unwrap(),
+            right,
+        ))
+    }
+
+    // answers the queries on up to threads threads, results are in the order of queries
+    pub fn batch_range_query(
+        tree: &Arc<KdTree>,
+        queries: &[((f32, f32), (f32, f32))],
+        threads: usize,
+    ) -> Vec<Vec<(f32, f32)>> {
+        Self::batch(tree, queries, threads, |tree, (min, max)| {
+            tree.range_query(*min, *max)
+        })
+    }
+
+    pub fn batch_nearest(
+        tree: &Arc<KdTree>,
+        queries: &[(f32, f32)],
+        threads: usize,
+    ) -> Vec<Option<(f32, f32)>> {
+        Self::batch(tree, queries, threads, |tree, point| tree.nearest(*point))
+    }
+
+    fn batch<Q, R>(
+        tree: &Arc<KdTree>,
+        queries: &[Q],
+        threads: usize,
+        query: fn(&KdTree, &Q) -> R,
+    ) -> Vec<R>
+    where
+        Q: Clone + Send + 'static,
+        R: Send + 'static,
+    {
+        let chunk_size = (queries.len() + threads.max(1) - 1) / threads.max(1);
+        let handles: Vec<_> = queries
+            .chunks(chunk_size.max(1))
+            .map(|chunk| {
+                let tree = Arc::clone(tree);
+                let chunk = chunk.to_vec();
+                thread::spawn(move || chunk.iter().map(|q| query(&tree, q)).collect::<Vec<R>>())
+            })
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().unwrap())
+            .collect()
+    }
+
+    fn collect(node: &Node, entries: &mut Vec<((f32, f32), f32)>) {
+        match node {
+            Node::Knot { left, right, .. } => {
+                if let Some(left) = left {
+                    Self::collect(left, entries);
+                }
+                if let Some(right) = right {
+                    Self::collect(right, entries);
+                }
+            }
+            Node::Leaf { value, payload } => entries.push((*value, *payload)),
+        }
+    }
+
+    // calls f for every node in pre-order together with the cell the partition assigns
+    // to it, the root's cell is the whole plane
+    pub fn visit_cells<F>(&self, mut f: F)
+    where
+        F: FnMut(&Node, &Bounds),
+    {
+        if let Some(root) = &self.root {
+            Self::visit_cell(root, Bounds::everything(), &mut f);
+        }
+    }
+
+    fn visit_cell<F>(node: &Node, cell: Bounds, f: &mut F)
+    where
+        F: FnMut(&Node, &Bounds),
+    {
+        f(node, &cell);
+        if let Node::Knot {
+            key, left, right, ..
+        } = node
+        {
+            let (left_cell, right_cell) = cell.split(key);
+            if let Some(left) = left {
+                Self::visit_cell(left, left_cell, f);
+            }
+            if let Some(right) = right {
+                Self::visit_cell(right, right_cell, f);
+            }
+        }
+    }
+
+    // the splitting line of every knot, cut to the knot's cell
+    pub fn partition_lines(&self) -> Vec<((f32, f32), (f32, f32))> {
+        let mut lines = Vec::new();
+        self.visit_cells(|node, cell| {
+            if let Node::Knot { key, .. } = node {
+                let line = match key.orientation {
+                    Orientation::Vertical => ((key.value, cell.min.1), (key.value, cell.max.1)),
+                    Orientation::Horizontal => ((cell.min.0, key.value), (cell.max.0, key.value)),
+                };
+                lines.push(line);
+            }
         });
+        lines
+    }
 
-        let node = Self::construct_balanced_2d_tree(
-            &pre_sorted_x[..],
-            &pre_sorted_y[..],
-            orientation_even,
-        );
-        KdTree(node.unwrap())
+    fn find(node: &Node, point: (f32, f32)) -> bool {
+        match node {
+            Node::Leaf { value, .. } => *value == point,
+            Node::Knot {
+                key, left, right, ..
+            } => {
+                let in_child = |child: &Option<Box<Node>>| match child {
+                    Some(child) => Self::find(child, point),
+                    None => false,
+                };
+                (key.may_go_left(point) && in_child(left))
+                    || (key.may_go_right(point) && in_child(right))
+            }
+        }
+    }
+
+    // orientation is the one a knot replacing node would get
+    fn insert_node(
+        mut node: Box<Node>,
+        entry: ((f32, f32), f32),
+        orientation: Orientation,
+    ) -> (Box<Node>, bool) {
+        let inserted = match *node {
+            Node::Leaf { value, payload } => {
+                if value == entry.0 {
+                    return (node, false);
+                }
+                let split = Self::build(&[(value, payload), entry], orientation).unwrap();
+                return (split, true);
+            }
+            Node::Knot {
+                ref key,
+                ref mut left,
+                ref mut right,
+                ..
+            } => {
+                let child = if key.goes_left(entry.0) { left } else { right };
+                match child.take() {
+                    None => {
+                        *child = Some(Node::leaf(entry.0, entry.1));
+                        true
+                    }
+                    Some(old) => {
+                        let (new, inserted) =
+                            Self::insert_node(old, entry, key.orientation.other());
+                        *child = Some(new);
+                        inserted
+                    }
+                }
+            }
+        };
+        if inserted {
+            node.update_summary();
+        }
+        (node, inserted)
+    }
+
+    fn remove_node(mut node: Box<Node>, point: (f32, f32)) -> (Option<Box<Node>>, bool) {
+        match *node {
+            Node::Leaf { value, .. } => {
+                if value == point {
+                    (None, true)
+                } else {
+                    (Some(node), false)
+                }
+            }
+            Node::Knot {
+                ref key,
+                ref mut left,
+                ref mut right,
+                ..
+            } => {
+                // points equal to the key can end up on both sides
+                let mut removed = false;
+                if key.may_go_left(point) {
+                    if let Some(old) = left.take() {
+                        let (new, r) = Self::remove_node(old, point);
+                        *left = new;
+                        removed = r;
+                    }
+                }
+                if !removed && key.may_go_right(point) {
+                    if let Some(old) = right.take() {
+                        let (new, r) = Self::remove_node(old, point);
+                        *right = new;
+                        removed = r;
+                    }
+                }
+                if !removed {
+                    return (Some(node), false);
+                }
+
+                // a knot with a single child is replaced by that child
+                match (left.take(), right.take()) {
+                    (Some(l), Some(r)) => {
+                        *left = Some(l);
+                        *right = Some(r);
+                        node.update_summary();
+                        (Some(node), true)
+                    }
+                    (Some(child), None) | (None, Some(child)) => (Some(child), true),
+                    (None, None) => (None, true),
+                }
+            }
+        }
+    }
+
+    fn rebalance_path(mut node: Box<Node>, point: (f32, f32)) -> Box<Node> {
+        if node.is_unbalanced() {
+            let mut entries = Vec::with_capacity(node.size());
+            Self::collect(&node, &mut entries);
+            let orientation = match *node {
+                Node::Knot { ref key, .. } => key.orientation,
+                Node::Leaf { .. } => unreachable!(),
+            };
+            return Self::build(&entries, orientation).unwrap();
+        }
+
+        if let Node::Knot {
+            ref key,
+            ref mut left,
+            ref mut right,
+            ..
+        } = *node
+        {
+            let child = if key.goes_left(point) { left } else { right };
+            if let Some(old) = child.take() {
+                *child = Some(Self::rebalance_path(old, point));
+            }
+        }
+        node
     }
 
     fn construct_balanced_2d_tree(
-        x: &[&(f32, f32)],
-        y: &[&(f32, f32)],
+        x: &[&((f32, f32), f32)],
+        y: &[&((f32, f32), f32)],
         orientation: Orientation,
     ) -> Option<Box<Node>> {
         assert_eq!(x.len(), y.len());
@@ -47,12 +464,10 @@ impl KdTree {
             Orientation::Horizontal => {
                 if x.len() == 1 {
                     // create Leaf
-                    Some(Box::new(Node::Leaf {
-                        value: (x[0].0, x[0].1),
-                    }))
+                    Some(Node::leaf(x[0].0, x[0].1))
                 } else {
                     // create Node with key as y
-                    let key = *y[median];
+                    let key = y[median].0;
 
                     // partitioning
                     let y_left = &y[..median];
@@ -60,50 +475,49 @@ impl KdTree {
 
                     let mut x_left = Vec::with_capacity(y_left.len());
                     let mut x_right = Vec::with_capacity(y_right.len());
-                    for point in x.iter() {
+                    for entry in x.iter() {
+                        let point = entry.0;
                         if point.1 < key.1 {
-                            x_left.push(*point);
+                            x_left.push(*entry);
                         } else if key.1 < point.1 {
-                            x_right.push(*point);
+                            x_right.push(*entry);
                         } else if point.1 == key.1 {
                             if key.0 <= point.0 {
-                                x_right.push(*point);
+                                x_right.push(*entry);
                             } else {
-                                x_left.push(*point);
+                                x_left.push(*entry);
                             }
                         } else {
                             panic!("Something went terible wrong");
                         }
                     }
 
-                    Some(Box::new(Node::Knot {
-                        key: Key {
+                    Some(Node::knot(
+                        Key {
                             value: key.1,
                             orientation: Orientation::Horizontal,
                         },
-                        left: Self::construct_balanced_2d_tree(
+                        Self::construct_balanced_2d_tree(
                             &x_left[..],
                             y_left,
                             Orientation::Vertical,
                         ),
-                        right: Self::construct_balanced_2d_tree(
+                        Self::construct_balanced_2d_tree(
                             &x_right[..],
                             y_right,
                             Orientation::Vertical,
                         ),
-                    }))
+                    ))
                 }
             }
             Orientation::Vertical => {
                 if x.len() == 1 {
                     // create Leaf
-                    Some(Box::new(Node::Leaf {
-                        value: (x[0].0, x[0].1),
-                    }))
+                    Some(Node::leaf(x[0].0, x[0].1))
                 } else {
                     // create Node with key as x
 
-                    let key = *x[median];
+                    let key = x[median].0;
 
                     // partitioning
                     let x_left = &x[..median];
@@ -111,92 +525,506 @@ impl KdTree {
 
                     let mut y_left = Vec::with_capacity(x_left.len());
                     let mut y_right = Vec::with_capacity(x_right.len());
-                    for point in y.iter() {
+                    for entry in y.iter() {
+                        let point = entry.0;
                         if point.0 < key.0 {
-                            y_left.push(*point);
+                            y_left.push(*entry);
                         } else if key.0 < point.0 {
-                            y_right.push(*point);
+                            y_right.push(*entry);
                         } else if point.0 == key.0 {
                             if key.1 <= point.1 {
-                                y_right.push(*point);
+                                y_right.push(*entry);
                             } else {
-                                y_left.push(*point);
+                                y_left.push(*entry);
                             }
                         } else {
                             panic!("Something went terible wrong");
                         }
                     }
 
-                    Some(Box::new(Node::Knot {
-                        key: Key {
+                    Some(Node::knot(
+                        Key {
                             value: key.0,
                             orientation: Orientation::Vertical,
                         },
-                        left: Self::construct_balanced_2d_tree(
+                        Self::construct_balanced_2d_tree(
                             x_left,
                             &y_left[..],
                             Orientation::Horizontal,
                         ),
-                        right: Self::construct_balanced_2d_tree(
+                        Self::construct_balanced_2d_tree(
                             x_right,
                             &y_right[..],
                             Orientation::Horizontal,
                         ),
-                    }))
+                    ))
                 }
             }
         }
     }
 
     pub fn range_query(&self, min: (f32, f32), max: (f32, f32)) -> Vec<(f32, f32)> {
-        Self::query(&self.0, min, max)
+        self.range_query_visited(min, max).0
     }
 
-    fn query(node: &Node, min: (f32, f32), max: (f32, f32)) -> Vec<(f32, f32)> {
+    // also returns the number of tree nodes the query looked at
+    pub fn range_query_visited(
+        &self,
+        min: (f32, f32),
+        max: (f32, f32),
+    ) -> (Vec<(f32, f32)>, usize) {
         let mut v = Vec::new();
+        let mut stats = QueryStats::default();
+        if let Some(root) = &self.root {
+            Self::query(root, Bounds::everything(), min, max, &mut v, &mut stats);
+        }
+        (v, stats.visited)
+    }
 
+    // also records which cells of the partition were visited and which were pruned
+    pub fn range_query_instrumented(
+        &self,
+        min: (f32, f32),
+        max: (f32, f32),
+    ) -> (Vec<(f32, f32)>, QueryStats) {
+        let mut v = Vec::new();
+        let mut stats = QueryStats {
+            record_cells: true,
+            ..QueryStats::default()
+        };
+        if let Some(root) = &self.root {
+            Self::query(root, Bounds::everything(), min, max, &mut v, &mut stats);
+        }
+        (v, stats)
+    }
+
+    // cell is the part of the plane the partition assigns to node
+    fn query(
+        node: &Node,
+        cell: Bounds,
+        min: (f32, f32),
+        max: (f32, f32),
+        v: &mut Vec<(f32, f32)>,
+        stats: &mut QueryStats,
+    ) {
+        stats.visited += 1;
+        if stats.record_cells {
+            stats.visited_cells.push(cell);
+        }
         match node {
-            Node::Knot { key, left, right } => {
-                match key.orientation {
-                    Orientation::Vertical => {
-                        if min.0 <= key.value {
-                            //left is inside
-                            if let Some(left) = left {
-                                v.append(&mut Self::query(left, min, max));
-                            }
-                        }
-                        if key.value <= max.0 {
-                            //right is inside
-                            if let Some(right) = right {
-                                v.append(&mut Self::query(right, min, max));
-                            }
-                        }
+            Node::Knot {
+                key, left, right, ..
+            } => {
+                let (left_cell, right_cell) = cell.split(key);
+                let (lower, upper) = match key.orientation {
+                    Orientation::Vertical => (min.0, max.0),
+                    Orientation::Horizontal => (min.1, max.1),
+                };
+                //left is inside
+                if let Some(left) = left {
+                    if lower <= key.value {
+                        Self::query(left, left_cell, min, max, v, stats);
+                    } else {
+                        stats.prune(left_cell);
                     }
-                    Orientation::Horizontal => {
-                        if min.1 <= key.value {
-                            //left is inside
-                            if let Some(left) = left {
-                                v.append(&mut Self::query(left, min, max));
-                            }
-                        }
-                        if key.value <= max.1 {
-                            //right is inside
-                            if let Some(right) = right {
-                                v.append(&mut Self::query(right, min, max));
-                            }
-                        }
+                }
+                //right is inside
+                if let Some(right) = right {
+                    if key.value <= upper {
+                        Self::query(right, right_cell, min, max, v, stats);
+                    } else {
+                        stats.prune(right_cell);
                     }
                 }
             }
-            Node::Leaf { value } => {
+            Node::Leaf { value, .. } => {
                 if min.0 <= value.0 && value.0 <= max.0 && min.1 <= value.1 && value.1 <= max.1 {
+                    stats.reported += 1;
                     v.push(*value);
                 }
             }
         };
+    }
 
+    // points inside shape, subtrees are pruned or reported by their bounding box
+    pub fn shape_query<S: Shape + ?Sized>(&self, shape: &S) -> Vec<(f32, f32)> {
+        let mut v = Vec::new();
+        if let Some(root) = &self.root {
+            Self::query_shape(root, shape, &mut v);
+        }
         v
     }
+
+    fn query_shape<S: Shape + ?Sized>(node: &Node, shape: &S, v: &mut Vec<(f32, f32)>) {
+        match node {
+            Node::Knot {
+                bounds,
+                left,
+                right,
+                ..
+            } => match shape.classify(bounds) {
+                Classification::Inside => Self::collect_points(node, v),
+                Classification::Outside => {}
+                Classification::Partial => {
+                    if let Some(left) = left {
+                        Self::query_shape(left, shape, v);
+                    }
+                    if let Some(right) = right {
+                        Self::query_shape(right, shape, v);
+                    }
+                }
+            },
+            Node::Leaf { value, .. } => {
+                if shape.contains(*value) {
+                    v.push(*value);
+                }
+            }
+        }
+    }
+
+    fn collect_points(node: &Node, v: &mut Vec<(f32, f32)>) {
+        match node {
+            Node::Knot { left, right, .. } => {
+                if let Some(left) = left {
+                    Self::collect_points(left, v);
+                }
+                if let Some(right) = right {
+                    Self::collect_points(right, v);
+                }
+            }
+            Node::Leaf { value, .. } => v.push(*value),
+        }
+    }
+
+    pub fn nearest(&self, point: (f32, f32)) -> Option<(f32, f32)> {
+        let mut best = None;
+        if let Some(root) = &self.root {
+            Self::nearest_node(root, point, &mut best);
+        }
+        best.map(|(p, _)| p)
+    }
+
+    fn nearest_node(node: &Node, point: (f32, f32), best: &mut Option<((f32, f32), f32)>) {
+        match node {
+            Node::Knot { left, right, .. } => {
+                // closer child first, the other one is often pruned then
+                let mut children: Vec<(&Node, f32)> = left
+                    .iter()
+                    .chain(right.iter())
+                    .map(|child| (&**child, child.summary().0.distance_squared(point)))
+                    .collect();
+                children.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
+                for (child, distance) in children {
+                    if best.map_or(true, |(_, best_distance)| distance < best_distance) {
+                        Self::nearest_node(child, point, best);
+                    }
+                }
+            }
+            Node::Leaf { value, .. } => {
+                let distance = Bounds::point(*value).distance_squared(point);
+                if best.map_or(true, |(_, best_distance)| distance < best_distance) {
+                    *best = Some((*value, distance));
+                }
+            }
+        }
+    }
+
+    // (1 + epsilon)-approximate nearest neighbour visiting at most max_leaves leaves,
+    // returns the point and the achieved bound, see approx_k_nearest
+    pub fn approx_nearest(
+        &self,
+        point: (f32, f32),
+        epsilon: f32,
+        max_leaves: usize,
+    ) -> Option<((f32, f32), f32)> {
+        let (neighbours, bound) = self.approx_k_nearest(point, 1, epsilon, max_leaves);
+        neighbours.first().map(|p| (*p, bound))
+    }
+
+    // up to k points sorted by distance. The search stops once no unvisited point can be
+    // closer than the k-th result divided by 1 + epsilon, or once max_leaves leaves were
+    // visited. The returned bound is the factor the i-th result is at most as far away as
+    // the true i-th nearest neighbour, at most 1 + epsilon unless the budget ran out and
+    // infinite if fewer than k points were found before that.
+    pub fn approx_k_nearest(
+        &self,
+        point: (f32, f32),
+        k: usize,
+        epsilon: f32,
+        max_leaves: usize,
+    ) -> (Vec<(f32, f32)>, f32) {
+        if k == 0 {
+            return (Vec::new(), 1.0);
+        }
+        // candidates and results are compared by squared distance
+        let mut best: Vec<((f32, f32), f32)> = Vec::with_capacity(k + 1);
+        let mut heap = BinaryHeap::new();
+        if let Some(root) = &self.root {
+            heap.push(Candidate {
+                distance: root.summary().0.distance_squared(point),
+                node: root,
+            });
+        }
+        let factor = (1.0 + epsilon) * (1.0 + epsilon);
+        let mut leaves = 0;
+        // lower bound for the distance of every point not looked at yet
+        let mut lower_bound = std::f32::INFINITY;
+
+        while let Some(candidate) = heap.pop() {
+            if best.len() == k && best[k - 1].1 <= candidate.distance * factor {
+                lower_bound = candidate.distance;
+                break;
+            }
+            if leaves == max_leaves {
+                lower_bound = candidate.distance;
+                break;
+            }
+            match candidate.node {
+                Node::Knot { left, right, .. } => {
+                    for child in left.iter().chain(right.iter()) {
+                        heap.push(Candidate {
+                            distance: child.summary().0.distance_squared(point),
+                            node: child,
+                        });
+                    }
+                }
+                Node::Leaf { value, .. } => {
+                    leaves += 1;
+                    let distance = Bounds::point(*value).distance_squared(point);
+                    let position = best
+                        .iter()
+                        .position(|(_, d)| distance < *d)
+                        .unwrap_or_else(|| best.len());
+                    best.insert(position, (*value, distance));
+                    best.truncate(k);
+                }
+            }
+        }
+
+        let bound = if lower_bound == std::f32::INFINITY {
+            // every point was looked at
+            1.0
+        } else if best.len() < k {
+            std::f32::INFINITY
+        } else if lower_bound == 0.0 {
+            if best[k - 1].1 == 0.0 {
+                1.0
+            } else {
+                std::f32::INFINITY
+            }
+        } else {
+            (best[k - 1].1 / lower_bound).sqrt().max(1.0)
+        };
+        (best.into_iter().map(|(p, _)| p).collect(), bound)
+    }
+
+    pub fn range_count(&self, min: (f32, f32), max: (f32, f32)) -> usize {
+        self.range_aggregate(min, max).count
+    }
+
+    pub fn range_aggregate(&self, min: (f32, f32), max: (f32, f32)) -> Aggregate {
+        let query = Bounds { min, max };
+        match &self.root {
+            Some(root) => Self::aggregate(root, &query),
+            None => Aggregate::empty(),
+        }
+    }
+
+    fn aggregate(node: &Node, query: &Bounds) -> Aggregate {
+        let (bounds, aggregate) = node.summary();
+        // subtrees completely inside or outside of the query are answered by their summary
+        if query.contains(&bounds) {
+            return aggregate;
+        }
+        if !query.intersects(&bounds) {
+            return Aggregate::empty();
+        }
+
+        match node {
+            Node::Knot { left, right, .. } => {
+                let mut aggregate = Aggregate::empty();
+                if let Some(left) = left {
+                    aggregate = aggregate.merge(Self::aggregate(left, query));
+                }
+                if let Some(right) = right {
+                    aggregate = aggregate.merge(Self::aggregate(right, query));
+                }
+                aggregate
+            }
+            // a leaf's bounds are its point, so it was either contained or disjoint
+            Node::Leaf { .. } => Aggregate::empty(),
+        }
+    }
+}
+
+fn x_order(a: &((f32, f32), f32), b: &((f32, f32), f32)) -> Ordering {
+    (a.0)
+        .0
+        .partial_cmp(&(b.0).0)
+        .unwrap()
+        .then_with(|| (a.0).1.partial_cmp(&(b.0).1).unwrap())
+}
+
+fn y_order(a: &((f32, f32), f32), b: &((f32, f32), f32)) -> Ordering {
+    (a.0)
+        .1
+        .partial_cmp(&(b.0).1)
+        .unwrap()
+        .then_with(|| (a.0).0.partial_cmp(&(b.0).0).unwrap())
+}
+
+// node waiting in the best-first search of the approximate nearest neighbours
+struct Candidate<'a> {
+    distance: f32,
+    node: &'a Node,
+}
+
+impl<'a> PartialEq for Candidate<'a> {
+    fn eq(&self, other: &Self) -> bool {
+        self.distance == other.distance
+    }
+}
+
+impl<'a> Eq for Candidate<'a> {}
+
+impl<'a> PartialOrd for Candidate<'a> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<'a> Ord for Candidate<'a> {
+    // reversed, the heap pops the closest candidate first
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.distance.partial_cmp(&self.distance).unwrap()
+    }
+}
+
+// what a range query did: nodes it looked at, subtrees it skipped because they lie
+// outside of the query and leaves it reported
+#[derive(Clone, Debug, Default)]
+pub struct QueryStats {
+    pub visited: usize,
+    pub pruned: usize,
+    pub reported: usize,
+    // partition cells of the visited nodes and of the roots of the pruned subtrees,
+    // cells at the border of the partition are unbounded
+    pub visited_cells: Vec<Bounds>,
+    pub pruned_cells: Vec<Bounds>,
+    record_cells: bool,
+}
+
+impl QueryStats {
+    fn prune(&mut self, cell: Bounds) {
+        self.pruned += 1;
+        if self.record_cells {
+            self.pruned_cells.push(cell);
+        }
+    }
+}
+
+// axis aligned rectangle, used for the bounding box of the points below a knot
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Bounds {
+    pub min: (f32, f32),
+    pub max: (f32, f32),
+}
+
+impl Bounds {
+    pub fn point(p: (f32, f32)) -> Self {
+        Bounds { min: p, max: p }
+    }
+
+    pub fn everything() -> Self {
+        Bounds {
+            min: (std::f32::NEG_INFINITY, std::f32::NEG_INFINITY),
+            max: (std::f32::INFINITY, std::f32::INFINITY),
+        }
+    }
+
+    pub fn union(&self, other: &Bounds) -> Bounds {
+        Bounds {
+            min: (self.min.0.min(other.min.0), self.min.1.min(other.min.1)),
+            max: (self.max.0.max(other.max.0), self.max.1.max(other.max.1)),
+        }
+    }
+
+    pub fn contains_point(&self, p: (f32, f32)) -> bool {
+        self.min.0 <= p.0 && p.0 <= self.max.0 && self.min.1 <= p.1 && p.1 <= self.max.1
+    }
+
+    pub fn contains(&self, other: &Bounds) -> bool {
+        self.contains_point(other.min) && self.contains_point(other.max)
+    }
+
+    // squared distance from p to the closest point of the rectangle
+    pub fn distance_squared(&self, p: (f32, f32)) -> f32 {
+        let dx = (self.min.0 - p.0).max(0.0).max(p.0 - self.max.0);
+        let dy = (self.min.1 - p.1).max(0.0).max(p.1 - self.max.1);
+        dx * dx + dy * dy
+    }
+
+    // the two halves on either side of the line of key
+    pub fn split(&self, key: &Key) -> (Bounds, Bounds) {
+        let (mut left, mut right) = (*self, *self);
+        match key.orientation {
+            Orientation::Vertical => {
+                left.max.0 = key.value;
+                right.min.0 = key.value;
+            }
+            Orientation::Horizontal => {
+                left.max.1 = key.value;
+                right.min.1 = key.value;
+            }
+        }
+        (left, right)
+    }
+
+    pub fn intersects(&self, other: &Bounds) -> bool {
+        self.min.0 <= other.max.0
+            && other.min.0 <= self.max.0
+            && self.min.1 <= other.max.1
+            && other.min.1 <= self.max.1
+    }
+}
+
+// count, sum, min and max of the payloads of a set of points,
+// min and max are +/- infinity for an empty set
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Aggregate {
+    pub count: usize,
+    pub sum: f32,
+    pub min: f32,
+    pub max: f32,
+}
+
+impl Aggregate {
+    pub fn empty() -> Self {
+        Aggregate {
+            count: 0,
+            sum: 0.0,
+            min: std::f32::INFINITY,
+            max: std::f32::NEG_INFINITY,
+        }
+    }
+
+    pub fn single(payload: f32) -> Self {
+        Aggregate {
+            count: 1,
+            sum: payload,
+            min: payload,
+            max: payload,
+        }
+    }
+
+    pub fn merge(self, other: Aggregate) -> Self {
+        Aggregate {
+            count: self.count + other.count,
+            sum: self.sum + other.sum,
+            min: self.min.min(other.min),
+            max: self.max.max(other.max),
+        }
+    }
 }
 
 #[derive(Clone, Debug, Copy, PartialEq)]
@@ -207,36 +1035,425 @@ pub enum Orientation {
     Vertical,
 }
 
+impl Orientation {
+    pub fn other(self) -> Orientation {
+        match self {
+            Orientation::Horizontal => Orientation::Vertical,
+            Orientation::Vertical => Orientation::Horizontal,
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct Key {
     pub orientation: Orientation,
     pub value: f32,
 }
 
+impl Key {
+    fn coord(&self, point: (f32, f32)) -> f32 {
+        match self.orientation {
+            Orientation::Horizontal => point.1,
+            Orientation::Vertical => point.0,
+        }
+    }
+
+    // side a newly inserted point is put into, points equal to the key go right
+    fn goes_left(&self, point: (f32, f32)) -> bool {
+        self.coord(point) < self.value
+    }
+
+    fn may_go_left(&self, point: (f32, f32)) -> bool {
+        self.coord(point) <= self.value
+    }
+
+    fn may_go_right(&self, point: (f32, f32)) -> bool {
+        self.value <= self.coord(point)
+    }
+}
+
 #[derive(Clone, Debug)]
 pub enum Node {
     Knot {
         key: Key,
+        // bounding box of all points below this knot
+        bounds: Bounds,
+        // payloads of all points below, aggregate.count is the size of the subtree
+        aggregate: Aggregate,
         left: Option<Box<Node>>,
         right: Option<Box<Node>>,
     },
     Leaf {
         value: (f32, f32),
+        payload: f32,
     },
 }
 
+impl Node {
+    pub fn leaf(value: (f32, f32), payload: f32) -> Box<Node> {
+        Box::new(Node::Leaf { value, payload })
+    }
+
+    pub fn knot(key: Key, left: Option<Box<Node>>, right: Option<Box<Node>>) -> Box<Node> {
+        let mut node = Box::new(Node::Knot {
+            key,
+            bounds: Bounds::point((0.0, 0.0)),
+            aggregate: Aggregate::empty(),
+            left,
+            right,
+        });
+        node.update_summary();
+        node
+    }
+
+    pub fn size(&self) -> usize {
+        match self {
+            Node::Knot { aggregate, .. } => aggregate.count,
+            Node::Leaf { .. } => 1,
+        }
+    }
+
+    pub fn summary(&self) -> (Bounds, Aggregate) {
+        match self {
+            Node::Knot {
+                bounds, aggregate, ..
+            } => (*bounds, *aggregate),
+            Node::Leaf { value, payload } => (Bounds::point(*value), Aggregate::single(*payload)),
+        }
+    }
+
+    // recomputes bounds and aggregate of a knot from its children
+    fn update_summary(&mut self) {
+        if let Node::Knot {
+            bounds,
+            aggregate,
+            left,
+            right,
+            ..
+        } = self
+        {
+            let summaries = left.iter().chain(right.iter()).map(|node| node.summary());
+            let mut new_bounds: Option<Bounds> = None;
+            let mut new_aggregate = Aggregate::empty();
+            for (b, a) in summaries {
+                new_bounds = Some(new_bounds.map_or(b, |bounds| bounds.union(&b)));
+                new_aggregate = new_aggregate.merge(a);
+            }
+            if let Some(new_bounds) = new_bounds {
+                *bounds = new_bounds;
+            }
+            *aggregate = new_aggregate;
+        }
+    }
+
+    fn is_unbalanced(&self) -> bool {
+        match self {
+            Node::Knot { left, right, .. } => {
+                let left = left.as_ref().map_or(0, |node| node.size());
+                let right = right.as_ref().map_or(0, |node| node.size());
+                ALPHA * (self.size() as f32) < left.max(right) as f32
+            }
+            Node::Leaf { .. } => false,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::shape::{Circle, ConvexPolygon, HalfPlane};
+    use crate::test_util::{random_lattice, sorted};
 
     fn _point_list() -> Vec<(f32, f32)> {
         vec![(20.0, 20.0), (10.0, 15.0), (15.0, 5.0), (-20.1, 24.0)]
     }
 
     #[test]
-    #[should_panic]
-    fn construct_kd_tree_from_empty_list_panics() {
+    fn construct_kd_tree_from_empty_list() {
         let points: Vec<(f32, f32)> = Vec::new();
-        KdTree::new(&points);
+        let tree = KdTree::new(&points);
+        assert!(tree.is_empty());
+        assert_eq!(tree.range_query((0.0, 0.0), (10.0, 10.0)), Vec::new());
+    }
+
+    #[test]
+    fn equal_points_are_kept_once() {
+        let mut points = _point_list();
+        points.extend(_point_list());
+        points.push((10.0, 15.0));
+        for tree in &[KdTree::new(&points), KdTree::with_threads(&points, 2)] {
+            assert_eq!(tree.len(), 4);
+            assert_eq!(sorted(tree.points()), sorted(_point_list()));
+            assert_eq!(tree.range_count((-30.0, 0.0), (30.0, 30.0)), 4);
+        }
+
+        let entries = vec![((1.0, 1.0), 5.0), ((2.0, 2.0), 1.0), ((1.0, 1.0), 7.0)];
+        let tree = KdTree::with_payloads(&entries);
+        assert_eq!(
+            sorted(tree.entries()),
+            vec![((1.0, 1.0), 5.0), ((2.0, 2.0), 1.0)]
+        );
+
+        let mut tree = KdTree::new(&points);
+        for point in &points {
+            assert!(!tree.insert(*point));
+        }
+        assert_eq!(tree.len(), 4);
+    }
+
+    #[test]
+    fn reinserting_points_with_equal_keys() {
+        // equal key coordinates end up on both sides of a knot in the build
+        let row: Vec<(f32, f32)> = (0..64).map(|i| (i as f32, 5.0)).collect();
+        let mut tree = KdTree::new(&row);
+        for point in &row {
+            assert!(!tree.insert(*point));
+        }
+        assert_eq!(tree.len(), row.len());
+        let rebuilt = KdTree::new(&row);
+        assert_eq!(
+            sorted(tree.range_query((0.0, 0.0), (100.0, 10.0))),
+            sorted(rebuilt.range_query((0.0, 0.0), (100.0, 10.0)))
+        );
+
+        let column: Vec<(f32, f32)> = (0..10).map(|i| (1.0, i as f32)).collect();
+        let mut tree = KdTree::new(&[]);
+        for point in &column {
+            assert!(tree.insert(*point));
+        }
+        for point in &column {
+            assert!(!tree.insert(*point));
+        }
+        assert_eq!(tree.len(), column.len());
+        assert_eq!(sorted(tree.points()), sorted(KdTree::new(&column).points()));
+    }
+
+    #[test]
+    fn removing_last_point_leaves_empty_tree() {
+        let mut tree = KdTree::new(&_point_list());
+        for point in _point_list() {
+            assert!(tree.remove(point));
+        }
+        assert!(tree.is_empty());
+        assert!(!tree.remove((20.0, 20.0)));
+        assert!(tree.insert((20.0, 20.0)));
+        assert_eq!(tree.points(), vec![(20.0, 20.0)]);
+    }
+
+    #[test]
+    fn insert_and_remove_match_rebuild() {
+        let points = random_lattice(300, 7);
+        let mut tree = KdTree::new(&[]);
+        let mut present = Vec::new();
+        for (i, point) in points.iter().enumerate() {
+            if present.contains(point) {
+                assert!(!tree.insert(*point));
+            } else {
+                assert!(tree.insert(*point));
+                present.push(*point);
+            }
+            // remove every third point again
+            if i % 3 == 2 {
+                let point = present.remove(i % present.len());
+                assert!(tree.remove(point));
+            }
+        }
+        assert_eq!(tree.len(), present.len());
+        assert_eq!(
+            tree.range_count((0.0, 0.0), (1000.0, 1000.0)),
+            present.len()
+        );
+
+        let rebuilt = KdTree::new(&present);
+        for window in random_lattice(50, 11).chunks(2) {
+            let min = (window[0].0.min(window[1].0), window[0].1.min(window[1].1));
+            let max = (window[0].0.max(window[1].0), window[0].1.max(window[1].1));
+            assert_eq!(
+                sorted(tree.range_query(min, max)),
+                sorted(rebuilt.range_query(min, max))
+            );
+        }
+    }
+
+    #[test]
+    fn instrumented_query_accounts_for_every_node() {
+        let tree = KdTree::new(&random_lattice(500, 3));
+        let (min, max) = ((200.0, 350.0), (420.0, 500.0));
+        let query = Bounds { min, max };
+        let (points, stats) = tree.range_query_instrumented(min, max);
+        assert_eq!(sorted(points.clone()), sorted(tree.range_query(min, max)));
+        assert_eq!(stats.reported, points.len());
+        assert_eq!(stats.visited, tree.range_query_visited(min, max).1);
+        assert_eq!(stats.visited_cells.len(), stats.visited);
+        assert_eq!(stats.pruned_cells.len(), stats.pruned);
+        assert!(0 < stats.pruned);
+
+        // pruned cells lie outside of the query, so no reported point can be in one
+        for cell in &stats.pruned_cells {
+            assert!(!cell.intersects(&query));
+        }
+        for point in &points {
+            assert!(stats.visited_cells.iter().any(|c| c.contains_point(*point)));
+        }
+    }
+
+    #[test]
+    fn cells_partition_the_plane() {
+        let mut points = random_lattice(300, 11);
+        // equal keys must not produce empty or inverted cells
+        points.extend((0..20).map(|i| (500.0, i as f32)));
+        let tree = KdTree::new(&points);
+        let mut leaves = Vec::new();
+        let mut knots = 0;
+        tree.visit_cells(|node, cell| {
+            assert!(cell.min.0 <= cell.max.0 && cell.min.1 <= cell.max.1);
+            match node {
+                Node::Knot { .. } => knots += 1,
+                Node::Leaf { value, .. } => {
+                    assert!(cell.contains_point(*value));
+                    leaves.push(*value);
+                }
+            }
+        });
+        assert_eq!(sorted(leaves), sorted(tree.points()));
+        assert_eq!(tree.partition_lines().len(), knots);
+    }
+
+    #[test]
+    fn shape_query_matches_brute_force() {
+        let points = random_lattice(600, 5);
+        let tree = KdTree::new(&points);
+        let shapes: Vec<Box<dyn Shape>> = vec![
+            box Circle {
+                center: (400.0, 600.0),
+                radius: 220.0,
+            },
+            box HalfPlane {
+                a: (0.0, 300.0),
+                b: (1000.0, 500.0),
+            },
+            box ConvexPolygon::triangle((100.0, 100.0), (900.0, 200.0), (300.0, 800.0)),
+            box ConvexPolygon::new(&[(200.0, 0.0), (700.0, 100.0), (800.0, 600.0), (100.0, 500.0)]),
+        ];
+        for shape in &shapes {
+            let mut expected = sorted(
+                points
+                    .iter()
+                    .cloned()
+                    .filter(|p| shape.contains(*p))
+                    .collect(),
+            );
+            expected.dedup();
+            assert_eq!(sorted(tree.shape_query(&**shape)), expected);
+        }
+    }
+
+    #[test]
+    fn approx_k_nearest_keeps_its_bound() {
+        let points = random_lattice(2000, 17);
+        let tree = KdTree::new(&points);
+        let distance = |a: (f32, f32), b: (f32, f32)| Bounds::point(a).distance_squared(b).sqrt();
+        for (i, query) in random_lattice(40, 23).into_iter().enumerate() {
+            let mut exact = tree.points();
+            exact.sort_by(|a, b| {
+                distance(*a, query)
+                    .partial_cmp(&distance(*b, query))
+                    .unwrap()
+            });
+            let k = 1 + i % 8;
+            let epsilon = (i % 4) as f32 * 0.25;
+            // an unlimited budget and a tight one that may run out
+            for max_leaves in [usize::max_value(), 12].iter() {
+                let (neighbours, bound) = tree.approx_k_nearest(query, k, epsilon, *max_leaves);
+                assert_eq!(neighbours.len(), k);
+                if *max_leaves == usize::max_value() {
+                    assert!(bound <= 1.0 + epsilon);
+                }
+                for (j, neighbour) in neighbours.iter().enumerate() {
+                    let (approx, exact) = (distance(*neighbour, query), distance(exact[j], query));
+                    assert!(
+                        approx <= bound * exact + 1e-3,
+                        "{} > {} * {}",
+                        approx,
+                        bound,
+                        exact
+                    );
+                }
+            }
+        }
+        // epsilon 0 is exact
+        let query = (500.5, 499.5);
+        let (nearest, bound) = tree.approx_nearest(query, 0.0, usize::max_value()).unwrap();
+        assert_eq!(bound, 1.0);
+        assert_eq!(
+            distance(nearest, query),
+            distance(tree.nearest(query).unwrap(), query)
+        );
+        assert!(KdTree::new(&[]).approx_nearest(query, 0.5, 10).is_none());
+    }
+
+    #[test]
+    fn parallel_build_and_batch_queries_match_sequential() {
+        // enough points for a few levels of threads, with duplicates
+        let points = random_lattice(20000, 29);
+        let sequential = KdTree::new(&points);
+        let parallel = KdTree::with_threads(&points, 4);
+        assert_eq!(parallel.len(), sequential.len());
+        assert_eq!(parallel.partition_lines(), sequential.partition_lines());
+
+        let tree = Arc::new(parallel);
+        let queries: Vec<((f32, f32), (f32, f32))> = random_lattice(50, 31)
+            .into_iter()
+            .map(|p| (p, (p.0 + 80.0, p.1 + 40.0)))
+            .collect();
+        let results = KdTree::batch_range_query(&tree, &queries, 3);
+        assert_eq!(results.len(), queries.len());
+        for (result, query) in results.into_iter().zip(&queries) {
+            assert_eq!(
+                sorted(result),
+                sorted(sequential.range_query(query.0, query.1))
+            );
+        }
+        let points = random_lattice(50, 37);
+        let nearest = KdTree::batch_nearest(&tree, &points, 8);
+        for (nearest, point) in nearest.into_iter().zip(&points) {
+            assert_eq!(nearest, sequential.nearest(*point));
+        }
+        assert!(KdTree::batch_nearest(&tree, &[], 4).is_empty());
+    }
+
+    #[test]
+    fn range_aggregate_matches_brute_force() {
+        let entries: Vec<((f32, f32), f32)> = random_lattice(400, 3)
+            .into_iter()
+            .zip(random_lattice(400, 5))
+            .map(|(point, (payload, _))| (point, payload))
+            .collect();
+        let mut tree = KdTree::with_payloads(&entries);
+        // a few more points inserted later, so updated summaries are checked as well
+        for (point, payload) in random_lattice(20, 9)
+            .into_iter()
+            .zip(random_lattice(20, 13))
+        {
+            tree.insert_with_payload(point, payload.0);
+        }
+        let entries = tree.entries();
+
+        for window in random_lattice(60, 21).chunks(2) {
+            let min = (window[0].0.min(window[1].0), window[0].1.min(window[1].1));
+            let max = (window[0].0.max(window[1].0), window[0].1.max(window[1].1));
+            let expected = entries
+                .iter()
+                .filter(|(p, _)| min.0 <= p.0 && p.0 <= max.0 && min.1 <= p.1 && p.1 <= max.1)
+                .fold(Aggregate::empty(), |a, (_, payload)| {
+                    a.merge(Aggregate::single(*payload))
+                });
+            let aggregate = tree.range_aggregate(min, max);
+            assert_eq!(aggregate.count, expected.count);
+            assert_eq!(aggregate.min, expected.min);
+            assert_eq!(aggregate.max, expected.max);
+            assert!((aggregate.sum - expected.sum).abs() < 1e-2);
+            assert_eq!(tree.range_count(min, max), tree.range_query(min, max).len());
+        }
     }
 }
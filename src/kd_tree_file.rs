@@ -0,0 +1,387 @@
+use crate::kd_tree::{Bounds, KdTree, Key, Node, Orientation};
+
+use std::convert::TryInto;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+// Binary format of a kd-tree, all numbers little endian.
+// header, 16 bytes: magic "KDTR", version u32, orientation of even levels u32
+// (0 horizontal, 1 vertical), number of nodes u32
+// then one 16 byte record per node in pre-order:
+//   0: tag u8, 0 leaf, 1 vertical knot, 2 horizontal knot
+//   1: children u8, bit 0 left child, bit 1 right child
+//   2..4: zero
+//   leaf: x f32, y f32, payload f32
+//   knot: key f32, index of the right child u32, zero u32
+// the left child of a knot is the record right after it.
+// Records have a fixed size and no pointers, so a mapped file can be queried in place.
+const MAGIC: &[u8; 4] = b"KDTR";
+const VERSION: u32 = 1;
+const HEADER_SIZE: usize = 16;
+const RECORD_SIZE: usize = 16;
+
+const TAG_LEAF: u8 = 0;
+const TAG_VERTICAL: u8 = 1;
+const TAG_HORIZONTAL: u8 = 2;
+const HAS_LEFT: u8 = 1;
+const HAS_RIGHT: u8 = 2;
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+fn read_f32(bytes: &[u8], offset: usize) -> f32 {
+    f32::from_bits(read_u32(bytes, offset))
+}
+
+impl KdTree {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut records = Vec::new();
+        if let Some(root) = self.root() {
+            write_node(root, &mut records);
+        }
+        let orientation = match self.orientation_even() {
+            Orientation::Horizontal => 0u32,
+            Orientation::Vertical => 1u32,
+        };
+        let mut bytes = Vec::with_capacity(HEADER_SIZE + records.len());
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&VERSION.to_le_bytes());
+        bytes.extend_from_slice(&orientation.to_le_bytes());
+        bytes.extend_from_slice(&((records.len() / RECORD_SIZE) as u32).to_le_bytes());
+        bytes.extend_from_slice(&records);
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> io::Result<KdTree> {
+        let view = KdTreeView::new(bytes)?;
+        let root = if view.node_count == 0 {
+            None
+        } else {
+            Some(view.build_node(0))
+        };
+        Ok(KdTree::from_root(root, view.orientation_even))
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        fs::write(path, self.to_bytes())
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<KdTree> {
+        KdTree::from_bytes(&fs::read(path)?)
+    }
+}
+
+fn write_node(node: &Node, records: &mut Vec<u8>) {
+    let start = records.len();
+    records.extend_from_slice(&[0; RECORD_SIZE]);
+    match node {
+        Node::Knot {
+            key, left, right, ..
+        } => {
+            records[start] = match key.orientation {
+                Orientation::Vertical => TAG_VERTICAL,
+                Orientation::Horizontal => TAG_HORIZONTAL,
+            };
+            records[start + 4..start + 8].copy_from_slice(&key.value.to_bits().to_le_bytes());
+            if let Some(left) = left {
+                records[start + 1] |= HAS_LEFT;
+                write_node(left, records);
+            }
+            if let Some(right) = right {
+                records[start + 1] |= HAS_RIGHT;
+                let index = (records.len() / RECORD_SIZE) as u32;
+                records[start + 8..start + 12].copy_from_slice(&index.to_le_bytes());
+                write_node(right, records);
+            }
+        }
+        Node::Leaf { value, payload } => {
+            records[start] = TAG_LEAF;
+            for (i, x) in [value.0, value.1, *payload].iter().enumerate() {
+                let offset = start + 4 + 4 * i;
+                records[offset..offset + 4].copy_from_slice(&x.to_bits().to_le_bytes());
+            }
+        }
+    }
+}
+
+enum Record {
+    Knot {
+        key: Key,
+        left: Option<usize>,
+        right: Option<usize>,
+    },
+    Leaf {
+        value: (f32, f32),
+        payload: f32,
+    },
+}
+
+// queries a serialized tree without building its nodes
+#[derive(Clone, Copy, Debug)]
+pub struct KdTreeView<'a> {
+    records: &'a [u8],
+    node_count: usize,
+    len: usize,
+    orientation_even: Orientation,
+}
+
+impl<'a> KdTreeView<'a> {
+    // checks the header and that the records form a single tree
+    pub fn new(bytes: &'a [u8]) -> io::Result<Self> {
+        if bytes.len() < HEADER_SIZE || &bytes[..4] != MAGIC {
+            return Err(invalid("not a kd-tree file"));
+        }
+        if read_u32(bytes, 4) != VERSION {
+            return Err(invalid("unsupported kd-tree file version"));
+        }
+        let orientation_even = match read_u32(bytes, 8) {
+            0 => Orientation::Horizontal,
+            1 => Orientation::Vertical,
+            _ => return Err(invalid("invalid orientation")),
+        };
+        let node_count = read_u32(bytes, 12) as usize;
+        let records = &bytes[HEADER_SIZE..];
+        if records.len() != node_count * RECORD_SIZE {
+            return Err(invalid("kd-tree file has the wrong size"));
+        }
+
+        let mut view = KdTreeView {
+            records,
+            node_count,
+            len: 0,
+            orientation_even,
+        };
+        // children always come after their parent, so the walk ends
+        let mut seen = vec![false; node_count];
+        let mut stack = if node_count == 0 { vec![] } else { vec![0] };
+        while let Some(i) = stack.pop() {
+            if seen[i] {
+                return Err(invalid("kd-tree node is referenced twice"));
+            }
+            seen[i] = true;
+            let tag = records[i * RECORD_SIZE];
+            let children = records[i * RECORD_SIZE + 1];
+            match tag {
+                TAG_LEAF => view.len += 1,
+                TAG_VERTICAL | TAG_HORIZONTAL => {
+                    if children & (HAS_LEFT | HAS_RIGHT) == 0 {
+                        return Err(invalid("kd-tree knot without children"));
+                    }
+                    if let Record::Knot { left, right, .. } = view.record(i) {
+                        for child in left.iter().chain(right.iter()) {
+                            if *child <= i || node_count <= *child {
+                                return Err(invalid("kd-tree child index out of range"));
+                            }
+                            stack.push(*child);
+                        }
+                    }
+                }
+                _ => return Err(invalid("invalid kd-tree node")),
+            }
+        }
+        if seen.iter().any(|seen| !seen) {
+            return Err(invalid("kd-tree file has unreachable nodes"));
+        }
+        Ok(view)
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn record(&self, i: usize) -> Record {
+        let offset = i * RECORD_SIZE;
+        let r = &self.records[offset..offset + RECORD_SIZE];
+        match r[0] {
+            TAG_LEAF => Record::Leaf {
+                value: (read_f32(r, 4), read_f32(r, 8)),
+                payload: read_f32(r, 12),
+            },
+            tag => Record::Knot {
+                key: Key {
+                    orientation: if tag == TAG_VERTICAL {
+                        Orientation::Vertical
+                    } else {
+                        Orientation::Horizontal
+                    },
+                    value: read_f32(r, 4),
+                },
+                left: if r[1] & HAS_LEFT != 0 {
+                    Some(i + 1)
+                } else {
+                    None
+                },
+                right: if r[1] & HAS_RIGHT != 0 {
+                    Some(read_u32(r, 8) as usize)
+                } else {
+                    None
+                },
+            },
+        }
+    }
+
+    fn build_node(&self, i: usize) -> Box<Node> {
+        match self.record(i) {
+            Record::Knot { key, left, right } => Node::knot(
+                key,
+                left.map(|left| self.build_node(left)),
+                right.map(|right| self.build_node(right)),
+            ),
+            Record::Leaf { value, payload } => Node::leaf(value, payload),
+        }
+    }
+
+    pub fn range_query(&self, min: (f32, f32), max: (f32, f32)) -> Vec<(f32, f32)> {
+        let mut v = Vec::new();
+        if self.node_count == 0 {
+            return v;
+        }
+        let query = Bounds { min, max };
+        let mut stack = vec![0];
+        while let Some(i) = stack.pop() {
+            match self.record(i) {
+                Record::Knot { key, left, right } => {
+                    let (lower, upper) = match key.orientation {
+                        Orientation::Vertical => (min.0, max.0),
+                        Orientation::Horizontal => (min.1, max.1),
+                    };
+                    if let Some(right) = right {
+                        if key.value <= upper {
+                            stack.push(right);
+                        }
+                    }
+                    if let Some(left) = left {
+                        if lower <= key.value {
+                            stack.push(left);
+                        }
+                    }
+                }
+                Record::Leaf { value, .. } => {
+                    if query.contains_point(value) {
+                        v.push(value);
+                    }
+                }
+            }
+        }
+        v
+    }
+
+    pub fn nearest(&self, point: (f32, f32)) -> Option<(f32, f32)> {
+        let mut best = None;
+        if self.node_count != 0 {
+            self.nearest_node(0, Bounds::everything(), point, &mut best);
+        }
+        best.map(|(p, _)| p)
+    }
+
+    // cell is the part of the plane the partition assigns to node i
+    fn nearest_node(
+        &self,
+        i: usize,
+        cell: Bounds,
+        point: (f32, f32),
+        best: &mut Option<((f32, f32), f32)>,
+    ) {
+        match self.record(i) {
+            Record::Knot { key, left, right } => {
+                let (left_cell, right_cell) = cell.split(&key);
+                let mut children: Vec<(usize, Bounds, f32)> = left
+                    .map(|left| (left, left_cell))
+                    .into_iter()
+                    .chain(right.map(|right| (right, right_cell)))
+                    .map(|(child, cell)| (child, cell, cell.distance_squared(point)))
+                    .collect();
+                children.sort_by(|a, b| a.2.partial_cmp(&b.2).unwrap());
+                for (child, cell, distance) in children {
+                    if best.map_or(true, |(_, best_distance)| distance < best_distance) {
+                        self.nearest_node(child, cell, point, best);
+                    }
+                }
+            }
+            Record::Leaf { value, .. } => {
+                let distance = Bounds::point(value).distance_squared(point);
+                if best.map_or(true, |(_, best_distance)| distance < best_distance) {
+                    *best = Some((value, distance));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::sorted;
+
+    fn points() -> Vec<((f32, f32), f32)> {
+        (0..500)
+            .map(|i| {
+                let p = (
+                    ((i * 37) % 211) as f32 * 1.5,
+                    ((i * 91) % 199) as f32 - 50.0,
+                );
+                (p, i as f32)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn round_trip_and_view_queries() {
+        let tree = KdTree::with_payloads(&points());
+        let bytes = tree.to_bytes();
+        assert_eq!(bytes.len() % 16, 0);
+
+        let loaded = KdTree::from_bytes(&bytes).unwrap();
+        assert_eq!(loaded.entries(), tree.entries());
+        assert_eq!(loaded.partition_lines(), tree.partition_lines());
+        assert_eq!(loaded.to_bytes(), bytes);
+
+        let view = KdTreeView::new(&bytes).unwrap();
+        assert_eq!(view.len(), tree.len());
+        for i in 0..40 {
+            let min = ((i * 7) as f32, (i * 3) as f32 - 60.0);
+            let max = (min.0 + 45.0, min.1 + 30.0);
+            assert_eq!(
+                sorted(view.range_query(min, max)),
+                sorted(tree.range_query(min, max))
+            );
+            let query = (min.0 + 0.3, max.1 - 0.7);
+            let distance =
+                |p: Option<(f32, f32)>| Bounds::point(p.unwrap()).distance_squared(query);
+            assert_eq!(distance(view.nearest(query)), distance(tree.nearest(query)));
+        }
+
+        let empty = KdTree::new(&[]).to_bytes();
+        assert!(KdTree::from_bytes(&empty).unwrap().is_empty());
+        assert_eq!(KdTreeView::new(&empty).unwrap().nearest((0.0, 0.0)), None);
+    }
+
+    #[test]
+    fn rejects_damaged_files() {
+        let bytes = KdTree::new(&[(1.0, 2.0), (3.0, 4.0), (5.0, 0.0)]).to_bytes();
+        assert!(KdTree::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+
+        let mut wrong_magic = bytes.clone();
+        wrong_magic[0] = b'X';
+        assert!(KdTree::from_bytes(&wrong_magic).is_err());
+
+        let mut wrong_version = bytes.clone();
+        wrong_version[4] = 2;
+        assert!(KdTree::from_bytes(&wrong_version).is_err());
+
+        // the root's right child pointing back at the root
+        let mut cycle = bytes.clone();
+        cycle[HEADER_SIZE + 8..HEADER_SIZE + 12].copy_from_slice(&0u32.to_le_bytes());
+        assert!(KdTree::from_bytes(&cycle).is_err());
+    }
+}
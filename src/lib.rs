@@ -0,0 +1,34 @@
+#![feature(box_syntax)]
+#![feature(vec_remove_item)]
+#![feature(drain_filter)]
+#![cfg_attr(test, feature(test))]
+
+extern crate ggez;
+#[macro_use]
+extern crate log;
+#[cfg(test)]
+extern crate test;
+
+pub mod cdt;
+pub mod convex_hull;
+pub mod flat_kd_tree;
+pub mod fortune;
+pub mod grid;
+pub mod interpolation;
+pub mod intersection;
+pub mod kd_tree;
+pub mod kd_tree_file;
+pub mod math;
+pub mod mesh;
+pub mod polygon;
+pub mod priority_search_tree;
+pub mod proximity;
+pub mod quadtree;
+pub mod range_tree;
+pub mod rtree;
+pub mod shape;
+pub mod spatial_index;
+#[cfg(test)]
+mod test_util;
+pub mod triangulation;
+pub mod voronoi;
@@ -11,12 +11,13 @@ extern crate log;
 use ggez::graphics::Point2;
 use ggez::*;
 
+// the algorithms are in the library, the states are the ui on top of them
+use ::convex_hull::{
+    cdt, convex_hull, fortune, intersection, kd_tree, mesh, polygon, priority_search_tree,
+    proximity, range_tree, shape, spatial_index, triangulation, voronoi,
+};
+
 // mod point_state;
-mod convex_hull;
-mod intersection;
-mod kd_tree;
-mod math;
-mod triangulation;
 mod states;
 
 fn _everything_is_convex() -> Vec<Point2> {
@@ -53,13 +53,14 @@ pub fn point_relative_to_triangle(p: Point2, triangle: &[Point2]) -> (f32, f32)
     let b = triangle[1];
     let c = triangle[0];
 
-    let s1 = c[1] - a[1];
-    let s2 = c[0] - a[0];
-    let s3 = b[1] - a[1];
-    let s4 = p[1] - a[1];
-
-    let w1 = (a[0] * s1 + s4 * s2 - p[0] * s1) / (s3 * s2 - (b[0] - a[0]) * s1);
-    let w2 = (s4 - w1 * s3) / s1;
+    // p = a + w1 * (b - a) + w2 * (c - a) solved with Cramer's rule, which unlike
+    // eliminating by hand does not break down on horizontal edges
+    let (ux, uy) = (b[0] - a[0], b[1] - a[1]);
+    let (vx, vy) = (c[0] - a[0], c[1] - a[1]);
+    let (px, py) = (p[0] - a[0], p[1] - a[1]);
+    let det = ux * vy - uy * vx;
+    let w1 = (px * vy - py * vx) / det;
+    let w2 = (ux * py - uy * px) / det;
 
     (w1, w2)
 }
@@ -100,6 +101,35 @@ fn triangle_is_ccw(triangle: &[Point2]) -> bool {
     !left_turn(triangle)
 }
 
+// twice the signed area of the triangle, positive if a, b and c are counter clockwise
+pub fn orient(a: (f64, f64), b: (f64, f64), c: (f64, f64)) -> f64 {
+    (b.0 - a.0) * (c.1 - a.1) - (b.1 - a.1) * (c.0 - a.0)
+}
+
+// whether d lies strictly inside the circumcircle of the counter clockwise triangle a, b, c
+pub fn in_circle(a: (f64, f64), b: (f64, f64), c: (f64, f64), d: (f64, f64)) -> bool {
+    let (a, b, c) = (
+        (a.0 - d.0, a.1 - d.1),
+        (b.0 - d.0, b.1 - d.1),
+        (c.0 - d.0, c.1 - d.1),
+    );
+    let det = (a.0 * a.0 + a.1 * a.1) * (b.0 * c.1 - c.0 * b.1)
+        + (b.0 * b.0 + b.1 * b.1) * (c.0 * a.1 - a.0 * c.1)
+        + (c.0 * c.0 + c.1 * c.1) * (a.0 * b.1 - b.0 * a.1);
+    0.0 < det
+}
+
+// relative to a to keep the precision for thin triangles
+pub fn circumcenter(a: (f64, f64), b: (f64, f64), c: (f64, f64)) -> (f64, f64) {
+    let (b, c) = ((b.0 - a.0, b.1 - a.1), (c.0 - a.0, c.1 - a.1));
+    let d = 2.0 * (b.0 * c.1 - b.1 * c.0);
+    let (sb, sc) = (b.0 * b.0 + b.1 * b.1, c.0 * c.0 + c.1 * c.1);
+    (
+        a.0 + (c.1 * sb - b.1 * sc) / d,
+        a.1 + (b.0 * sc - c.0 * sb) / d,
+    )
+}
+
 #[cfg(test)]
 mod tests {
     // use ggez::graphics::Point2;
@@ -128,4 +158,31 @@ mod tests {
         ];
         assert_eq!(left_turn(&is_right[..]), false);
     }
+
+    #[test]
+    fn point_relative_to_triangle_with_horizontal_edge() {
+        // the edge from the last to the first point is horizontal
+        let triangle = vec![
+            Point2::new(4.0, 0.0),
+            Point2::new(0.0, 3.0),
+            Point2::new(0.0, 0.0),
+        ];
+        let (w1, w2) = point_relative_to_triangle(Point2::new(1.0, 1.0), &triangle[..]);
+        assert!((w1 - 1.0 / 3.0).abs() < 1e-6);
+        assert!((w2 - 0.25).abs() < 1e-6);
+        assert!(point_in_triangle(Point2::new(1.0, 1.0), &triangle[..]));
+        assert!(!point_in_triangle(Point2::new(-1.0, 1.0), &triangle[..]));
+    }
+
+    #[test]
+    fn orient_in_circle_and_circumcenter() {
+        let (a, b, c) = ((0.0, 0.0), (4.0, 0.0), (0.0, 4.0));
+        assert_eq!(orient(a, b, c), 16.0);
+        assert_eq!(orient(a, c, b), -16.0);
+        assert_eq!(orient(a, b, (2.0, 0.0)), 0.0);
+        assert_eq!(circumcenter(a, b, c), (2.0, 2.0));
+        assert!(in_circle(a, b, c, (3.0, 3.0)));
+        assert!(!in_circle(a, b, c, (4.0, 4.0)));
+        assert!(!in_circle(a, b, c, (5.0, 5.0)));
+    }
 }
@@ -0,0 +1,365 @@
+use ggez::graphics::Point2;
+use std::collections::HashMap;
+
+// Triangle mesh over a point set. Triangles store indices into the points, counter
+// clockwise in a y-up coordinate system, points that are not used by any triangle
+// (duplicates, collinear input) are kept so indices stay valid.
+//
+// Every triangle t has the half-edges 3t, 3t + 1 and 3t + 2, half-edge 3t + i runs from
+// vertex i to vertex i + 1 of t. Neighbour i of a triangle lies across the edge opposite
+// of vertex i, so it is the triangle of the twin of half-edge 3t + i + 1.
+#[derive(Clone, Debug, Default)]
+pub struct Mesh {
+    points: Vec<Point2>,
+    triangles: Vec<[usize; 3]>,
+    neighbours: Vec<[Option<usize>; 3]>,
+    // Outgoing half-edges that start a fan of triangles around their origin, the fans of
+    // vertex v are fans[fan_offsets[v]..fan_offsets[v + 1]]. On the boundary a fan starts
+    // at the first edge counter clockwise. Vertices where triangles only touch in the
+    // vertex, like the pinched ones left by Cdt::mesh_with_holes, have more than one fan.
+    fans: Vec<usize>,
+    fan_offsets: Vec<usize>,
+}
+
+impl Mesh {
+    // triangles have to be counter clockwise and share edges only with one other triangle
+    pub fn new(points: Vec<Point2>, triangles: Vec<[usize; 3]>) -> Self {
+        let mut edges = HashMap::with_capacity(3 * triangles.len());
+        for (t, triangle) in triangles.iter().enumerate() {
+            for i in 0..3 {
+                edges.insert((triangle[i], triangle[(i + 1) % 3]), 3 * t + i);
+            }
+        }
+        let mut mesh = Mesh {
+            neighbours: vec![[None; 3]; triangles.len()],
+            fans: Vec::new(),
+            fan_offsets: vec![0; points.len() + 1],
+            points,
+            triangles,
+        };
+        for t in 0..mesh.triangles.len() {
+            for i in 0..3 {
+                let (a, b) = (
+                    mesh.triangles[t][(i + 1) % 3],
+                    mesh.triangles[t][(i + 2) % 3],
+                );
+                mesh.neighbours[t][i] = edges.get(&(b, a)).map(|e| e / 3);
+            }
+        }
+
+        // every boundary edge starts a fan, a vertex without one has a single closed fan
+        let mut any_edge = vec![None; mesh.points.len()];
+        let mut starts = Vec::new();
+        for e in 0..mesh.half_edge_count() {
+            let origin = mesh.origin(e);
+            any_edge[origin] = Some(e);
+            if mesh.twin(e).is_none() {
+                starts.push((origin, e));
+            }
+        }
+        let mut on_boundary = vec![false; mesh.points.len()];
+        for (v, _) in &starts {
+            on_boundary[*v] = true;
+        }
+        for (v, e) in any_edge.into_iter().enumerate() {
+            if let (Some(e), false) = (e, on_boundary[v]) {
+                starts.push((v, e));
+            }
+        }
+        starts.sort();
+        for (v, _) in &starts {
+            mesh.fan_offsets[v + 1] += 1;
+        }
+        for v in 0..mesh.points.len() {
+            mesh.fan_offsets[v + 1] += mesh.fan_offsets[v];
+        }
+        mesh.fans = starts.into_iter().map(|(_, e)| e).collect();
+        mesh
+    }
+
+    pub fn points(&self) -> &[Point2] {
+        &self.points
+    }
+
+    pub fn triangles(&self) -> &[[usize; 3]] {
+        &self.triangles
+    }
+
+    pub fn neighbours(&self) -> &[[Option<usize>; 3]] {
+        &self.neighbours
+    }
+
+    pub fn len(&self) -> usize {
+        self.triangles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.triangles.is_empty()
+    }
+
+    pub fn triangle(&self, t: usize) -> [Point2; 3] {
+        let [a, b, c] = self.triangles[t];
+        [self.points[a], self.points[b], self.points[c]]
+    }
+
+    pub fn half_edge_count(&self) -> usize {
+        3 * self.triangles.len()
+    }
+
+    pub fn face(&self, e: usize) -> usize {
+        e / 3
+    }
+
+    pub fn origin(&self, e: usize) -> usize {
+        self.triangles[e / 3][e % 3]
+    }
+
+    pub fn destination(&self, e: usize) -> usize {
+        self.triangles[e / 3][(e + 1) % 3]
+    }
+
+    pub fn next(&self, e: usize) -> usize {
+        e - e % 3 + (e + 1) % 3
+    }
+
+    pub fn prev(&self, e: usize) -> usize {
+        e - e % 3 + (e + 2) % 3
+    }
+
+    // the opposite half-edge in the neighbouring triangle, None on the boundary
+    pub fn twin(&self, e: usize) -> Option<usize> {
+        let neighbour = self.neighbours[e / 3][(e + 2) % 3]?;
+        let (origin, destination) = (self.origin(e), self.destination(e));
+        (3 * neighbour..3 * neighbour + 3)
+            .find(|f| self.origin(*f) == destination && self.destination(*f) == origin)
+    }
+
+    // every undirected edge once, as the half-edge whose twin is absent or larger
+    pub fn edges(&self) -> Vec<usize> {
+        (0..self.half_edge_count())
+            .filter(|e| self.twin(*e).map_or(true, |twin| *e < twin))
+            .collect()
+    }
+
+    // the half-edges leaving vertex v, fan by fan in counter clockwise order
+    pub fn vertex_edges(&self, v: usize) -> Vec<usize> {
+        (self.fan_offsets[v]..self.fan_offsets[v + 1])
+            .flat_map(|fan| self.fan(self.fans[fan]))
+            .collect()
+    }
+
+    // the vertices connected to v, fan by fan in counter clockwise order
+    pub fn vertex_neighbours(&self, v: usize) -> Vec<usize> {
+        let mut vertices = Vec::new();
+        for fan in self.fans[self.fan_offsets[v]..self.fan_offsets[v + 1]].iter() {
+            let edges = self.fan(*fan);
+            let mut fan_vertices: Vec<usize> = edges.iter().map(|e| self.destination(*e)).collect();
+            // on the boundary the last triangle adds one more vertex
+            if let Some(last) = edges.last() {
+                if self.twin(self.prev(*last)).is_none() {
+                    fan_vertices.push(self.origin(self.prev(*last)));
+                }
+            }
+            // fans touching each other in another vertex share it
+            for u in fan_vertices {
+                if !vertices.contains(&u) {
+                    vertices.push(u);
+                }
+            }
+        }
+        vertices
+    }
+
+    // the half-edges from start counter clockwise around its origin
+    fn fan(&self, start: usize) -> Vec<usize> {
+        let mut edges = Vec::new();
+        let mut e = start;
+        loop {
+            edges.push(e);
+            match self.twin(self.prev(e)) {
+                Some(next) if next != start => e = next,
+                _ => return edges,
+            }
+        }
+    }
+
+    // q lies inside or on the boundary of triangle t
+    pub fn contains(&self, t: usize, q: Point2) -> bool {
+        (0..3).all(|i| 0.0 <= self.side(t, i, q))
+    }
+
+    // Visibility walk from the hint triangle to the one containing q. Every step crosses
+    // an edge q lies behind, the edge tried first changes every step so the walk does not
+    // circle. None if the walk leaves the mesh, which only proves q outside of a convex
+    // mesh, or if it takes more steps than there are triangles, which can only happen in
+    // meshes that are not Delaunay.
+    pub fn walk(&self, hint: usize, q: Point2) -> Option<usize> {
+        if self.triangles.len() <= hint {
+            return None;
+        }
+        let mut t = hint;
+        'walk: for step in 0..=self.triangles.len() {
+            for k in 0..3 {
+                let i = (k + step) % 3;
+                if self.side(t, i, q) < 0.0 {
+                    t = self.neighbours[t][i]?;
+                    continue 'walk;
+                }
+            }
+            return Some(t);
+        }
+        None
+    }
+
+    // positive if q lies on the inner side of the edge opposite of vertex i of t
+    fn side(&self, t: usize, i: usize, q: Point2) -> f64 {
+        let triangle = self.triangles[t];
+        let (a, b) = (
+            self.points[triangle[(i + 1) % 3]],
+            self.points[triangle[(i + 2) % 3]],
+        );
+        let (ax, ay) = (f64::from(a.x), f64::from(a.y));
+        (f64::from(b.x) - ax) * (f64::from(q.y) - ay)
+            - (f64::from(b.y) - ay) * (f64::from(q.x) - ax)
+    }
+
+    // None for a mesh without triangles
+    pub fn quality(&self) -> Option<Quality> {
+        if self.is_empty() {
+            return None;
+        }
+        let mut quality = Quality {
+            min_angle: 180.0,
+            max_angle: 0.0,
+            max_aspect_ratio: 0.0,
+            mean_aspect_ratio: 0.0,
+            min_area: std::f64::INFINITY,
+            max_area: 0.0,
+        };
+        for t in 0..self.triangles.len() {
+            let [a, b, c] = self.triangle(t);
+            let (a, b, c) = (
+                (f64::from(a.x), f64::from(a.y)),
+                (f64::from(b.x), f64::from(b.y)),
+                (f64::from(c.x), f64::from(c.y)),
+            );
+            let lengths = [
+                (b.0 - c.0).hypot(b.1 - c.1),
+                (c.0 - a.0).hypot(c.1 - a.1),
+                (a.0 - b.0).hypot(a.1 - b.1),
+            ];
+            let area = ((b.0 - a.0) * (c.1 - a.1) - (b.1 - a.1) * (c.0 - a.0)) / 2.0;
+            for i in 0..3 {
+                // law of cosines for the angle opposite of edge i
+                let (x, y, z) = (lengths[i], lengths[(i + 1) % 3], lengths[(i + 2) % 3]);
+                let cos = ((y * y + z * z - x * x) / (2.0 * y * z)).max(-1.0).min(1.0);
+                let angle = cos.acos().to_degrees();
+                quality.min_angle = quality.min_angle.min(angle);
+                quality.max_angle = quality.max_angle.max(angle);
+            }
+            let longest = lengths[0].max(lengths[1]).max(lengths[2]);
+            let aspect_ratio = longest * longest * 3f64.sqrt() / (4.0 * area);
+            quality.max_aspect_ratio = quality.max_aspect_ratio.max(aspect_ratio);
+            quality.mean_aspect_ratio += aspect_ratio / self.triangles.len() as f64;
+            quality.min_area = quality.min_area.min(area);
+            quality.max_area = quality.max_area.max(area);
+        }
+        Some(quality)
+    }
+}
+
+// angles in degrees, the aspect ratio is the longest edge over the shortest altitude
+// scaled to 1 for the equilateral triangle
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Quality {
+    pub min_angle: f64,
+    pub max_angle: f64,
+    pub max_aspect_ratio: f64,
+    pub mean_aspect_ratio: f64,
+    pub min_area: f64,
+    pub max_area: f64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a square split along its diagonal plus a triangle on top
+    fn mesh() -> Mesh {
+        let points = vec![
+            Point2::new(0.0, 0.0),
+            Point2::new(1.0, 0.0),
+            Point2::new(1.0, 1.0),
+            Point2::new(0.0, 1.0),
+            Point2::new(0.5, 2.0),
+            Point2::new(5.0, 5.0),
+        ];
+        Mesh::new(points, vec![[0, 1, 2], [0, 2, 3], [3, 2, 4]])
+    }
+
+    #[test]
+    fn half_edges_are_consistent() {
+        let mesh = mesh();
+        assert_eq!(mesh.neighbours()[0], [None, Some(1), None]);
+        assert_eq!(mesh.neighbours()[1], [Some(2), None, Some(0)]);
+        for e in 0..mesh.half_edge_count() {
+            assert_eq!(mesh.next(mesh.prev(e)), e);
+            assert_eq!(mesh.destination(e), mesh.origin(mesh.next(e)));
+            if let Some(twin) = mesh.twin(e) {
+                assert_eq!(mesh.twin(twin), Some(e));
+                assert_eq!(mesh.origin(twin), mesh.destination(e));
+            }
+        }
+        // 2 inner and 5 boundary edges
+        assert_eq!(mesh.edges().len(), 7);
+        assert_eq!(mesh.vertex_neighbours(0), vec![1, 2, 3]);
+        assert_eq!(mesh.vertex_neighbours(2), vec![4, 3, 0, 1]);
+        assert_eq!(mesh.vertex_neighbours(4), vec![3, 2]);
+        assert!(mesh.vertex_neighbours(5).is_empty());
+        assert_eq!(Mesh::default().quality(), None);
+    }
+
+    #[test]
+    fn pinched_vertices_have_every_fan() {
+        // two squares touching in vertex 2, each split along its diagonal
+        let points = vec![
+            Point2::new(0.0, 0.0),
+            Point2::new(1.0, 0.0),
+            Point2::new(1.0, 1.0),
+            Point2::new(0.0, 1.0),
+            Point2::new(2.0, 1.0),
+            Point2::new(2.0, 2.0),
+            Point2::new(1.0, 2.0),
+        ];
+        let mesh = Mesh::new(points, vec![[0, 1, 2], [0, 2, 3], [2, 4, 5], [2, 5, 6]]);
+        let mut edges = mesh.vertex_edges(2);
+        edges.sort();
+        let mut expected: Vec<usize> = (0..mesh.half_edge_count())
+            .filter(|e| mesh.origin(*e) == 2)
+            .collect();
+        expected.sort();
+        assert_eq!(edges, expected);
+        let mut neighbours = mesh.vertex_neighbours(2);
+        neighbours.sort();
+        assert_eq!(neighbours, vec![0, 1, 3, 4, 5, 6]);
+        assert_eq!(mesh.vertex_neighbours(0), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn walks_find_the_triangle() {
+        let mesh = mesh();
+        let queries = [(0.9, 0.1, 0), (0.1, 0.9, 1), (0.5, 1.5, 2), (0.5, 0.5, 0)];
+        for hint in 0..mesh.len() {
+            for (x, y, t) in &queries {
+                let q = Point2::new(*x, *y);
+                let found = mesh.walk(hint, q).unwrap();
+                assert!(mesh.contains(found, q));
+                // on the diagonal both triangles contain q
+                if *x != *y {
+                    assert_eq!(found, *t);
+                }
+            }
+            assert_eq!(mesh.walk(hint, Point2::new(2.0, 0.5)), None);
+        }
+    }
+}
@@ -0,0 +1,788 @@
+use crate::cdt::Cdt;
+use crate::math::orient;
+use crate::mesh::Mesh;
+use ggez::graphics::Point2;
+
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashSet};
+
+// Simple polygon with holes. The vertices are numbered through the outer boundary and then
+// through every hole, triangles refer to these indices. The outer boundary is stored
+// counter clockwise and the holes clockwise in a y-up coordinate system, so the inside is
+// always left of the edges. Holes have to lie inside the outer boundary without touching it
+// or each other.
+#[derive(Clone, Debug)]
+pub struct Polygon {
+    vertices: Vec<Point2>,
+    rings: Vec<Vec<usize>>,
+}
+
+impl Polygon {
+    pub fn new(outer: &[Point2]) -> Self {
+        Self::with_holes(outer, &[])
+    }
+
+    pub fn with_holes(outer: &[Point2], holes: &[Vec<Point2>]) -> Self {
+        let mut vertices = outer.to_vec();
+        let mut rings = vec![(0..outer.len()).collect::<Vec<usize>>()];
+        for hole in holes {
+            rings.push((vertices.len()..vertices.len() + hole.len()).collect());
+            vertices.extend_from_slice(hole);
+        }
+        for (r, ring) in rings.iter_mut().enumerate() {
+            let area = signed_area(&vertices, ring);
+            if (r == 0 && area < 0.0) || (r != 0 && 0.0 < area) {
+                ring.reverse();
+            }
+        }
+        Polygon { vertices, rings }
+    }
+
+    pub fn vertices(&self) -> &[Point2] {
+        &self.vertices
+    }
+
+    pub fn area(&self) -> f64 {
+        self.rings
+            .iter()
+            .map(|ring| signed_area(&self.vertices, ring))
+            .sum()
+    }
+
+    // no two edges intersect except neighbours in their common vertex
+    pub fn is_simple(&self) -> bool {
+        let edges: Vec<(usize, usize)> = self
+            .rings
+            .iter()
+            .flat_map(|ring| (0..ring.len()).map(move |i| (ring[i], ring[(i + 1) % ring.len()])))
+            .collect();
+        if self.rings.iter().any(|ring| ring.len() < 3) {
+            return false;
+        }
+        for i in 0..edges.len() {
+            for j in i + 1..edges.len() {
+                let (a, b) = (edges[i], edges[j]);
+                let shared = [a.0, a.1]
+                    .iter()
+                    .filter(|v| **v == b.0 || **v == b.1)
+                    .count();
+                let (p, q) = (self.position(a.0), self.position(a.1));
+                let (r, s) = (self.position(b.0), self.position(b.1));
+                let touching = match shared {
+                    // neighbours may only overlap if they fold back onto each other
+                    1 => {
+                        let other = if a.0 == b.0 || a.1 == b.0 { s } else { r };
+                        let common = if a.0 == b.0 || a.0 == b.1 { p } else { q };
+                        let own = if common == p { q } else { p };
+                        orient(common, own, other) == 0.0
+                            && 0.0
+                                < (own.0 - common.0) * (other.0 - common.0)
+                                    + (own.1 - common.1) * (other.1 - common.1)
+                    }
+                    0 => segments_touch(p, q, r, s),
+                    _ => true,
+                };
+                // a triangle shares every edge with both others
+                if touching && !(shared == 2 && edges.len() == 3) {
+                    return false;
+                }
+            }
+        }
+        true
+    }
+
+    // Delaunay refinement of the polygon, see Cdt::refine
+    pub fn quality_mesh(&self, min_angle: f64, max_area: Option<f64>, limit: usize) -> Mesh {
+        let constraints: Vec<(usize, usize)> = self
+            .rings
+            .iter()
+            .flat_map(|ring| (0..ring.len()).map(move |i| (ring[i], ring[(i + 1) % ring.len()])))
+            .collect();
+        // a point inside every hole, the center of one of its triangles
+        let holes: Vec<Point2> = self.rings[1..]
+            .iter()
+            .filter_map(|ring| {
+                let hole =
+                    Polygon::new(&ring.iter().map(|v| self.vertices[*v]).collect::<Vec<_>>());
+                let [a, b, c] = hole.ear_clipping().into_iter().next()?;
+                let (a, b, c) = (hole.vertices[a], hole.vertices[b], hole.vertices[c]);
+                Some(Point2::new(
+                    (a.x + b.x + c.x) / 3.0,
+                    (a.y + b.y + c.y) / 3.0,
+                ))
+            })
+            .collect();
+        let mut cdt = Cdt::with_constraints(&self.vertices, &constraints);
+        cdt.refine(&holes, true, min_angle, max_area, limit);
+        cdt.mesh_with_holes(&holes, true)
+    }
+
+    // ear clipping on a single boundary, holes are connected to the outer boundary by a
+    // bridge to a vertex they can see. O(n^2), the reference for triangulate.
+    pub fn ear_clipping(&self) -> Vec<[usize; 3]> {
+        let mut ring = self.bridged();
+        let mut triangles = Vec::with_capacity(ring.len());
+        let (mut i, mut failed) = (0, 0);
+        while 3 < ring.len() && failed < ring.len() {
+            let n = ring.len();
+            i %= n;
+            let (a, b, c) = (ring[(i + n - 1) % n], ring[i], ring[(i + 1) % n]);
+            let (pa, pb, pc) = (self.position(a), self.position(b), self.position(c));
+            let turn = orient(pa, pb, pc);
+            if 0.0 < turn && self.is_ear(&ring, (pa, pb, pc)) {
+                triangles.push([a, b, c]);
+                ring.remove(i);
+                failed = 0;
+            } else {
+                i += 1;
+                failed += 1;
+            }
+        }
+        if ring.len() == 3 {
+            let [a, b, c] = [ring[0], ring[1], ring[2]];
+            if 0.0 < orient(self.position(a), self.position(b), self.position(c)) {
+                triangles.push([a, b, c]);
+            }
+        }
+        triangles
+    }
+
+    // no vertex of the ring lies in the triangle, besides the corners and their copies
+    fn is_ear(&self, ring: &[usize], (a, b, c): ((f64, f64), (f64, f64), (f64, f64))) -> bool {
+        ring.iter().map(|v| self.position(*v)).all(|p| {
+            p == a
+                || p == b
+                || p == c
+                || orient(a, b, p) < 0.0
+                || orient(b, c, p) < 0.0
+                || orient(c, a, p) < 0.0
+        })
+    }
+
+    // the outer boundary with every hole spliced in after a vertex visible from the
+    // rightmost vertex of the hole
+    fn bridged(&self) -> Vec<usize> {
+        let mut ring = self.rings[0].clone();
+        let mut holes: Vec<(usize, &Vec<usize>)> = self.rings[1..]
+            .iter()
+            .map(|hole| {
+                let right = (0..hole.len())
+                    .max_by(|a, b| {
+                        let (a, b) = (self.position(hole[*a]), self.position(hole[*b]));
+                        a.partial_cmp(&b).unwrap()
+                    })
+                    .unwrap();
+                (right, hole)
+            })
+            .collect();
+        holes.sort_by(|a, b| {
+            let (a, b) = (self.position(a.1[a.0]), self.position(b.1[b.0]));
+            b.partial_cmp(&a).unwrap()
+        });
+
+        for (right, hole) in holes {
+            let m = self.position(hole[right]);
+            // the closest edge hit by the ray from m to the right
+            let mut hit: Option<(f64, usize)> = None;
+            for k in 0..ring.len() {
+                let (a, b) = (
+                    self.position(ring[k]),
+                    self.position(ring[(k + 1) % ring.len()]),
+                );
+                if a.1 == b.1 || m.1 < a.1.min(b.1) || a.1.max(b.1) < m.1 {
+                    continue;
+                }
+                let x = a.0 + (m.1 - a.1) * (b.0 - a.0) / (b.1 - a.1);
+                if m.0 <= x && hit.map_or(true, |(best, _)| x < best) {
+                    hit = Some((x, k));
+                }
+            }
+            let (x, k) = match hit {
+                Some(hit) => hit,
+                None => continue,
+            };
+            let n = ring.len();
+            // a vertex hit by the ray is visible, otherwise the right end of the edge may be
+            let (a, b) = (self.position(ring[k]), self.position(ring[(k + 1) % n]));
+            let mut bridge = if a.1 == m.1 || (b.1 != m.1 && b.0 < a.0) {
+                k
+            } else {
+                (k + 1) % n
+            };
+            // reflex vertices in the triangle between m, the hit and the candidate may
+            // block the view, the one closest in angle to the ray is visible
+            let (i, p) = ((x, m.1), self.position(ring[bridge]));
+            let mut best = (std::f64::INFINITY, std::f64::INFINITY);
+            for j in 0..n {
+                let r = self.position(ring[j]);
+                let (before, after) = (
+                    self.position(ring[(j + n - 1) % n]),
+                    self.position(ring[(j + 1) % n]),
+                );
+                let inside = if orient(m, i, p) < 0.0 {
+                    orient(m, p, r) >= 0.0 && orient(p, i, r) >= 0.0 && orient(i, m, r) >= 0.0
+                } else {
+                    orient(m, i, r) >= 0.0 && orient(i, p, r) >= 0.0 && orient(p, m, r) >= 0.0
+                };
+                if r != p
+                    && orient(before, r, after) < 0.0
+                    && inside
+                    && m.0 < r.0
+                    && r.0 <= x.max(p.0)
+                {
+                    let angle = (((r.1 - m.1) / (r.0 - m.0)).abs(), r.0 - m.0);
+                    if angle < best {
+                        best = angle;
+                        bridge = j;
+                    }
+                }
+            }
+            // a vertex used by earlier bridges occurs several times, the bridge has to leave
+            // from the occurrence whose inside contains m
+            let v = ring[bridge];
+            if let Some(j) = (0..n).find(|j| {
+                ring[*j] == v
+                    && in_wedge(
+                        self.position(ring[(j + n - 1) % n]),
+                        self.position(v),
+                        self.position(ring[(j + 1) % n]),
+                        m,
+                    )
+            }) {
+                bridge = j;
+            }
+            let hole_ring = (0..=hole.len()).map(|h| hole[(right + h) % hole.len()]);
+            let splice: Vec<usize> = hole_ring.chain(Some(ring[bridge])).collect();
+            ring.splice(bridge + 1..bridge + 1, splice);
+        }
+        ring
+    }
+
+    // diagonals that split the polygon into y-monotone pieces, sweeping from the top
+    pub fn monotone_diagonals(&self) -> Vec<(usize, usize)> {
+        let (next, prev) = self.links();
+        let mut order: Vec<usize> = self.rings.iter().flatten().cloned().collect();
+        order.sort_by(|a, b| {
+            if self.above(*a, *b) {
+                Ordering::Less
+            } else if self.above(*b, *a) {
+                Ordering::Greater
+            } else {
+                Ordering::Equal
+            }
+        });
+
+        // edges with the inside on their right ordered from left to right, each with the
+        // lowest vertex above the sweep line that sees it
+        let mut status: BTreeMap<SegmentKey, usize> = BTreeMap::new();
+        let mut diagonals = Vec::new();
+        let is_merge = |v: usize| self.vertex_kind(v, &next, &prev) == VertexKind::Merge;
+
+        for v in order {
+            let key = |e: usize| self.segment_key(e, next[e]);
+            let probe = SegmentKey {
+                top: self.position(v),
+                bottom: self.position(v),
+            };
+            match self.vertex_kind(v, &next, &prev) {
+                VertexKind::Start => {
+                    status.insert(key(v), v);
+                }
+                VertexKind::End => {
+                    if let Some(helper) = status.remove(&key(prev[v])) {
+                        if is_merge(helper) {
+                            diagonals.push((v, helper));
+                        }
+                    }
+                }
+                VertexKind::Split => {
+                    if let Some((_, helper)) = status.range_mut(..probe).next_back() {
+                        diagonals.push((v, *helper));
+                        *helper = v;
+                    }
+                    status.insert(key(v), v);
+                }
+                VertexKind::Merge => {
+                    if let Some(helper) = status.remove(&key(prev[v])) {
+                        if is_merge(helper) {
+                            diagonals.push((v, helper));
+                        }
+                    }
+                    if let Some((_, helper)) = status.range_mut(..probe).next_back() {
+                        if is_merge(*helper) {
+                            diagonals.push((v, *helper));
+                        }
+                        *helper = v;
+                    }
+                }
+                VertexKind::Regular => {
+                    // the inside is right of v if the boundary goes down through it
+                    if self.above(prev[v], v) {
+                        if let Some(helper) = status.remove(&key(prev[v])) {
+                            if is_merge(helper) {
+                                diagonals.push((v, helper));
+                            }
+                        }
+                        status.insert(key(v), v);
+                    } else if let Some((_, helper)) = status.range_mut(..probe).next_back() {
+                        if is_merge(*helper) {
+                            diagonals.push((v, *helper));
+                        }
+                        *helper = v;
+                    }
+                }
+            }
+        }
+        diagonals
+    }
+
+    // the faces of the polygon cut along the monotone diagonals, counter clockwise
+    pub fn monotone_pieces(&self) -> Vec<Vec<usize>> {
+        let diagonals = self.monotone_diagonals();
+        let (next, _) = self.links();
+        let mut adjacent: Vec<Vec<usize>> = vec![Vec::new(); self.vertices.len()];
+        let mut half_edges = Vec::new();
+        for ring in &self.rings {
+            for v in ring {
+                adjacent[*v].push(next[*v]);
+                adjacent[next[*v]].push(*v);
+                half_edges.push((*v, next[*v]));
+            }
+        }
+        for (a, b) in &diagonals {
+            adjacent[*a].push(*b);
+            adjacent[*b].push(*a);
+            half_edges.push((*a, *b));
+            half_edges.push((*b, *a));
+        }
+        for (v, neighbours) in adjacent.iter_mut().enumerate() {
+            let center = self.position(v);
+            let angle = |n: &usize| {
+                let p = self.position(*n);
+                (p.1 - center.1).atan2(p.0 - center.0)
+            };
+            neighbours.sort_by(|a, b| angle(a).partial_cmp(&angle(b)).unwrap());
+        }
+
+        // the inside is left of every half-edge, at each vertex the face turns to the
+        // neighbour following the incoming edge clockwise
+        let mut visited = HashSet::new();
+        let mut pieces = Vec::new();
+        for start in half_edges {
+            if visited.contains(&start) {
+                continue;
+            }
+            let mut piece = Vec::new();
+            let mut edge = start;
+            loop {
+                visited.insert(edge);
+                piece.push(edge.0);
+                let (from, at) = edge;
+                let around = &adjacent[at];
+                let k = around.iter().position(|n| *n == from).unwrap();
+                edge = (at, around[(k + around.len() - 1) % around.len()]);
+                if edge == start || piece.len() > self.vertices.len() {
+                    break;
+                }
+            }
+            pieces.push(piece);
+        }
+        pieces
+    }
+
+    // monotone decomposition in O(n log n) and a linear scan of every piece
+    pub fn triangulate(&self) -> Vec<[usize; 3]> {
+        let mut triangles = Vec::with_capacity(self.vertices.len());
+        for piece in self.monotone_pieces() {
+            self.triangulate_monotone(&piece, &mut triangles);
+        }
+        triangles
+    }
+
+    fn triangulate_monotone(&self, piece: &[usize], triangles: &mut Vec<[usize; 3]>) {
+        if piece.len() < 3 {
+            return;
+        }
+        // going counter clockwise from the top vertex leads down the left chain
+        let n = piece.len();
+        let top = (1..n).fold(0, |top, i| {
+            if self.above(piece[i], piece[top]) {
+                i
+            } else {
+                top
+            }
+        });
+        let bottom = (1..n).fold(0, |bottom, i| {
+            if self.above(piece[bottom], piece[i]) {
+                i
+            } else {
+                bottom
+            }
+        });
+        let mut left = vec![false; n];
+        let mut i = top;
+        while i != bottom {
+            left[i] = true;
+            i = (i + 1) % n;
+        }
+        // both chains are already ordered from the top, merging them sorts the piece
+        let mut order = Vec::with_capacity(n);
+        order.push(top);
+        let (mut l, mut r) = ((top + 1) % n, (top + n - 1) % n);
+        while l != bottom || r != bottom {
+            if r == bottom || (l != bottom && self.above(piece[l], piece[r])) {
+                order.push(l);
+                l = (l + 1) % n;
+            } else {
+                order.push(r);
+                r = (r + n - 1) % n;
+            }
+        }
+        order.push(bottom);
+
+        let mut push = |a: usize, b: usize, c: usize| {
+            let (a, b, c) = (piece[a], piece[b], piece[c]);
+            if orient(self.position(a), self.position(b), self.position(c)) < 0.0 {
+                triangles.push([a, c, b]);
+            } else {
+                triangles.push([a, b, c]);
+            }
+        };
+        let mut stack = vec![order[0], order[1]];
+        for j in 2..n - 1 {
+            let v = order[j];
+            if left[v] != left[*stack.last().unwrap()] {
+                // v sees every vertex on the stack
+                while 1 < stack.len() {
+                    let a = stack.pop().unwrap();
+                    push(v, a, *stack.last().unwrap());
+                }
+                stack.clear();
+                stack.push(order[j - 1]);
+                stack.push(v);
+            } else {
+                let mut last = stack.pop().unwrap();
+                while let Some(&top) = stack.last() {
+                    let turn = orient(
+                        self.position(piece[top]),
+                        self.position(piece[last]),
+                        self.position(piece[v]),
+                    );
+                    if (left[v] && turn <= 0.0) || (!left[v] && 0.0 <= turn) {
+                        break;
+                    }
+                    push(v, last, top);
+                    last = stack.pop().unwrap();
+                }
+                stack.push(last);
+                stack.push(v);
+            }
+        }
+        let v = order[n - 1];
+        while 1 < stack.len() {
+            let a = stack.pop().unwrap();
+            push(v, a, *stack.last().unwrap());
+        }
+    }
+
+    // the following and preceding vertex of every vertex on its ring
+    fn links(&self) -> (Vec<usize>, Vec<usize>) {
+        let mut next = vec![0; self.vertices.len()];
+        let mut prev = vec![0; self.vertices.len()];
+        for ring in &self.rings {
+            for i in 0..ring.len() {
+                next[ring[i]] = ring[(i + 1) % ring.len()];
+                prev[ring[(i + 1) % ring.len()]] = ring[i];
+            }
+        }
+        (next, prev)
+    }
+
+    fn vertex_kind(&self, v: usize, next: &[usize], prev: &[usize]) -> VertexKind {
+        let convex = 0.0
+            < orient(
+                self.position(prev[v]),
+                self.position(v),
+                self.position(next[v]),
+            );
+        match (self.above(v, prev[v]), self.above(v, next[v]), convex) {
+            (true, true, true) => VertexKind::Start,
+            (true, true, false) => VertexKind::Split,
+            (false, false, true) => VertexKind::End,
+            (false, false, false) => VertexKind::Merge,
+            _ => VertexKind::Regular,
+        }
+    }
+
+    // sweep order, higher y first and smaller x first on the same height
+    fn above(&self, a: usize, b: usize) -> bool {
+        let (a, b) = (self.position(a), self.position(b));
+        b.1 < a.1 || (a.1 == b.1 && a.0 < b.0)
+    }
+
+    fn segment_key(&self, a: usize, b: usize) -> SegmentKey {
+        let (top, bottom) = if self.above(a, b) { (a, b) } else { (b, a) };
+        SegmentKey {
+            top: self.position(top),
+            bottom: self.position(bottom),
+        }
+    }
+
+    fn position(&self, v: usize) -> (f64, f64) {
+        (f64::from(self.vertices[v].x), f64::from(self.vertices[v].y))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum VertexKind {
+    Start,
+    End,
+    Split,
+    Merge,
+    Regular,
+}
+
+// edge on the sweep line, a vertex is looked up as an edge of length zero. Edges on the
+// sweep line do not cross, so the one starting lower is compared with the other one at
+// its top, on the same side for the whole time both are on the sweep line.
+#[derive(Clone, Copy, Debug)]
+struct SegmentKey {
+    top: (f64, f64),
+    bottom: (f64, f64),
+}
+
+impl SegmentKey {
+    // whether other lies right of self, the edge running downwards has right on its left
+    fn right_of(&self, other: &SegmentKey) -> bool {
+        let side = orient(self.top, self.bottom, other.top);
+        if side != 0.0 {
+            0.0 < side
+        } else {
+            0.0 < orient(self.top, self.bottom, other.bottom)
+        }
+    }
+
+    fn starts_lower(&self, other: &SegmentKey) -> bool {
+        self.top.1 < other.top.1 || (self.top.1 == other.top.1 && other.top.0 < self.top.0)
+    }
+}
+
+impl PartialEq for SegmentKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.top == other.top && self.bottom == other.bottom
+    }
+}
+
+impl Eq for SegmentKey {}
+
+impl PartialOrd for SegmentKey {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for SegmentKey {
+    fn cmp(&self, other: &Self) -> Ordering {
+        if self == other {
+            Ordering::Equal
+        } else if self.starts_lower(other) {
+            if other.right_of(self) {
+                Ordering::Greater
+            } else {
+                Ordering::Less
+            }
+        } else if self.right_of(other) {
+            Ordering::Less
+        } else {
+            Ordering::Greater
+        }
+    }
+}
+
+fn signed_area(vertices: &[Point2], ring: &[usize]) -> f64 {
+    (0..ring.len())
+        .map(|i| {
+            let (a, b) = (vertices[ring[i]], vertices[ring[(i + 1) % ring.len()]]);
+            f64::from(a.x) * f64::from(b.y) - f64::from(b.x) * f64::from(a.y)
+        })
+        .sum::<f64>()
+        / 2.0
+}
+
+// whether c lies in the inside left of the edges through a, v and b
+fn in_wedge(a: (f64, f64), v: (f64, f64), b: (f64, f64), c: (f64, f64)) -> bool {
+    let angle = |p: (f64, f64)| {
+        let turn = (p.1 - v.1).atan2(p.0 - v.0) - (b.1 - v.1).atan2(b.0 - v.0);
+        if turn < 0.0 {
+            turn + 2.0 * std::f64::consts::PI
+        } else {
+            turn
+        }
+    };
+    angle(c) < angle(a)
+}
+
+// closed segments pq and rs have a point in common
+fn segments_touch(p: (f64, f64), q: (f64, f64), r: (f64, f64), s: (f64, f64)) -> bool {
+    let (d1, d2) = (orient(r, s, p), orient(r, s, q));
+    let (d3, d4) = (orient(p, q, r), orient(p, q, s));
+    let within = |a: (f64, f64), b: (f64, f64), c: (f64, f64)| {
+        a.0.min(b.0) <= c.0 && c.0 <= a.0.max(b.0) && a.1.min(b.1) <= c.1 && c.1 <= a.1.max(b.1)
+    };
+    (d1 * d2 < 0.0 && d3 * d4 < 0.0)
+        || (d1 == 0.0 && within(r, s, p))
+        || (d2 == 0.0 && within(r, s, q))
+        || (d3 == 0.0 && within(p, q, r))
+        || (d4 == 0.0 && within(p, q, s))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::random_coordinates;
+
+    // star shaped around center, so it is simple
+    fn star(n: usize, center: (f32, f32), radius: f32, seed: u64) -> Vec<Point2> {
+        random_coordinates(n, seed)
+            .into_iter()
+            .enumerate()
+            .map(|(i, (x, _))| {
+                let r = radius * (0.4 + 0.6 * x / 1000.0);
+                let angle = i as f32 / n as f32 * 2.0 * std::f32::consts::PI;
+                Point2::new(
+                    (center.0 + r * angle.cos()).round(),
+                    (center.1 + r * angle.sin()).round(),
+                )
+            })
+            .collect()
+    }
+
+    fn check(polygon: &Polygon, triangles: &[[usize; 3]]) {
+        let holes = polygon.rings.len() - 1;
+        assert_eq!(triangles.len(), polygon.vertices().len() + 2 * holes - 2);
+        let mut total = 0.0;
+        for t in triangles {
+            let (a, b, c) = (
+                polygon.position(t[0]),
+                polygon.position(t[1]),
+                polygon.position(t[2]),
+            );
+            assert!(0.0 < orient(a, b, c), "{:?}", t);
+            total += orient(a, b, c) / 2.0;
+        }
+        assert!((total - polygon.area()).abs() < 1e-6 * polygon.area());
+    }
+
+    // both chains between the top and the bottom go down
+    fn is_monotone(polygon: &Polygon, piece: &[usize]) -> bool {
+        let n = piece.len();
+        let descents = (0..n)
+            .filter(|i| polygon.above(piece[*i], piece[(i + 1) % n]))
+            .collect::<Vec<usize>>();
+        let turns = (0..n)
+            .filter(|i| descents.contains(i) != descents.contains(&((i + 1) % n)))
+            .count();
+        turns == 2
+    }
+
+    #[test]
+    fn star_polygons() {
+        for seed in 0..20 {
+            let outer = star(40 + seed as usize, (500.0, 500.0), 400.0, seed);
+            let polygon = Polygon::new(&outer);
+            assert!(polygon.is_simple());
+            check(&polygon, &polygon.ear_clipping());
+            check(&polygon, &polygon.triangulate());
+            for piece in polygon.monotone_pieces() {
+                assert!(is_monotone(&polygon, &piece), "{:?}", piece);
+            }
+        }
+    }
+
+    #[test]
+    fn quality_meshes() {
+        let outer = [
+            Point2::new(0.0, 0.0),
+            Point2::new(400.0, 0.0),
+            Point2::new(400.0, 150.0),
+            Point2::new(150.0, 150.0),
+            Point2::new(120.0, 400.0),
+            Point2::new(0.0, 400.0),
+        ];
+        let hole = vec![
+            Point2::new(40.0, 40.0),
+            Point2::new(90.0, 60.0),
+            Point2::new(60.0, 100.0),
+        ];
+        let polygon = Polygon::with_holes(&outer, &[hole]);
+        for (min_angle, max_area) in &[(20.0, None), (30.0, None), (25.0, Some(500.0))] {
+            let mesh = polygon.quality_mesh(*min_angle, *max_area, 100_000);
+            let quality = mesh.quality().unwrap();
+            assert!(*min_angle <= quality.min_angle + 1e-6, "{:?}", quality);
+            assert!(max_area.map_or(true, |max| quality.max_area <= max));
+            let total: f64 = (0..mesh.len())
+                .map(|t| {
+                    let [a, b, c] = mesh.triangle(t);
+                    let (a, b, c) = (
+                        (f64::from(a.x), f64::from(a.y)),
+                        (f64::from(b.x), f64::from(b.y)),
+                        (f64::from(c.x), f64::from(c.y)),
+                    );
+                    orient(a, b, c) / 2.0
+                })
+                .sum();
+            assert!((total - polygon.area()).abs() < 1e-3 * polygon.area());
+        }
+    }
+
+    #[test]
+    fn holes_and_horizontal_edges() {
+        // a comb with teeth pointing up and down, every edge horizontal or vertical
+        let mut outer = vec![Point2::new(0.0, 0.0)];
+        for i in 0..6 {
+            let x = i as f32 * 100.0;
+            outer.push(Point2::new(x + 50.0, 0.0));
+            outer.push(Point2::new(x + 50.0, -80.0));
+            outer.push(Point2::new(x + 100.0, -80.0));
+            outer.push(Point2::new(x + 100.0, 0.0));
+        }
+        outer.push(Point2::new(600.0, 300.0));
+        for i in (0..6).rev() {
+            let x = i as f32 * 100.0;
+            outer.push(Point2::new(x + 100.0, 300.0));
+            outer.push(Point2::new(x + 100.0, 400.0));
+            outer.push(Point2::new(x + 50.0, 400.0));
+            outer.push(Point2::new(x + 50.0, 300.0));
+        }
+        outer.push(Point2::new(0.0, 300.0));
+        outer.dedup();
+        let holes: Vec<Vec<Point2>> = (0..5)
+            .map(|i| {
+                let x = 30.0 + i as f32 * 110.0;
+                vec![
+                    Point2::new(x, 100.0),
+                    Point2::new(x + 60.0, 100.0),
+                    Point2::new(x + 60.0, 200.0),
+                    Point2::new(x + 30.0, 150.0),
+                    Point2::new(x, 200.0),
+                ]
+            })
+            .chain(Some(star(12, (300.0, 250.0), 30.0, 3)))
+            .collect();
+        let polygon = Polygon::with_holes(&outer, &holes);
+        assert!(polygon.is_simple());
+        check(&polygon, &polygon.ear_clipping());
+        check(&polygon, &polygon.triangulate());
+        for piece in polygon.monotone_pieces() {
+            assert!(is_monotone(&polygon, &piece), "{:?}", piece);
+        }
+
+        let crossed = Polygon::new(&[
+            Point2::new(0.0, 0.0),
+            Point2::new(10.0, 10.0),
+            Point2::new(10.0, 0.0),
+            Point2::new(0.0, 10.0),
+        ]);
+        assert!(!crossed.is_simple());
+    }
+}
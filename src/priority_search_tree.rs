@@ -0,0 +1,129 @@
+// Priority search tree for three-sided queries x in [x_min, x_max] and y >= y_min.
+// Every node holds the point with the largest y of its subtree (a heap on y) and the
+// remaining points are split at the median x (a search tree on x), so a query only
+// descends into subtrees whose top point is high enough, O(log n + k).
+#[derive(Clone, Debug)]
+pub struct PrioritySearchTree {
+    nodes: Vec<PstNode>,
+    root: Option<usize>,
+}
+
+#[derive(Clone, Debug)]
+struct PstNode {
+    point: (f32, f32),
+    // points of the left subtree have x <= split, the ones of the right subtree x >= split
+    split: f32,
+    left: Option<usize>,
+    right: Option<usize>,
+}
+
+impl PrioritySearchTree {
+    pub fn new(points: &[(f32, f32)]) -> Self {
+        let mut sorted_x = points.to_vec();
+        sorted_x.sort_by(|a, b| {
+            a.0.partial_cmp(&b.0)
+                .unwrap()
+                .then_with(|| a.1.partial_cmp(&b.1).unwrap())
+        });
+        sorted_x.dedup();
+
+        let mut tree = PrioritySearchTree {
+            nodes: Vec::with_capacity(sorted_x.len()),
+            root: None,
+        };
+        tree.root = tree.construct(sorted_x);
+        tree
+    }
+
+    fn construct(&mut self, mut sorted_x: Vec<(f32, f32)>) -> Option<usize> {
+        if sorted_x.is_empty() {
+            return None;
+        }
+        let top = (0..sorted_x.len())
+            .max_by(|a, b| sorted_x[*a].1.partial_cmp(&sorted_x[*b].1).unwrap())
+            .unwrap();
+        let point = sorted_x.remove(top);
+
+        let median = sorted_x.len() / 2;
+        let right_points = sorted_x.split_off(median);
+        let split = match (sorted_x.last(), right_points.first()) {
+            (Some(left), _) => left.0,
+            (None, Some(right)) => right.0,
+            (None, None) => point.0,
+        };
+        let left = self.construct(sorted_x);
+        let right = self.construct(right_points);
+        self.nodes.push(PstNode {
+            point,
+            split,
+            left,
+            right,
+        });
+        Some(self.nodes.len() - 1)
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    pub fn query(&self, x_min: f32, x_max: f32, y_min: f32) -> Vec<(f32, f32)> {
+        let mut v = Vec::new();
+        let mut stack: Vec<usize> = self.root.into_iter().collect();
+        while let Some(i) = stack.pop() {
+            let node = &self.nodes[i];
+            // everything below is lower than the top point
+            if node.point.1 < y_min {
+                continue;
+            }
+            if x_min <= node.point.0 && node.point.0 <= x_max {
+                v.push(node.point);
+            }
+            if let Some(left) = node.left {
+                if x_min <= node.split {
+                    stack.push(left);
+                }
+            }
+            if let Some(right) = node.right {
+                if node.split <= x_max {
+                    stack.push(right);
+                }
+            }
+        }
+        v
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::sorted;
+
+    #[test]
+    fn query_matches_brute_force() {
+        // many equal x and y values
+        let points: Vec<(f32, f32)> = (0..600)
+            .map(|i| (((i * 17) % 41) as f32, ((i * 29) % 37) as f32))
+            .collect();
+        let tree = PrioritySearchTree::new(&points);
+        let mut unique = sorted(points.clone());
+        unique.dedup();
+        assert_eq!(tree.len(), unique.len());
+
+        for i in 0..50 {
+            let x_min = (i % 13) as f32 * 3.0 - 2.0;
+            let x_max = x_min + (i % 7) as f32 * 4.0;
+            let y_min = (i % 11) as f32 * 3.5;
+            let expected: Vec<(f32, f32)> = unique
+                .iter()
+                .cloned()
+                .filter(|p| x_min <= p.0 && p.0 <= x_max && y_min <= p.1)
+                .collect();
+            assert_eq!(sorted(tree.query(x_min, x_max, y_min)), expected);
+        }
+        assert!(PrioritySearchTree::new(&[]).query(0.0, 1.0, 0.0).is_empty());
+    }
+}
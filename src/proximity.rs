@@ -0,0 +1,301 @@
+use crate::kd_tree::KdTree;
+use crate::mesh::Mesh;
+use crate::shape::Circle;
+use ggez::graphics::Point2;
+
+use std::collections::HashMap;
+
+// Proximity graphs as edges between point indices. The Euclidean minimum spanning tree,
+// the relative neighbourhood graph and the Gabriel graph are subgraphs of the Delaunay
+// triangulation in this order, so they are taken from the mesh of triangulation::delaunay.
+
+// Kruskal's algorithm on the Delaunay edges, O(n log n). Equal points are left out of
+// the triangulation, they are joined to the one in it by edges of length zero so the
+// tree spans all n points with n - 1 edges.
+pub fn euclidean_mst(mesh: &Mesh) -> Vec<(usize, usize)> {
+    let points = mesh.points();
+    let mut edges: Vec<(usize, usize)> = if mesh.is_empty() {
+        path(points)
+    } else {
+        mesh.edges()
+            .into_iter()
+            .map(|e| (mesh.origin(e), mesh.destination(e)))
+            .collect()
+    };
+    edges.extend(duplicate_edges(mesh));
+    edges.sort_by(|a, b| length(points, *a).partial_cmp(&length(points, *b)).unwrap());
+
+    let mut parent: Vec<usize> = (0..points.len()).collect();
+    fn root(parent: &mut Vec<usize>, mut v: usize) -> usize {
+        while parent[v] != v {
+            parent[v] = parent[parent[v]];
+            v = parent[v];
+        }
+        v
+    }
+    edges
+        .into_iter()
+        .filter(|(a, b)| {
+            let (a, b) = (root(&mut parent, *a), root(&mut parent, *b));
+            parent[a] = b;
+            a != b
+        })
+        .collect()
+}
+
+// Delaunay edges whose diametral circle holds no other point, of equal points only the
+// one in the triangulation is connected. Checking the two vertices
+// opposite of the edge is enough, any other point inside would violate the Delaunay
+// property of one of the triangles.
+pub fn gabriel_graph(mesh: &Mesh) -> Vec<(usize, usize)> {
+    let points = mesh.points();
+    if mesh.is_empty() {
+        return path(points);
+    }
+    mesh.edges()
+        .into_iter()
+        .filter(|e| {
+            let opposite = Some(*e).into_iter().chain(mesh.twin(*e));
+            let (a, b) = (mesh.origin(*e), mesh.destination(*e));
+            opposite
+                .map(|e| mesh.origin(mesh.prev(e)))
+                .all(|c| !in_diametral_circle(points, (a, b), c))
+        })
+        .map(|e| (mesh.origin(e), mesh.destination(e)))
+        .collect()
+}
+
+// Gabriel edges with an empty lune, no point closer to both ends than they are to each
+// other. The candidates come from a circle query around one end.
+pub fn relative_neighbourhood_graph(mesh: &Mesh) -> Vec<(usize, usize)> {
+    let points = mesh.points();
+    let (tree, indices) = tree(points);
+    gabriel_graph(mesh)
+        .into_iter()
+        .filter(|(a, b)| {
+            let d = length(points, (*a, *b));
+            let circle = Circle {
+                center: (points[*a].x, points[*a].y),
+                radius: d as f32,
+            };
+            tree.shape_query(&circle).into_iter().all(|p| {
+                let c = indices[&key(p)];
+                c == *a || c == *b || d <= length(points, (*a, c)) || d <= length(points, (*b, c))
+            })
+        })
+        .collect()
+}
+
+// every point to each of its k nearest neighbours, equal points count once
+pub fn k_nearest_graph(points: &[Point2], k: usize) -> Vec<(usize, usize)> {
+    let (tree, indices) = tree(points);
+    let mut edges = Vec::with_capacity(k * points.len());
+    for p in points {
+        let (neighbours, _) = tree.approx_k_nearest((p.x, p.y), k + 1, 0.0, std::usize::MAX);
+        let a = indices[&key((p.x, p.y))];
+        edges.extend(
+            neighbours
+                .into_iter()
+                .map(|q| indices[&key(q)])
+                .filter(|b| *b != a)
+                .take(k)
+                .map(|b| (a, b)),
+        );
+    }
+    edges
+}
+
+// Collinear points have no triangles, all three graphs are the path through them in
+// sorted order. Like in the triangulation equal points are on it once, with their first
+// index.
+fn path(points: &[Point2]) -> Vec<(usize, usize)> {
+    let mut order: Vec<usize> = (0..points.len()).collect();
+    order.sort_by(|a, b| {
+        let (a, b) = (points[*a], points[*b]);
+        (a.x, a.y).partial_cmp(&(b.x, b.y)).unwrap()
+    });
+    order.dedup_by(|a, b| points[*a] == points[*b]);
+    order.windows(2).map(|w| (w[0], w[1])).collect()
+}
+
+// an edge from the point standing in for its equal points in the graph to each of them
+fn duplicate_edges(mesh: &Mesh) -> Vec<(usize, usize)> {
+    let points = mesh.points();
+    let mut canonical = HashMap::with_capacity(points.len());
+    for (i, p) in points.iter().enumerate().rev() {
+        canonical.insert(key((p.x, p.y)), i);
+    }
+    for v in mesh.triangles().iter().flatten() {
+        canonical.insert(key((points[*v].x, points[*v].y)), *v);
+    }
+    (0..points.len())
+        .filter_map(|i| {
+            let c = canonical[&key((points[i].x, points[i].y))];
+            if c == i {
+                None
+            } else {
+                Some((c, i))
+            }
+        })
+        .collect()
+}
+
+// a kd-tree of the points and the first index of every position
+fn tree(points: &[Point2]) -> (KdTree, HashMap<(u32, u32), usize>) {
+    let mut indices = HashMap::with_capacity(points.len());
+    for (i, p) in points.iter().enumerate().rev() {
+        indices.insert(key((p.x, p.y)), i);
+    }
+    let coordinates: Vec<(f32, f32)> = points.iter().map(|p| (p.x, p.y)).collect();
+    (KdTree::new(&coordinates), indices)
+}
+
+fn key(p: (f32, f32)) -> (u32, u32) {
+    (p.0.to_bits(), p.1.to_bits())
+}
+
+fn length(points: &[Point2], (a, b): (usize, usize)) -> f64 {
+    let (a, b) = (points[a], points[b]);
+    (f64::from(b.x) - f64::from(a.x)).hypot(f64::from(b.y) - f64::from(a.y))
+}
+
+// strictly inside the circle with the segment from a to b as diameter
+fn in_diametral_circle(points: &[Point2], (a, b): (usize, usize), c: usize) -> bool {
+    let (a, b, c) = (points[a], points[b], points[c]);
+    let (ax, ay) = (
+        f64::from(a.x) - f64::from(c.x),
+        f64::from(a.y) - f64::from(c.y),
+    );
+    let (bx, by) = (
+        f64::from(b.x) - f64::from(c.x),
+        f64::from(b.y) - f64::from(c.y),
+    );
+    ax * bx + ay * by < 0.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{random_points, sorted};
+    use crate::triangulation;
+
+    // edges with the smaller index first, in order
+    fn undirected(edges: Vec<(usize, usize)>) -> Vec<(usize, usize)> {
+        sorted(
+            edges
+                .into_iter()
+                .map(|(a, b)| (a.min(b), a.max(b)))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn graphs_match_their_definitions() {
+        let points = random_points(80, 5);
+        let mesh = triangulation::delaunay(&points);
+        let n = points.len();
+        let distance = |a: usize, b: usize| length(&points, (a, b));
+        let pairs = || (0..n).flat_map(|a| (a + 1..n).map(move |b| (a, b)));
+
+        let gabriel: Vec<(usize, usize)> = pairs()
+            .filter(|(a, b)| (0..n).all(|c| !in_diametral_circle(&points, (*a, *b), c)))
+            .collect();
+        assert_eq!(undirected(gabriel_graph(&mesh)), gabriel);
+
+        let relative: Vec<(usize, usize)> = pairs()
+            .filter(|(a, b)| {
+                (0..n).all(|c| distance(*a, *b) <= distance(*a, c).max(distance(*b, c)))
+            })
+            .collect();
+        assert_eq!(undirected(relative_neighbourhood_graph(&mesh)), relative);
+
+        // Prim's algorithm on the complete graph
+        let mut tree = vec![false; n];
+        let mut best = vec![(std::f64::INFINITY, 0); n];
+        let mut weight = 0.0;
+        best[0].0 = 0.0;
+        for _ in 0..n {
+            let v = (0..n)
+                .filter(|v| !tree[*v])
+                .min_by(|a, b| best[*a].0.partial_cmp(&best[*b].0).unwrap())
+                .unwrap();
+            tree[v] = true;
+            weight += best[v].0;
+            for u in 0..n {
+                if !tree[u] && distance(u, v) < best[u].0 {
+                    best[u] = (distance(u, v), v);
+                }
+            }
+        }
+        let mst = euclidean_mst(&mesh);
+        assert_eq!(mst.len(), n - 1);
+        let total: f64 = mst.iter().map(|(a, b)| distance(*a, *b)).sum();
+        assert!((total - weight).abs() < 1e-6);
+        // the tree is part of the relative neighbourhood graph
+        assert!(undirected(mst).iter().all(|e| relative.contains(e)));
+
+        for k in &[1, 3, 6] {
+            let graph = k_nearest_graph(&points, *k);
+            assert_eq!(graph.len(), k * n);
+            for a in 0..n {
+                let mut others: Vec<usize> = (0..n).filter(|b| *b != a).collect();
+                others.sort_by(|x, y| distance(a, *x).partial_cmp(&distance(a, *y)).unwrap());
+                let kth = distance(a, others[k - 1]);
+                let neighbours: Vec<usize> = graph
+                    .iter()
+                    .filter(|(x, _)| *x == a)
+                    .map(|(_, b)| *b)
+                    .collect();
+                assert_eq!(neighbours.len(), *k);
+                assert!(neighbours.iter().all(|b| distance(a, *b) <= kth));
+            }
+        }
+    }
+
+    #[test]
+    fn duplicates_join_the_spanning_tree() {
+        let distinct = random_points(40, 8);
+        let collinear: Vec<Point2> = (0..6).map(|i| Point2::new(i as f32, 1.0)).collect();
+        for distinct in &[distinct, collinear] {
+            let mut points = distinct.clone();
+            points.extend_from_slice(&distinct[..5]);
+            points.push(distinct[0]);
+            let weight = |points: &[Point2], edges: &[(usize, usize)]| -> f64 {
+                edges.iter().map(|e| length(points, *e)).sum()
+            };
+            let expected = weight(distinct, &euclidean_mst(&triangulation::delaunay(distinct)));
+
+            let mst = euclidean_mst(&triangulation::delaunay(&points));
+            assert_eq!(mst.len(), points.len() - 1);
+            assert!((weight(&points, &mst) - expected).abs() < 1e-6);
+            let mut reached = vec![false; points.len()];
+            reached[0] = true;
+            for _ in 0..points.len() {
+                for (a, b) in &mst {
+                    if reached[*a] || reached[*b] {
+                        reached[*a] = true;
+                        reached[*b] = true;
+                    }
+                }
+            }
+            assert!(reached.iter().all(|r| *r));
+        }
+    }
+
+    #[test]
+    fn collinear_points() {
+        let points: Vec<Point2> = [3.0, 0.0, 7.0, 1.0, 5.0]
+            .iter()
+            .map(|x| Point2::new(*x, 2.0 * *x))
+            .collect();
+        let mesh = triangulation::delaunay(&points);
+        assert_eq!(
+            undirected(euclidean_mst(&mesh)),
+            vec![(0, 3), (0, 4), (1, 3), (2, 4)]
+        );
+        assert_eq!(
+            undirected(relative_neighbourhood_graph(&mesh)),
+            undirected(euclidean_mst(&mesh))
+        );
+    }
+}
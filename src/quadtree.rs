@@ -0,0 +1,446 @@
+use crate::kd_tree::Bounds;
+
+// Point-region quadtree: every inner node splits its square region at the center
+// into four equally sized quadrants, independent of where the points are.
+// Leaves hold up to `capacity` points, more only once `max_depth` is reached.
+#[derive(Clone, Debug)]
+pub struct QuadTree {
+    root: QuadNode,
+    // region covered by the root, grows when points outside of it are inserted
+    region: Bounds,
+    capacity: usize,
+    max_depth: usize,
+    len: usize,
+}
+
+#[derive(Clone, Debug)]
+enum QuadNode {
+    Leaf(Vec<(f32, f32)>),
+    // quadrants in the order (low x, low y), (high x, low y), (low x, high y), (high x, high y)
+    Inner(Box<[QuadNode; 4]>),
+}
+
+impl QuadTree {
+    pub fn new(region: Bounds, capacity: usize, max_depth: usize) -> Self {
+        assert!(0 < capacity, "quadtree leaves must be able to hold a point");
+        // PR quadtree cells are squares
+        let size = (region.max.0 - region.min.0).max(region.max.1 - region.min.1);
+        let size = if size > 0.0 { size } else { 1.0 };
+        QuadTree {
+            root: QuadNode::Leaf(Vec::new()),
+            region: Bounds {
+                min: region.min,
+                max: (region.min.0 + size, region.min.1 + size),
+            },
+            capacity,
+            max_depth,
+            len: 0,
+        }
+    }
+
+    pub fn with_points(
+        points: &[(f32, f32)],
+        region: Bounds,
+        capacity: usize,
+        max_depth: usize,
+    ) -> Self {
+        let mut tree = Self::new(region, capacity, max_depth);
+        for point in points {
+            tree.insert(*point);
+        }
+        tree
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn region(&self) -> Bounds {
+        self.region
+    }
+
+    // inserts point, returns false if the point was already part of the tree or is not
+    // finite, the region could never grow far enough to contain it
+    pub fn insert(&mut self, point: (f32, f32)) -> bool {
+        if !point.0.is_finite() || !point.1.is_finite() {
+            return false;
+        }
+        while !self.region.contains_point(point) {
+            self.grow_towards(point);
+        }
+        let inserted = Self::insert_node(
+            &mut self.root,
+            self.region,
+            point,
+            0,
+            self.capacity,
+            self.max_depth,
+        );
+        if inserted {
+            self.len += 1;
+        }
+        inserted
+    }
+
+    // doubles the region, the old root becomes the quadrant facing away from point
+    fn grow_towards(&mut self, point: (f32, f32)) {
+        let size = self.region.max.0 - self.region.min.0;
+        let grow_left = point.0 < self.region.min.0;
+        let grow_up = point.1 < self.region.min.1;
+        let min = (
+            if grow_left {
+                self.region.min.0 - size
+            } else {
+                self.region.min.0
+            },
+            if grow_up {
+                self.region.min.1 - size
+            } else {
+                self.region.min.1
+            },
+        );
+        let old_quadrant = (grow_left as usize) + 2 * (grow_up as usize);
+
+        let old_root = std::mem::replace(&mut self.root, QuadNode::Leaf(Vec::new()));
+        let mut children = [
+            QuadNode::Leaf(Vec::new()),
+            QuadNode::Leaf(Vec::new()),
+            QuadNode::Leaf(Vec::new()),
+            QuadNode::Leaf(Vec::new()),
+        ];
+        children[old_quadrant] = old_root;
+        self.root = QuadNode::Inner(Box::new(children));
+        self.region = Bounds {
+            min,
+            max: (min.0 + 2.0 * size, min.1 + 2.0 * size),
+        };
+    }
+
+    fn insert_node(
+        node: &mut QuadNode,
+        region: Bounds,
+        point: (f32, f32),
+        depth: usize,
+        capacity: usize,
+        max_depth: usize,
+    ) -> bool {
+        match node {
+            QuadNode::Inner(children) => {
+                let i = quadrant(&region, point);
+                Self::insert_node(
+                    &mut children[i],
+                    child_region(&region, i),
+                    point,
+                    depth + 1,
+                    capacity,
+                    max_depth,
+                )
+            }
+            QuadNode::Leaf(points) => {
+                if points.contains(&point) {
+                    return false;
+                }
+                points.push(point);
+                if points.len() <= capacity || max_depth <= depth {
+                    return true;
+                }
+
+                // split the leaf and redistribute its points
+                let points = std::mem::replace(points, Vec::new());
+                *node = QuadNode::Inner(Box::new([
+                    QuadNode::Leaf(Vec::new()),
+                    QuadNode::Leaf(Vec::new()),
+                    QuadNode::Leaf(Vec::new()),
+                    QuadNode::Leaf(Vec::new()),
+                ]));
+                for p in points {
+                    Self::insert_node(node, region, p, depth, capacity, max_depth);
+                }
+                true
+            }
+        }
+    }
+
+    // removes point, returns false if the point was not part of the tree
+    pub fn remove(&mut self, point: (f32, f32)) -> bool {
+        if !self.region.contains_point(point) {
+            return false;
+        }
+        let removed = Self::remove_node(&mut self.root, self.region, point, self.capacity);
+        if removed {
+            self.len -= 1;
+        }
+        removed
+    }
+
+    fn remove_node(
+        node: &mut QuadNode,
+        region: Bounds,
+        point: (f32, f32),
+        capacity: usize,
+    ) -> bool {
+        let merge = match node {
+            QuadNode::Leaf(points) => {
+                return match points.iter().position(|p| *p == point) {
+                    Some(i) => {
+                        points.swap_remove(i);
+                        true
+                    }
+                    None => false,
+                };
+            }
+            QuadNode::Inner(children) => {
+                let i = quadrant(&region, point);
+                if !Self::remove_node(&mut children[i], child_region(&region, i), point, capacity) {
+                    return false;
+                }
+                // collapse the quadrants again once they fit into a single leaf
+                let all_leaves = children.iter().all(|child| match child {
+                    QuadNode::Leaf(_) => true,
+                    QuadNode::Inner(_) => false,
+                });
+                all_leaves && children.iter().map(|child| child.len()).sum::<usize>() <= capacity
+            }
+        };
+        if merge {
+            let mut points = Vec::new();
+            node.collect(&mut points);
+            *node = QuadNode::Leaf(points);
+        }
+        true
+    }
+
+    pub fn points(&self) -> Vec<(f32, f32)> {
+        let mut points = Vec::with_capacity(self.len);
+        self.root.collect(&mut points);
+        points
+    }
+
+    pub fn range_query(&self, min: (f32, f32), max: (f32, f32)) -> Vec<(f32, f32)> {
+        let mut v = Vec::new();
+        Self::query(&self.root, self.region, &Bounds { min, max }, &mut v);
+        v
+    }
+
+    fn query(node: &QuadNode, region: Bounds, query: &Bounds, v: &mut Vec<(f32, f32)>) {
+        if !query.intersects(&region) {
+            return;
+        }
+        match node {
+            QuadNode::Leaf(points) => {
+                v.extend(points.iter().filter(|p| query.contains_point(**p)));
+            }
+            QuadNode::Inner(children) => {
+                for (i, child) in children.iter().enumerate() {
+                    Self::query(child, child_region(&region, i), query, v);
+                }
+            }
+        }
+    }
+
+    pub fn nearest(&self, point: (f32, f32)) -> Option<(f32, f32)> {
+        let mut best = None;
+        Self::nearest_node(&self.root, self.region, point, &mut best);
+        best.map(|(p, _)| p)
+    }
+
+    fn nearest_node(
+        node: &QuadNode,
+        region: Bounds,
+        point: (f32, f32),
+        best: &mut Option<((f32, f32), f32)>,
+    ) {
+        if let Some((_, best_dist)) = best {
+            if *best_dist <= region.distance_squared(point) {
+                return;
+            }
+        }
+        match node {
+            QuadNode::Leaf(points) => {
+                for p in points {
+                    let dist = distance_squared(*p, point);
+                    if best.map_or(true, |(_, best_dist)| dist < best_dist) {
+                        *best = Some((*p, dist));
+                    }
+                }
+            }
+            QuadNode::Inner(children) => {
+                // closest quadrants first, so the others are likely pruned
+                let mut order: Vec<(usize, f32)> = (0..4)
+                    .map(|i| {
+                        let dist = child_region(&region, i).distance_squared(point);
+                        (i, dist)
+                    })
+                    .collect();
+                order.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
+                for (i, _) in order {
+                    Self::nearest_node(&children[i], child_region(&region, i), point, best);
+                }
+            }
+        }
+    }
+
+    // regions of all leaves, used to draw the subdivision
+    pub fn cells(&self) -> Vec<Bounds> {
+        let mut cells = Vec::new();
+        Self::collect_cells(&self.root, self.region, &mut cells);
+        cells
+    }
+
+    fn collect_cells(node: &QuadNode, region: Bounds, cells: &mut Vec<Bounds>) {
+        match node {
+            QuadNode::Leaf(_) => cells.push(region),
+            QuadNode::Inner(children) => {
+                for (i, child) in children.iter().enumerate() {
+                    Self::collect_cells(child, child_region(&region, i), cells);
+                }
+            }
+        }
+    }
+}
+
+impl QuadNode {
+    fn len(&self) -> usize {
+        match self {
+            QuadNode::Leaf(points) => points.len(),
+            QuadNode::Inner(children) => children.iter().map(|child| child.len()).sum(),
+        }
+    }
+
+    fn collect(&self, points: &mut Vec<(f32, f32)>) {
+        match self {
+            QuadNode::Leaf(p) => points.extend_from_slice(p),
+            QuadNode::Inner(children) => {
+                for child in children.iter() {
+                    child.collect(points);
+                }
+            }
+        }
+    }
+}
+
+fn center(region: &Bounds) -> (f32, f32) {
+    (
+        (region.min.0 + region.max.0) / 2.0,
+        (region.min.1 + region.max.1) / 2.0,
+    )
+}
+
+fn quadrant(region: &Bounds, point: (f32, f32)) -> usize {
+    let center = center(region);
+    (center.0 <= point.0) as usize + 2 * ((center.1 <= point.1) as usize)
+}
+
+fn child_region(region: &Bounds, i: usize) -> Bounds {
+    let center = center(region);
+    let (x_min, x_max) = if i % 2 == 0 {
+        (region.min.0, center.0)
+    } else {
+        (center.0, region.max.0)
+    };
+    let (y_min, y_max) = if i / 2 == 0 {
+        (region.min.1, center.1)
+    } else {
+        (center.1, region.max.1)
+    };
+    Bounds {
+        min: (x_min, y_min),
+        max: (x_max, y_max),
+    }
+}
+
+fn distance_squared(a: (f32, f32), b: (f32, f32)) -> f32 {
+    (a.0 - b.0) * (a.0 - b.0) + (a.1 - b.1) * (a.1 - b.1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kd_tree::KdTree;
+    use crate::test_util::sorted;
+
+    fn region() -> Bounds {
+        Bounds {
+            min: (0.0, 0.0),
+            max: (100.0, 100.0),
+        }
+    }
+
+    fn points() -> Vec<(f32, f32)> {
+        (0..300)
+            .map(|i| (((i * 37) % 101) as f32, ((i * 53) % 97) as f32))
+            .collect()
+    }
+
+    #[test]
+    fn range_and_nearest_queries() {
+        let points = points();
+        // some points lie outside of the initial region
+        let mut tree = QuadTree::with_points(&points, region(), 4, 10);
+        tree.insert((-30.0, 250.0));
+        let mut all = points.clone();
+        all.push((-30.0, 250.0));
+        let kd_tree = KdTree::new(&all);
+        assert_eq!(tree.len(), kd_tree.len());
+
+        for i in 0..20 {
+            let min = ((i * 7 % 90) as f32 - 10.0, (i * 11 % 90) as f32 - 10.0);
+            let max = (min.0 + 25.0, min.1 + 40.0);
+            assert_eq!(
+                sorted(tree.range_query(min, max)),
+                sorted(kd_tree.range_query(min, max))
+            );
+
+            let q = (min.0 + 0.3, max.1 - 0.6);
+            let nearest = tree.nearest(q).unwrap();
+            let best = all
+                .iter()
+                .map(|p| distance_squared(*p, q))
+                .fold(std::f32::INFINITY, f32::min);
+            assert_eq!(distance_squared(nearest, q), best);
+        }
+    }
+
+    #[test]
+    fn removing_points_merges_cells() {
+        let points = points();
+        let mut tree = QuadTree::with_points(&points, region(), 4, 10);
+        assert!(1 < tree.cells().len());
+        for point in &points[4..] {
+            tree.remove(*point);
+        }
+        assert_eq!(tree.cells().len(), 1);
+        assert_eq!(sorted(tree.points()), sorted(points[..4].to_vec()));
+        assert!(!tree.remove((1000.0, 1000.0)));
+    }
+
+    #[test]
+    fn non_finite_points_are_rejected() {
+        let mut tree = QuadTree::with_points(&points(), region(), 4, 10);
+        let before = tree.region();
+        for point in &[
+            (std::f32::NAN, 1.0),
+            (1.0, std::f32::INFINITY),
+            (std::f32::NEG_INFINITY, 0.0),
+        ] {
+            assert!(!tree.insert(*point));
+        }
+        assert_eq!(tree.len(), points().len());
+        assert_eq!(tree.region(), before);
+    }
+
+    #[test]
+    fn depth_limit_stops_splitting() {
+        let mut tree = QuadTree::new(region(), 1, 3);
+        for i in 0..10 {
+            tree.insert((1.0 + i as f32 * 0.01, 1.0));
+        }
+        assert_eq!(tree.len(), 10);
+        // only the quadrants along the path to the cluster were split
+        assert_eq!(tree.cells().len(), 3 * 3 + 1);
+    }
+}
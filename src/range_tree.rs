@@ -0,0 +1,208 @@
+// 2d range tree with fractional cascading.
+// The primary tree is a balanced tree over the points sorted by x, every node stores the
+// points below it sorted by y. Instead of binary searching the y-array of every node,
+// each entry knows where the same position lies in the arrays of both children,
+// so a query needs one binary search at the root and O(log n + k) overall.
+#[derive(Clone, Debug)]
+pub struct RangeTree {
+    nodes: Vec<RangeNode>,
+    root: Option<usize>,
+}
+
+#[derive(Clone, Debug)]
+struct RangeNode {
+    // x range of the points below this node
+    x_min: f32,
+    x_max: f32,
+    left: Option<usize>,
+    right: Option<usize>,
+    // points below this node sorted by y then x
+    ys: Vec<(f32, f32)>,
+    // for position i of ys, the position of the first entry in the child's ys
+    // that is not smaller than ys[i], one additional entry for the end
+    left_bridge: Vec<usize>,
+    right_bridge: Vec<usize>,
+}
+
+impl RangeTree {
+    pub fn new(points: &[(f32, f32)]) -> Self {
+        let mut sorted_x = points.to_vec();
+        sorted_x.sort_by(|a, b| {
+            a.0.partial_cmp(&b.0)
+                .unwrap()
+                .then_with(|| a.1.partial_cmp(&b.1).unwrap())
+        });
+        sorted_x.dedup();
+
+        let mut tree = RangeTree {
+            nodes: Vec::with_capacity(2 * sorted_x.len()),
+            root: None,
+        };
+        if !sorted_x.is_empty() {
+            tree.root = Some(tree.construct(&sorted_x));
+        }
+        tree
+    }
+
+    fn construct(&mut self, sorted_x: &[(f32, f32)]) -> usize {
+        let x_min = sorted_x[0].0;
+        let x_max = sorted_x[sorted_x.len() - 1].0;
+
+        if sorted_x.len() == 1 {
+            self.nodes.push(RangeNode {
+                x_min,
+                x_max,
+                left: None,
+                right: None,
+                ys: sorted_x.to_vec(),
+                left_bridge: Vec::new(),
+                right_bridge: Vec::new(),
+            });
+            return self.nodes.len() - 1;
+        }
+
+        let median = sorted_x.len() / 2;
+        let left = self.construct(&sorted_x[..median]);
+        let right = self.construct(&sorted_x[median..]);
+
+        // merge the y-arrays of the children and remember the positions on the way
+        let len = sorted_x.len();
+        let mut ys = Vec::with_capacity(len);
+        let mut left_bridge = Vec::with_capacity(len + 1);
+        let mut right_bridge = Vec::with_capacity(len + 1);
+        {
+            let left_ys = &self.nodes[left].ys;
+            let right_ys = &self.nodes[right].ys;
+            let (mut l, mut r) = (0, 0);
+            while l < left_ys.len() || r < right_ys.len() {
+                left_bridge.push(l);
+                right_bridge.push(r);
+                let take_left =
+                    r == right_ys.len() || (l < left_ys.len() && y_order(left_ys[l], right_ys[r]));
+                if take_left {
+                    ys.push(left_ys[l]);
+                    l += 1;
+                } else {
+                    ys.push(right_ys[r]);
+                    r += 1;
+                }
+            }
+            left_bridge.push(l);
+            right_bridge.push(r);
+        }
+
+        self.nodes.push(RangeNode {
+            x_min,
+            x_max,
+            left: Some(left),
+            right: Some(right),
+            ys,
+            left_bridge,
+            right_bridge,
+        });
+        self.nodes.len() - 1
+    }
+
+    pub fn len(&self) -> usize {
+        self.root.map_or(0, |root| self.nodes[root].ys.len())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+
+    pub fn range_query(&self, min: (f32, f32), max: (f32, f32)) -> Vec<(f32, f32)> {
+        self.range_query_visited(min, max).0
+    }
+
+    // also returns the number of tree nodes the query looked at
+    pub fn range_query_visited(
+        &self,
+        min: (f32, f32),
+        max: (f32, f32),
+    ) -> (Vec<(f32, f32)>, usize) {
+        let mut v = Vec::new();
+        let mut visited = 0;
+        if let Some(root) = self.root {
+            // the only binary search, all other positions follow the bridges
+            let ys = &self.nodes[root].ys;
+            let (mut lo, mut hi) = (0, ys.len());
+            while lo < hi {
+                let mid = (lo + hi) / 2;
+                if ys[mid].1 < min.1 {
+                    lo = mid + 1;
+                } else {
+                    hi = mid;
+                }
+            }
+            self.query(root, lo, min, max, &mut v, &mut visited);
+        }
+        (v, visited)
+    }
+
+    // position is the first entry of the node's ys with y >= min.1
+    fn query(
+        &self,
+        node: usize,
+        position: usize,
+        min: (f32, f32),
+        max: (f32, f32),
+        v: &mut Vec<(f32, f32)>,
+        visited: &mut usize,
+    ) {
+        *visited += 1;
+        let n = &self.nodes[node];
+        if n.x_max < min.0 || max.0 < n.x_min {
+            return;
+        }
+
+        if min.0 <= n.x_min && n.x_max <= max.0 {
+            // x range is completely inside, report by y
+            for point in n.ys[position..].iter().take_while(|p| p.1 <= max.1) {
+                v.push(*point);
+            }
+            return;
+        }
+
+        // leaves have a single x value, so only knots can be partially inside
+        if let Some(left) = n.left {
+            self.query(left, n.left_bridge[position], min, max, v, visited);
+        }
+        if let Some(right) = n.right {
+            self.query(right, n.right_bridge[position], min, max, v, visited);
+        }
+    }
+}
+
+fn y_order(a: (f32, f32), b: (f32, f32)) -> bool {
+    a.1 < b.1 || (a.1 == b.1 && a.0 <= b.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kd_tree::KdTree;
+    use crate::test_util::sorted;
+
+    #[test]
+    fn range_query_matches_kd_tree() {
+        // a grid with many equal coordinates
+        let points: Vec<(f32, f32)> = (0..400)
+            .map(|i| (((i * 7) % 23) as f32, ((i * 13) % 19) as f32))
+            .collect();
+        let range_tree = RangeTree::new(&points);
+        let kd_tree = KdTree::new(&points);
+
+        for i in 0..30 {
+            let min = ((i % 11) as f32, (i % 7) as f32);
+            let max = (min.0 + (i % 5) as f32 * 3.0, min.1 + (i % 4) as f32 * 4.0);
+            assert_eq!(
+                sorted(range_tree.range_query(min, max)),
+                sorted(kd_tree.range_query(min, max))
+            );
+        }
+        assert!(RangeTree::new(&[])
+            .range_query((0.0, 0.0), (1.0, 1.0))
+            .is_empty());
+    }
+}
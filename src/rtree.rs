@@ -0,0 +1,526 @@
+use crate::kd_tree::Bounds;
+
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+// share of the entries of an overflowing leaf that is inserted again instead of splitting
+const REINSERT_SHARE: f32 = 0.3;
+
+// R-tree over bounding boxes with a payload each.
+// Bulk loading uses sort-tile-recursive packing, single insertions follow the R*-tree:
+// subtrees are chosen by least overlap enlargement on the leaf level, overflowing nodes are
+// split along the axis with the smallest margin and overflowing leaves first try to
+// reinsert their outermost entries.
+#[derive(Clone, Debug)]
+pub struct RTree<T> {
+    root: RNode<T>,
+    max_entries: usize,
+    min_entries: usize,
+    len: usize,
+}
+
+#[derive(Clone, Debug)]
+enum RNode<T> {
+    Leaf(Vec<(Bounds, T)>),
+    Inner(Vec<(Bounds, RNode<T>)>),
+}
+
+impl<T> RTree<T> {
+    pub fn new(max_entries: usize) -> Self {
+        assert!(
+            4 <= max_entries,
+            "r-tree nodes need room for at least 4 entries"
+        );
+        RTree {
+            root: RNode::Leaf(Vec::new()),
+            max_entries,
+            // R* uses 40% of the maximum as minimum fill
+            min_entries: (max_entries * 2 / 5).max(2),
+            len: 0,
+        }
+    }
+
+    pub fn bulk_load(entries: Vec<(Bounds, T)>, max_entries: usize) -> Self {
+        let mut tree = Self::new(max_entries);
+        tree.len = entries.len();
+        if entries.len() <= max_entries {
+            tree.root = RNode::Leaf(entries);
+            return tree;
+        }
+
+        let mut level: Vec<(Bounds, RNode<T>)> = str_pack(entries, max_entries)
+            .into_iter()
+            .map(|group| (union_all(&group), RNode::Leaf(group)))
+            .collect();
+        while max_entries < level.len() {
+            level = str_pack(level, max_entries)
+                .into_iter()
+                .map(|group| (union_all(&group), RNode::Inner(group)))
+                .collect();
+        }
+        tree.root = RNode::Inner(level);
+        tree
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn insert(&mut self, bounds: Bounds, payload: T) {
+        let mut reinsert = Vec::new();
+        self.insert_entry(bounds, payload, true, &mut reinsert);
+        // reinserted entries may not cause another reinsertion
+        for (bounds, payload) in reinsert {
+            self.insert_entry(bounds, payload, false, &mut Vec::new());
+        }
+        self.len += 1;
+    }
+
+    fn insert_entry(
+        &mut self,
+        bounds: Bounds,
+        payload: T,
+        allow_reinsert: bool,
+        reinsert: &mut Vec<(Bounds, T)>,
+    ) {
+        let mut allow_reinsert = allow_reinsert;
+        let split = Self::insert_node(
+            &mut self.root,
+            bounds,
+            payload,
+            (self.min_entries, self.max_entries),
+            true,
+            &mut allow_reinsert,
+            reinsert,
+        );
+        // a split root gets a new root above it
+        if let Some(sibling) = split {
+            let old_root = std::mem::replace(&mut self.root, RNode::Inner(Vec::new()));
+            let old_bounds = old_root.bounds();
+            if let RNode::Inner(children) = &mut self.root {
+                children.push((old_bounds, old_root));
+                children.push(sibling);
+            }
+        }
+    }
+
+    // returns the new sibling if node had to be split
+    fn insert_node(
+        node: &mut RNode<T>,
+        bounds: Bounds,
+        payload: T,
+        (min_entries, max_entries): (usize, usize),
+        is_root: bool,
+        allow_reinsert: &mut bool,
+        reinsert: &mut Vec<(Bounds, T)>,
+    ) -> Option<(Bounds, RNode<T>)> {
+        match node {
+            RNode::Leaf(entries) => {
+                entries.push((bounds, payload));
+                if entries.len() <= max_entries {
+                    return None;
+                }
+                if !is_root && *allow_reinsert {
+                    // remove the entries farthest from the center and insert them again later
+                    *allow_reinsert = false;
+                    let center = center(&union_all(entries));
+                    entries.sort_by(|a, b| {
+                        distance_squared(center, self::center(&a.0))
+                            .partial_cmp(&distance_squared(center, self::center(&b.0)))
+                            .unwrap()
+                    });
+                    let keep = entries.len() - (entries.len() as f32 * REINSERT_SHARE) as usize;
+                    reinsert.extend(entries.drain(keep..));
+                    return None;
+                }
+                let all = std::mem::replace(entries, Vec::new());
+                let (first, second) = split_entries(all, min_entries);
+                *entries = first;
+                Some((union_all(&second), RNode::Leaf(second)))
+            }
+            RNode::Inner(children) => {
+                let i = choose_subtree(children, &bounds);
+                let split = Self::insert_node(
+                    &mut children[i].1,
+                    bounds,
+                    payload,
+                    (min_entries, max_entries),
+                    false,
+                    allow_reinsert,
+                    reinsert,
+                );
+                children[i].0 = children[i].1.bounds();
+                if let Some(sibling) = split {
+                    children.push(sibling);
+                }
+                if children.len() <= max_entries {
+                    return None;
+                }
+                let all = std::mem::replace(children, Vec::new());
+                let (first, second) = split_entries(all, min_entries);
+                *children = first;
+                Some((union_all(&second), RNode::Inner(second)))
+            }
+        }
+    }
+
+    // payloads whose bounds intersect query
+    pub fn intersecting(&self, query: &Bounds) -> Vec<&T> {
+        let mut v = Vec::new();
+        self.root
+            .search(&mut v, &|b| query.intersects(b), &|b| query.intersects(b));
+        v
+    }
+
+    // payloads whose bounds are completely inside of query
+    pub fn contained_in(&self, query: &Bounds) -> Vec<&T> {
+        let mut v = Vec::new();
+        self.root
+            .search(&mut v, &|b| query.intersects(b), &|b| query.contains(b));
+        v
+    }
+
+    // payloads whose bounds completely contain query
+    pub fn containing(&self, query: &Bounds) -> Vec<&T> {
+        let mut v = Vec::new();
+        self.root
+            .search(&mut v, &|b| b.contains(query), &|b| b.contains(query));
+        v
+    }
+
+    // nearest entry by distance to its bounding box
+    pub fn nearest(&self, point: (f32, f32)) -> Option<(&T, f32)> {
+        self.nearest_by(point, |bounds, _| bounds.distance_squared(point).sqrt())
+    }
+
+    // nearest entry by a custom distance, e.g. to the segment inside of a bounding box,
+    // distance must never be smaller than the distance to the bounding box
+    pub fn nearest_by<F: Fn(&Bounds, &T) -> f32>(
+        &self,
+        point: (f32, f32),
+        distance: F,
+    ) -> Option<(&T, f32)> {
+        if self.is_empty() {
+            return None;
+        }
+        let mut heap = BinaryHeap::new();
+        let mut best: Option<(&T, f32)> = None;
+        heap.push(Candidate {
+            distance: self.root.bounds().distance_squared(point).sqrt(),
+            node: &self.root,
+        });
+        while let Some(Candidate { distance: d, node }) = heap.pop() {
+            if let Some((_, best_distance)) = best {
+                if best_distance <= d {
+                    break;
+                }
+            }
+            match node {
+                RNode::Leaf(entries) => {
+                    for (bounds, payload) in entries {
+                        let d = distance(bounds, payload);
+                        if best.map_or(true, |(_, best_distance)| d < best_distance) {
+                            best = Some((payload, d));
+                        }
+                    }
+                }
+                RNode::Inner(children) => {
+                    for (bounds, child) in children {
+                        heap.push(Candidate {
+                            distance: bounds.distance_squared(point).sqrt(),
+                            node: child,
+                        });
+                    }
+                }
+            }
+        }
+        best
+    }
+}
+
+impl<T> RNode<T> {
+    fn bounds(&self) -> Bounds {
+        match self {
+            RNode::Leaf(entries) => union_all(entries),
+            RNode::Inner(children) => union_all(children),
+        }
+    }
+
+    // descends into children passing visit, reports entries passing report
+    fn search<'a, V: Fn(&Bounds) -> bool, R: Fn(&Bounds) -> bool>(
+        &'a self,
+        v: &mut Vec<&'a T>,
+        visit: &V,
+        report: &R,
+    ) {
+        match self {
+            RNode::Leaf(entries) => {
+                for (bounds, payload) in entries {
+                    if report(bounds) {
+                        v.push(payload);
+                    }
+                }
+            }
+            RNode::Inner(children) => {
+                for (bounds, child) in children {
+                    if visit(bounds) {
+                        child.search(v, visit, report);
+                    }
+                }
+            }
+        }
+    }
+}
+
+struct Candidate<'a, T> {
+    distance: f32,
+    node: &'a RNode<T>,
+}
+
+impl<'a, T> PartialEq for Candidate<'a, T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.distance == other.distance
+    }
+}
+
+impl<'a, T> Eq for Candidate<'a, T> {}
+
+impl<'a, T> PartialOrd for Candidate<'a, T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<'a, T> Ord for Candidate<'a, T> {
+    // reversed, the heap pops the closest candidate first
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.distance.partial_cmp(&self.distance).unwrap()
+    }
+}
+
+// sort-tile-recursive: vertical slabs by x center, each slab cut into nodes by y center
+fn str_pack<X>(mut entries: Vec<(Bounds, X)>, max_entries: usize) -> Vec<Vec<(Bounds, X)>> {
+    let node_count = (entries.len() + max_entries - 1) / max_entries;
+    let slab_count = (node_count as f32).sqrt().ceil() as usize;
+    let slab_size = slab_count * max_entries;
+
+    entries.sort_by(|a, b| center(&a.0).0.partial_cmp(&center(&b.0).0).unwrap());
+    let mut groups = Vec::with_capacity(node_count);
+    while !entries.is_empty() {
+        let rest = entries.split_off(slab_size.min(entries.len()));
+        let mut slab = std::mem::replace(&mut entries, rest);
+        slab.sort_by(|a, b| center(&a.0).1.partial_cmp(&center(&b.0).1).unwrap());
+        while !slab.is_empty() {
+            let rest = slab.split_off(max_entries.min(slab.len()));
+            groups.push(std::mem::replace(&mut slab, rest));
+        }
+    }
+    groups
+}
+
+// R* split: the axis with the smallest margin sum, on it the distribution with least overlap
+fn split_entries<X>(
+    mut entries: Vec<(Bounds, X)>,
+    min_entries: usize,
+) -> (Vec<(Bounds, X)>, Vec<(Bounds, X)>) {
+    let count = entries.len();
+    let distributions = min_entries..=(count - min_entries);
+
+    let sort_axis = |entries: &mut Vec<(Bounds, X)>, axis: usize, by_max: bool| {
+        entries.sort_by(|a, b| {
+            let (a, b) = if by_max {
+                (a.0.max, b.0.max)
+            } else {
+                (a.0.min, b.0.min)
+            };
+            let (a, b) = if axis == 0 { (a.0, b.0) } else { (a.1, b.1) };
+            a.partial_cmp(&b).unwrap()
+        })
+    };
+
+    let mut best_axis = 0;
+    let mut best_margin = std::f32::INFINITY;
+    for axis in 0..2 {
+        let mut margin = 0.0;
+        for &by_max in &[false, true] {
+            sort_axis(&mut entries, axis, by_max);
+            for k in distributions.clone() {
+                margin += perimeter(&union_all(&entries[..k]));
+                margin += perimeter(&union_all(&entries[k..]));
+            }
+        }
+        if margin < best_margin {
+            best_margin = margin;
+            best_axis = axis;
+        }
+    }
+
+    let mut best = (false, min_entries);
+    let mut best_cost = (std::f32::INFINITY, std::f32::INFINITY);
+    for &by_max in &[false, true] {
+        sort_axis(&mut entries, best_axis, by_max);
+        for k in distributions.clone() {
+            let first = union_all(&entries[..k]);
+            let second = union_all(&entries[k..]);
+            let cost = (overlap(&first, &second), area(&first) + area(&second));
+            if cost < best_cost {
+                best_cost = cost;
+                best = (by_max, k);
+            }
+        }
+    }
+
+    sort_axis(&mut entries, best_axis, best.0);
+    let second = entries.split_off(best.1);
+    (entries, second)
+}
+
+fn choose_subtree<T>(children: &[(Bounds, RNode<T>)], bounds: &Bounds) -> usize {
+    let leaf_level = children.iter().all(|(_, child)| match child {
+        RNode::Leaf(_) => true,
+        RNode::Inner(_) => false,
+    });
+
+    let cost = |i: usize| -> (f32, f32, f32) {
+        let old = &children[i].0;
+        let new = old.union(bounds);
+        let area_enlargement = area(&new) - area(old);
+        let overlap_enlargement = if leaf_level {
+            children
+                .iter()
+                .enumerate()
+                .filter(|(j, _)| *j != i)
+                .map(|(_, (other, _))| overlap(&new, other) - overlap(old, other))
+                .sum()
+        } else {
+            0.0
+        };
+        (overlap_enlargement, area_enlargement, area(old))
+    };
+
+    let mut best = 0;
+    let mut best_cost = cost(0);
+    for i in 1..children.len() {
+        let c = cost(i);
+        if c < best_cost {
+            best = i;
+            best_cost = c;
+        }
+    }
+    best
+}
+
+fn union_all<X>(entries: &[(Bounds, X)]) -> Bounds {
+    let mut bounds = entries[0].0;
+    for (b, _) in &entries[1..] {
+        bounds = bounds.union(b);
+    }
+    bounds
+}
+
+fn area(b: &Bounds) -> f32 {
+    (b.max.0 - b.min.0) * (b.max.1 - b.min.1)
+}
+
+fn perimeter(b: &Bounds) -> f32 {
+    2.0 * ((b.max.0 - b.min.0) + (b.max.1 - b.min.1))
+}
+
+fn overlap(a: &Bounds, b: &Bounds) -> f32 {
+    let dx = a.max.0.min(b.max.0) - a.min.0.max(b.min.0);
+    let dy = a.max.1.min(b.max.1) - a.min.1.max(b.min.1);
+    if dx <= 0.0 || dy <= 0.0 {
+        0.0
+    } else {
+        dx * dy
+    }
+}
+
+fn center(b: &Bounds) -> (f32, f32) {
+    ((b.min.0 + b.max.0) / 2.0, (b.min.1 + b.max.1) / 2.0)
+}
+
+fn distance_squared(a: (f32, f32), b: (f32, f32)) -> f32 {
+    (a.0 - b.0) * (a.0 - b.0) + (a.1 - b.1) * (a.1 - b.1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::sorted;
+
+    fn rects() -> Vec<(Bounds, usize)> {
+        (0..500)
+            .map(|i| {
+                let min = (((i * 37) % 211) as f32, ((i * 53) % 197) as f32);
+                let size = (((i * 7) % 13) as f32, ((i * 11) % 9) as f32);
+                let max = (min.0 + size.0, min.1 + size.1);
+                (Bounds { min, max }, i)
+            })
+            .collect()
+    }
+
+    fn check_queries(tree: &RTree<usize>, rects: &[(Bounds, usize)]) {
+        for i in 0..25 {
+            let min = ((i * 17 % 200) as f32, (i * 29 % 190) as f32);
+            let query = Bounds {
+                min,
+                max: (min.0 + 30.0, min.1 + 20.0),
+            };
+            let expected = |f: &dyn Fn(&Bounds) -> bool| -> Vec<usize> {
+                sorted(
+                    rects
+                        .iter()
+                        .filter(|(b, _)| f(b))
+                        .map(|(_, i)| *i)
+                        .collect(),
+                )
+            };
+            let found = |v: Vec<&usize>| sorted(v.into_iter().cloned().collect());
+
+            assert_eq!(
+                found(tree.intersecting(&query)),
+                expected(&|b| query.intersects(b))
+            );
+            assert_eq!(
+                found(tree.contained_in(&query)),
+                expected(&|b| query.contains(b))
+            );
+            let point = Bounds::point(min);
+            assert_eq!(
+                found(tree.containing(&point)),
+                expected(&|b| b.contains(&point))
+            );
+
+            let (_, distance) = tree.nearest(min).unwrap();
+            let best = rects
+                .iter()
+                .map(|(b, _)| b.distance_squared(min).sqrt())
+                .fold(std::f32::INFINITY, f32::min);
+            assert_eq!(distance, best);
+        }
+    }
+
+    #[test]
+    fn bulk_loaded_queries_match_brute_force() {
+        let rects = rects();
+        let tree = RTree::bulk_load(rects.clone(), 8);
+        assert_eq!(tree.len(), rects.len());
+        check_queries(&tree, &rects);
+    }
+
+    #[test]
+    fn inserted_queries_match_brute_force() {
+        let rects = rects();
+        let mut tree = RTree::new(6);
+        for (bounds, i) in &rects {
+            tree.insert(*bounds, *i);
+        }
+        assert_eq!(tree.len(), rects.len());
+        check_queries(&tree, &rects);
+    }
+}
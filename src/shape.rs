@@ -0,0 +1,225 @@
+use crate::kd_tree::Bounds;
+
+// where a rectangle lies relative to a shape
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Classification {
+    Inside,
+    Outside,
+    Partial,
+}
+
+// query region for the spatial structures, boundaries count as inside
+pub trait Shape {
+    fn contains(&self, p: (f32, f32)) -> bool;
+
+    // has to be exact for Inside and Outside, Partial is always a safe answer
+    fn classify(&self, bounds: &Bounds) -> Classification;
+}
+
+fn corners(bounds: &Bounds) -> [(f32, f32); 4] {
+    [
+        bounds.min,
+        (bounds.max.0, bounds.min.1),
+        bounds.max,
+        (bounds.min.0, bounds.max.1),
+    ]
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Circle {
+    pub center: (f32, f32),
+    pub radius: f32,
+}
+
+impl Shape for Circle {
+    fn contains(&self, p: (f32, f32)) -> bool {
+        Bounds::point(p).distance_squared(self.center) <= self.radius * self.radius
+    }
+
+    fn classify(&self, bounds: &Bounds) -> Classification {
+        if self.radius * self.radius < bounds.distance_squared(self.center) {
+            Classification::Outside
+        } else if corners(bounds).iter().all(|c| self.contains(*c)) {
+            Classification::Inside
+        } else {
+            Classification::Partial
+        }
+    }
+}
+
+// everything left of the directed line from a to b, left being counter clockwise
+// in a y-up coordinate system
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HalfPlane {
+    pub a: (f32, f32),
+    pub b: (f32, f32),
+}
+
+impl HalfPlane {
+    // positive left of the line, negative right of it
+    fn side(&self, p: (f32, f32)) -> f32 {
+        (self.b.0 - self.a.0) * (p.1 - self.a.1) - (self.b.1 - self.a.1) * (p.0 - self.a.0)
+    }
+}
+
+impl Shape for HalfPlane {
+    fn contains(&self, p: (f32, f32)) -> bool {
+        0.0 <= self.side(p)
+    }
+
+    fn classify(&self, bounds: &Bounds) -> Classification {
+        let corners = corners(bounds);
+        if corners.iter().all(|c| 0.0 <= self.side(*c)) {
+            Classification::Inside
+        } else if corners.iter().all(|c| self.side(*c) < 0.0) {
+            Classification::Outside
+        } else {
+            Classification::Partial
+        }
+    }
+}
+
+// intersection of the half-planes left of its edges
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConvexPolygon {
+    vertices: Vec<(f32, f32)>,
+    edges: Vec<HalfPlane>,
+    bounds: Option<Bounds>,
+}
+
+impl ConvexPolygon {
+    // vertices have to be in convex position, in either order
+    pub fn new(vertices: &[(f32, f32)]) -> Self {
+        let mut vertices = vertices.to_vec();
+        let area: f32 = (0..vertices.len())
+            .map(|i| {
+                let (a, b) = (vertices[i], vertices[(i + 1) % vertices.len()]);
+                a.0 * b.1 - b.0 * a.1
+            })
+            .sum();
+        if area < 0.0 {
+            vertices.reverse();
+        }
+        let edges = (0..vertices.len())
+            .map(|i| HalfPlane {
+                a: vertices[i],
+                b: vertices[(i + 1) % vertices.len()],
+            })
+            .collect();
+        let bounds = vertices.first().map(|first| {
+            vertices
+                .iter()
+                .fold(Bounds::point(*first), |b, v| b.union(&Bounds::point(*v)))
+        });
+        ConvexPolygon {
+            vertices,
+            edges,
+            bounds,
+        }
+    }
+
+    pub fn triangle(a: (f32, f32), b: (f32, f32), c: (f32, f32)) -> Self {
+        Self::new(&[a, b, c])
+    }
+
+    pub fn vertices(&self) -> &[(f32, f32)] {
+        &self.vertices
+    }
+}
+
+impl Shape for ConvexPolygon {
+    fn contains(&self, p: (f32, f32)) -> bool {
+        match &self.bounds {
+            Some(bounds) => bounds.contains_point(p) && self.edges.iter().all(|e| e.contains(p)),
+            None => false,
+        }
+    }
+
+    fn classify(&self, bounds: &Bounds) -> Classification {
+        // separating axis test, the axes of the rectangle are covered by the bounding box
+        match &self.bounds {
+            Some(polygon_bounds) if polygon_bounds.intersects(bounds) => {}
+            _ => return Classification::Outside,
+        }
+        let mut inside = true;
+        for edge in &self.edges {
+            match edge.classify(bounds) {
+                Classification::Outside => return Classification::Outside,
+                Classification::Partial => inside = false,
+                Classification::Inside => {}
+            }
+        }
+        if inside {
+            Classification::Inside
+        } else {
+            Classification::Partial
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grid() -> Vec<(f32, f32)> {
+        (0..400)
+            .map(|i| ((i % 20) as f32 * 5.0, (i / 20) as f32 * 5.0))
+            .collect()
+    }
+
+    fn cells() -> Vec<Bounds> {
+        (0..64)
+            .map(|i| {
+                let min = ((i % 8) as f32 * 13.0 - 5.0, (i / 8) as f32 * 13.0 - 5.0);
+                Bounds {
+                    min,
+                    max: (min.0 + 3.0 + (i % 5) as f32 * 7.0, min.1 + 11.0),
+                }
+            })
+            .collect()
+    }
+
+    // classification has to agree with contains on every point of the rectangle
+    fn check_classification<S: Shape>(shape: &S) {
+        for cell in cells() {
+            let inside: Vec<bool> = grid()
+                .into_iter()
+                .filter(|p| cell.contains_point(*p))
+                .map(|p| shape.contains(p))
+                .collect();
+            match shape.classify(&cell) {
+                Classification::Inside => assert!(inside.iter().all(|x| *x), "{:?}", cell),
+                Classification::Outside => assert!(inside.iter().all(|x| !*x), "{:?}", cell),
+                Classification::Partial => {}
+            }
+        }
+    }
+
+    #[test]
+    fn classification_matches_contains() {
+        check_classification(&Circle {
+            center: (40.0, 50.0),
+            radius: 23.0,
+        });
+        check_classification(&HalfPlane {
+            a: (10.0, 0.0),
+            b: (60.0, 90.0),
+        });
+        let triangle = ConvexPolygon::triangle((10.0, 10.0), (80.0, 30.0), (30.0, 70.0));
+        check_classification(&triangle);
+        // the same triangle in the other orientation
+        let reversed = ConvexPolygon::triangle((10.0, 10.0), (30.0, 70.0), (80.0, 30.0));
+        for p in grid() {
+            assert_eq!(triangle.contains(p), reversed.contains(p));
+        }
+        assert!(triangle.contains((30.0, 30.0)));
+        assert!(!triangle.contains((70.0, 60.0)));
+        check_classification(&ConvexPolygon::new(&[
+            (20.0, 5.0),
+            (70.0, 10.0),
+            (90.0, 50.0),
+            (50.0, 90.0),
+            (5.0, 40.0),
+        ]));
+    }
+}
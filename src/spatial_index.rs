@@ -0,0 +1,301 @@
+use crate::grid::UniformGrid;
+use crate::kd_tree::{Bounds, KdTree};
+use crate::quadtree::QuadTree;
+
+// Point set structures that can be exchanged against each other at runtime.
+// Points are a set, inserting a present point or removing a missing one returns false.
+pub trait SpatialIndex {
+    fn build(points: &[(f32, f32)]) -> Self
+    where
+        Self: Sized;
+
+    fn name(&self) -> &str;
+
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn insert(&mut self, point: (f32, f32)) -> bool;
+
+    fn remove(&mut self, point: (f32, f32)) -> bool;
+
+    fn range(&self, min: (f32, f32), max: (f32, f32)) -> Vec<(f32, f32)>;
+
+    fn nearest(&self, point: (f32, f32)) -> Option<(f32, f32)>;
+
+    fn radius(&self, center: (f32, f32), radius: f32) -> Vec<(f32, f32)> {
+        let min = (center.0 - radius, center.1 - radius);
+        let max = (center.0 + radius, center.1 + radius);
+        self.range(min, max)
+            .into_iter()
+            .filter(|p| Bounds::point(*p).distance_squared(center) <= radius * radius)
+            .collect()
+    }
+
+    // cells the structure partitions the plane into, empty if there are none to show
+    fn cells(&self) -> Vec<Bounds> {
+        Vec::new()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum IndexKind {
+    KdTree,
+    QuadTree,
+    Grid,
+    BruteForce,
+}
+
+impl IndexKind {
+    pub fn all() -> [IndexKind; 4] {
+        [
+            IndexKind::KdTree,
+            IndexKind::QuadTree,
+            IndexKind::Grid,
+            IndexKind::BruteForce,
+        ]
+    }
+
+    pub fn next(self) -> IndexKind {
+        let all = Self::all();
+        let i = all.iter().position(|kind| *kind == self).unwrap();
+        all[(i + 1) % all.len()]
+    }
+
+    pub fn build(self, points: &[(f32, f32)]) -> Box<dyn SpatialIndex> {
+        match self {
+            IndexKind::KdTree => Box::new(KdTree::build(points)),
+            IndexKind::QuadTree => Box::new(QuadTree::build(points)),
+            IndexKind::Grid => Box::new(UniformGrid::build(points)),
+            IndexKind::BruteForce => Box::new(BruteForce::build(points)),
+        }
+    }
+}
+
+// reference implementation that checks every point
+#[derive(Clone, Debug)]
+pub struct BruteForce {
+    points: Vec<(f32, f32)>,
+}
+
+impl SpatialIndex for BruteForce {
+    fn build(points: &[(f32, f32)]) -> Self {
+        let mut index = BruteForce { points: Vec::new() };
+        for point in points {
+            index.insert(*point);
+        }
+        index
+    }
+
+    fn name(&self) -> &str {
+        "brute force"
+    }
+
+    fn len(&self) -> usize {
+        self.points.len()
+    }
+
+    fn insert(&mut self, point: (f32, f32)) -> bool {
+        if self.points.contains(&point) {
+            return false;
+        }
+        self.points.push(point);
+        true
+    }
+
+    fn remove(&mut self, point: (f32, f32)) -> bool {
+        self.points.remove_item(&point).is_some()
+    }
+
+    fn range(&self, min: (f32, f32), max: (f32, f32)) -> Vec<(f32, f32)> {
+        let query = Bounds { min, max };
+        self.points
+            .iter()
+            .filter(|p| query.contains_point(**p))
+            .cloned()
+            .collect()
+    }
+
+    fn nearest(&self, point: (f32, f32)) -> Option<(f32, f32)> {
+        self.points.iter().cloned().min_by(|a, b| {
+            let a = Bounds::point(*a).distance_squared(point);
+            let b = Bounds::point(*b).distance_squared(point);
+            a.partial_cmp(&b).unwrap()
+        })
+    }
+}
+
+impl SpatialIndex for KdTree {
+    fn build(points: &[(f32, f32)]) -> Self {
+        KdTree::new(points)
+    }
+
+    fn name(&self) -> &str {
+        "2d-tree"
+    }
+
+    fn len(&self) -> usize {
+        KdTree::len(self)
+    }
+
+    fn insert(&mut self, point: (f32, f32)) -> bool {
+        KdTree::insert(self, point)
+    }
+
+    fn remove(&mut self, point: (f32, f32)) -> bool {
+        KdTree::remove(self, point)
+    }
+
+    fn range(&self, min: (f32, f32), max: (f32, f32)) -> Vec<(f32, f32)> {
+        self.range_query(min, max)
+    }
+
+    fn nearest(&self, point: (f32, f32)) -> Option<(f32, f32)> {
+        KdTree::nearest(self, point)
+    }
+}
+
+impl SpatialIndex for QuadTree {
+    fn build(points: &[(f32, f32)]) -> Self {
+        let region = if points.is_empty() {
+            Bounds {
+                min: (0.0, 0.0),
+                max: (1.0, 1.0),
+            }
+        } else {
+            points
+                .iter()
+                .fold(Bounds::point(points[0]), |b, p| b.union(&Bounds::point(*p)))
+        };
+        QuadTree::with_points(points, region, 4, 16)
+    }
+
+    fn name(&self) -> &str {
+        "quadtree"
+    }
+
+    fn len(&self) -> usize {
+        QuadTree::len(self)
+    }
+
+    fn insert(&mut self, point: (f32, f32)) -> bool {
+        QuadTree::insert(self, point)
+    }
+
+    fn remove(&mut self, point: (f32, f32)) -> bool {
+        QuadTree::remove(self, point)
+    }
+
+    fn range(&self, min: (f32, f32), max: (f32, f32)) -> Vec<(f32, f32)> {
+        self.range_query(min, max)
+    }
+
+    fn nearest(&self, point: (f32, f32)) -> Option<(f32, f32)> {
+        QuadTree::nearest(self, point)
+    }
+
+    fn cells(&self) -> Vec<Bounds> {
+        QuadTree::cells(self)
+    }
+}
+
+impl SpatialIndex for UniformGrid {
+    fn build(points: &[(f32, f32)]) -> Self {
+        UniformGrid::with_points(points)
+    }
+
+    fn name(&self) -> &str {
+        "uniform grid"
+    }
+
+    fn len(&self) -> usize {
+        UniformGrid::len(self)
+    }
+
+    fn insert(&mut self, point: (f32, f32)) -> bool {
+        UniformGrid::insert(self, point)
+    }
+
+    fn remove(&mut self, point: (f32, f32)) -> bool {
+        UniformGrid::remove(self, point)
+    }
+
+    fn range(&self, min: (f32, f32), max: (f32, f32)) -> Vec<(f32, f32)> {
+        self.range_query(min, max)
+    }
+
+    fn nearest(&self, point: (f32, f32)) -> Option<(f32, f32)> {
+        UniformGrid::nearest(self, point)
+    }
+
+    fn cells(&self) -> Vec<Bounds> {
+        UniformGrid::cells(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::sorted;
+
+    fn points() -> Vec<(f32, f32)> {
+        (0..400)
+            .map(|i| (((i * 37) % 101) as f32 * 3.0, ((i * 53) % 97) as f32 * 2.0))
+            .collect()
+    }
+
+    #[test]
+    fn all_indexes_agree_with_brute_force() {
+        let points = points();
+        let mut reference = BruteForce::build(&points);
+        // remove some points and add some outside of the original bounds
+        let removed: Vec<(f32, f32)> = (0..50).map(|i| points[i * 7]).collect();
+        let added: Vec<(f32, f32)> = (0..50)
+            .map(|i| (-5.0 * i as f32, 400.0 + i as f32))
+            .collect();
+        for (r, a) in removed.iter().zip(&added) {
+            reference.remove(*r);
+            reference.insert(*a);
+        }
+
+        for kind in IndexKind::all().iter() {
+            let mut index = kind.build(&points);
+            assert_eq!(index.len(), points.len());
+            for (r, a) in removed.iter().zip(&added) {
+                assert!(index.remove(*r));
+                assert!(!index.remove(*r));
+                assert!(index.insert(*a));
+                assert!(!index.insert(*a));
+            }
+            assert_eq!(index.len(), reference.len());
+
+            for i in 0..30 {
+                let q = ((i * 13 % 300) as f32 + 0.5, (i * 29 % 210) as f32 - 0.25);
+                let max = (q.0 + 40.0, q.1 + 25.0);
+                assert_eq!(
+                    sorted(index.range(q, max)),
+                    sorted(reference.range(q, max)),
+                    "{}",
+                    index.name()
+                );
+                assert_eq!(
+                    sorted(index.radius(q, 17.0)),
+                    sorted(reference.radius(q, 17.0)),
+                    "{}",
+                    index.name()
+                );
+                let nearest = index.nearest(q).unwrap();
+                let expected = reference.nearest(q).unwrap();
+                assert_eq!(
+                    Bounds::point(nearest).distance_squared(q),
+                    Bounds::point(expected).distance_squared(q),
+                    "{}",
+                    index.name()
+                );
+            }
+        }
+        assert_eq!(BruteForce::build(&[]).nearest((0.0, 0.0)), None);
+    }
+}
@@ -2,9 +2,11 @@ use ggez::event::{Keycode, Mod, MouseButton, MouseState};
 use ggez::*;
 use ggez_goodies::scene::*;
 
+pub mod fortune_state;
 pub mod line_state;
 pub mod menu_state;
 pub mod point_state;
+pub mod polygon_state;
 pub mod search_tree_state;
 pub mod triangulate_state;
 
@@ -15,11 +17,16 @@ pub enum Event {
     LeftMouseButton { x: i32, y: i32 },
     MouseMove { x: i32, y: i32 },
     RightMouseButton { x: i32, y: i32 },
+    RightMouseButtonDown { x: i32, y: i32 },
     ArrowDown,
     ArrowUp,
     Return,
     Esc,
     Mode,
+    Compare,
+    Structure,
+    Shape,
+    Voronoi,
     Ignore,
 }
 
@@ -44,6 +51,11 @@ impl event::EventHandler for MainState {
         self.scenes.draw(ctx);
         Ok(())
     }
+    fn mouse_button_down_event(&mut self, _ctx: &mut Context, button: MouseButton, x: i32, y: i32) {
+        if let MouseButton::Right = button {
+            self.scenes.input(Event::RightMouseButtonDown { x, y }, true);
+        }
+    }
     fn mouse_button_up_event(&mut self, _ctx: &mut Context, button: MouseButton, x: i32, y: i32) {
         let event = match button {
             MouseButton::Left => Event::LeftMouseButton { x, y },
@@ -62,6 +74,10 @@ impl event::EventHandler for MainState {
                 Keycode::Return => Event::Return,
                 Keycode::Escape => Event::Esc,
                 Keycode::M => Event::Mode,
+                Keycode::C => Event::Compare,
+                Keycode::S => Event::Structure,
+                Keycode::Q => Event::Shape,
+                Keycode::V => Event::Voronoi,
                 _ => Event::Ignore,
             }
         } else {
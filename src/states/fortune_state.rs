@@ -0,0 +1,166 @@
+use ggez::graphics::{DrawMode, Point2};
+use ggez::*;
+
+use super::*;
+use crate::fortune::Fortune;
+
+pub struct FortuneState {
+    points: Vec<Point2>,
+    sweep: Fortune,
+    // the animated sweep line, every event above it has been handled
+    position: f32,
+    speed: f32,
+    paused: bool,
+    point_color: graphics::Color,
+    sweep_color: graphics::Color,
+    beach_color: graphics::Color,
+    edge_color: graphics::Color,
+    event_color: graphics::Color,
+    close: bool,
+}
+
+impl FortuneState {
+    pub fn new() -> Self {
+        FortuneState {
+            points: Vec::new(),
+            sweep: Fortune::new(&[]),
+            position: 0.0,
+            speed: 1.0,
+            paused: false,
+            point_color: graphics::Color::from_rgb(255, 255, 255),
+            sweep_color: graphics::Color::from_rgb(200, 50, 50),
+            beach_color: graphics::Color::from_rgb(0, 200, 255),
+            edge_color: graphics::Color::from_rgb(255, 255, 0),
+            event_color: graphics::Color::from_rgb(100, 100, 100),
+            close: false,
+        }
+    }
+
+    fn restart(&mut self) {
+        self.sweep = Fortune::new(&self.points);
+        self.position = 0.0;
+    }
+}
+
+impl Scene<SharedState, Event> for FortuneState {
+    fn update(&mut self, _state: &mut SharedState) -> SceneSwitch<SharedState, Event> {
+        if !self.paused {
+            self.position += self.speed;
+        }
+        while let Some(y) = self.sweep.next_event() {
+            if self.position < y {
+                break;
+            }
+            self.sweep.step();
+        }
+
+        if self.close {
+            SceneSwitch::Pop
+        } else {
+            SceneSwitch::None
+        }
+    }
+    fn draw(&mut self, _state: &mut SharedState, ctx: &mut ggez::Context) -> ggez::GameResult<()> {
+        graphics::clear(ctx);
+        let width = ctx.conf.window_mode.width as f32;
+        let height = ctx.conf.window_mode.height as f32;
+        // keep the sweep line from running away once everything is done
+        if 2.0 * height < self.position && self.sweep.is_done() {
+            self.position = 2.0 * height;
+        }
+
+        // pending circle events and the point where the sweep line reaches them
+        graphics::set_color(ctx, self.event_color)?;
+        for (center, radius) in self.sweep.circle_events() {
+            graphics::circle(ctx, DrawMode::Line(1.0), center, radius, 0.5)?;
+            let bottom = Point2::new(center.x, center.y + radius);
+            graphics::circle(ctx, DrawMode::Fill, bottom, 2.0, 0.15)?;
+        }
+
+        graphics::set_color(ctx, self.edge_color)?;
+        for (a, b) in self.sweep.edges(self.position) {
+            graphics::line(ctx, &[a, b], 1.0)?;
+        }
+        for vertex in self.sweep.vertices() {
+            graphics::circle(ctx, DrawMode::Fill, vertex, 2.0, 0.15)?;
+        }
+
+        // the beach line, sampled between the breakpoints
+        graphics::set_color(ctx, self.beach_color)?;
+        for (site, from, to) in self.sweep.beach_line(self.position) {
+            if self.position <= self.points[site].y {
+                continue;
+            }
+            let (from, to) = (from.max(0.0), to.min(width));
+            if to <= from {
+                continue;
+            }
+            let samples = ((to - from) / 4.0).ceil().max(1.0) as usize;
+            let curve: Vec<Point2> = (0..=samples)
+                .map(|i| {
+                    let x = from + (to - from) * i as f32 / samples as f32;
+                    let y = self.sweep.parabola(site, self.position, x);
+                    Point2::new(x, y.max(-10.0))
+                })
+                .collect();
+            graphics::line(ctx, &curve, 1.0)?;
+        }
+
+        graphics::set_color(ctx, self.sweep_color)?;
+        graphics::line(
+            ctx,
+            &[
+                Point2::new(0.0, self.position),
+                Point2::new(width, self.position),
+            ],
+            1.0,
+        )?;
+
+        graphics::set_color(ctx, self.point_color)?;
+        for point in &self.points {
+            graphics::circle(ctx, DrawMode::Fill, *point, 2.5, 0.15)?;
+        }
+
+        let font = graphics::Font::default_font().unwrap();
+        let text = graphics::Text::new(
+            ctx,
+            "press return to jump to the next event, m to pause, up and down to change the speed",
+            &font,
+        )?;
+        graphics::draw(ctx, &text, Point2::new(10.0, 10.0), 0.0)?;
+
+        graphics::present(ctx);
+        Ok(())
+    }
+    fn input(&mut self, _state: &mut SharedState, event: Event, _started: bool) {
+        match event {
+            Event::LeftMouseButton { x, y } => {
+                let point = Point2::new(x as f32, y as f32);
+                if !self.points.contains(&point) {
+                    debug!("Created Point: {}", point);
+                    self.points.push(point);
+                } else {
+                    debug!("Removed Point: {}", point);
+                    self.points.remove_item(&point);
+                }
+                self.restart();
+            }
+            Event::Return => {
+                if let Some(y) = self.sweep.next_event() {
+                    self.position = y;
+                }
+            }
+            Event::Mode => self.paused = !self.paused,
+            Event::ArrowUp => self.speed *= 2.0,
+            Event::ArrowDown => self.speed /= 2.0,
+            Event::Esc => self.close = true,
+            _ => {}
+        }
+    }
+    fn name(&self) -> &str {
+        "fortune's sweep"
+    }
+    fn draw_previous(&self) -> bool {
+        false
+    }
+}
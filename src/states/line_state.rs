@@ -4,11 +4,19 @@ use ggez::*;
 use super::*;
 use crate::intersection;
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Method {
+    // candidate pairs from an r-tree of the segment bounding boxes
+    RTree,
+    ScanLine,
+}
+
 pub struct LineState {
     lines: Vec<(Point2, Point2)>,
     intersection: Vec<(Point2, Point2)>,
     intersection_points: Vec<Point2>,
     point_of_line: Option<Point2>,
+    method: Method,
     color: graphics::Color,
     line_color: graphics::Color,
     intersection_color: graphics::Color,
@@ -26,6 +34,7 @@ impl LineState {
             intersection: Vec::new(),
             intersection_points: Vec::new(),
             point_of_line: None,
+            method: Method::RTree,
             color,
             line_color,
             intersection_color,
@@ -39,7 +48,10 @@ impl Scene<SharedState, Event> for LineState {
     fn update(&mut self, _state: &mut SharedState) -> SceneSwitch<SharedState, Event> {
         if self.dirty_flag {
             self.dirty_flag = false;
-            let (a, b) = intersection::iso_scan_line(&self.lines[..]);
+            let (a, b) = match self.method {
+                Method::RTree => intersection::segment_intersections(&self.lines[..]),
+                Method::ScanLine => intersection::iso_scan_line(&self.lines[..]),
+            };
             self.intersection = a;
             self.intersection_points = b;
         }
@@ -81,6 +93,14 @@ impl Scene<SharedState, Event> for LineState {
             graphics::circle(ctx, DrawMode::Fill, p.clone(), 2.5, 0.15)?;
         }
 
+        let font = graphics::Font::default_font().unwrap();
+        let help = match self.method {
+            Method::RTree => "r-tree candidates, press m for the scan line",
+            Method::ScanLine => "scan line, press m for r-tree candidates",
+        };
+        let text = graphics::Text::new(ctx, help, &font)?;
+        graphics::draw(ctx, &text, Point2::new(10.0, 10.0), 0.0)?;
+
         graphics::present(ctx);
         Ok(())
     }
@@ -121,6 +141,14 @@ impl Scene<SharedState, Event> for LineState {
             self.point_of_line = None;
         }
 
+        if let Event::Mode = event {
+            self.method = match self.method {
+                Method::RTree => Method::ScanLine,
+                Method::ScanLine => Method::RTree,
+            };
+            self.dirty_flag = true;
+        }
+
         if let Event::Esc = event {
             self.close = true;
         }
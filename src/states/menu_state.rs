@@ -25,6 +25,8 @@ impl MenuState {
             "iso scan line".to_string(),
             "2d-tree".to_string(),
             "triangulate".to_string(),
+            "fortune's sweep".to_string(),
+            "polygon".to_string(),
         ];
         MenuState {
             switch: false,
@@ -66,6 +68,8 @@ impl Scene<SharedState, Event> for MenuState {
                     "2d-tree",
                 )),
                 "triangulate" => SceneSwitch::Push(box super::triangulate_state::TriangulateState::new()),
+                "fortune's sweep" => SceneSwitch::Push(box super::fortune_state::FortuneState::new()),
+                "polygon" => SceneSwitch::Push(box super::polygon_state::PolygonState::new()),
                 _ => SceneSwitch::None,
             }
         } else {
@@ -0,0 +1,210 @@
+use ggez::graphics::{DrawMode, Point2};
+use ggez::*;
+
+use crate::mesh::Quality;
+use crate::polygon::Polygon;
+
+use super::*;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Mode {
+    Monotone,
+    EarClipping,
+    // Delaunay refinement to a minimum angle of MIN_ANGLE
+    Quality,
+}
+
+const MIN_ANGLE: f64 = 30.0;
+
+pub struct PolygonState {
+    // the first ring is the outer boundary, every following one a hole
+    rings: Vec<Vec<Point2>>,
+    // the ring being drawn, closed with the right mouse button
+    open: Vec<Point2>,
+    cursor: Point2,
+    mode: Mode,
+    pieces: Vec<Vec<Point2>>,
+    triangles: Vec<[Point2; 3]>,
+    quality: Option<Quality>,
+    simple: bool,
+    point_color: graphics::Color,
+    triangle_color: graphics::Color,
+    piece_colors: Vec<graphics::Color>,
+    dirty_flag: bool,
+    close: bool,
+}
+
+impl PolygonState {
+    pub fn new() -> Self {
+        PolygonState {
+            rings: Vec::new(),
+            open: Vec::new(),
+            cursor: Point2::new(0.0, 0.0),
+            mode: Mode::Monotone,
+            pieces: Vec::new(),
+            triangles: Vec::new(),
+            quality: None,
+            simple: true,
+            point_color: graphics::Color::from_rgb(255, 255, 255),
+            triangle_color: graphics::Color::from_rgb(255, 255, 0),
+            piece_colors: vec![
+                graphics::Color::from_rgba(200, 50, 50, 120),
+                graphics::Color::from_rgba(50, 200, 50, 120),
+                graphics::Color::from_rgba(50, 100, 255, 120),
+                graphics::Color::from_rgba(200, 200, 50, 120),
+                graphics::Color::from_rgba(200, 50, 200, 120),
+                graphics::Color::from_rgba(50, 200, 200, 120),
+            ],
+            dirty_flag: false,
+            close: false,
+        }
+    }
+
+    fn triangulate(&mut self) {
+        self.pieces.clear();
+        self.triangles.clear();
+        self.quality = None;
+        if self.rings.is_empty() {
+            self.simple = true;
+            return;
+        }
+        let polygon = Polygon::with_holes(&self.rings[0], &self.rings[1..]);
+        self.simple = polygon.is_simple();
+        if !self.simple {
+            return;
+        }
+        let vertices = polygon.vertices();
+        let triangles = match self.mode {
+            Mode::Monotone => {
+                self.pieces = polygon
+                    .monotone_pieces()
+                    .iter()
+                    .map(|piece| piece.iter().map(|v| vertices[*v]).collect())
+                    .collect();
+                polygon.triangulate()
+            }
+            Mode::EarClipping => polygon.ear_clipping(),
+            Mode::Quality => {
+                let mesh = polygon.quality_mesh(MIN_ANGLE, None, 10_000);
+                self.quality = mesh.quality();
+                self.triangles = (0..mesh.len()).map(|t| mesh.triangle(t)).collect();
+                return;
+            }
+        };
+        self.triangles = triangles
+            .iter()
+            .map(|t| [vertices[t[0]], vertices[t[1]], vertices[t[2]]])
+            .collect();
+    }
+}
+
+impl Scene<SharedState, Event> for PolygonState {
+    fn update(&mut self, _state: &mut SharedState) -> SceneSwitch<SharedState, Event> {
+        if self.dirty_flag {
+            self.dirty_flag = false;
+            self.triangulate();
+        }
+        if self.close {
+            SceneSwitch::Pop
+        } else {
+            SceneSwitch::None
+        }
+    }
+    fn draw(&mut self, _state: &mut SharedState, ctx: &mut ggez::Context) -> ggez::GameResult<()> {
+        graphics::clear(ctx);
+
+        for (i, piece) in self.pieces.iter().enumerate() {
+            graphics::set_color(ctx, self.piece_colors[i % self.piece_colors.len()])?;
+            graphics::polygon(ctx, DrawMode::Fill, piece)?;
+        }
+
+        graphics::set_color(ctx, self.triangle_color)?;
+        for triangle in &self.triangles {
+            graphics::polygon(ctx, DrawMode::Line(1.0), &triangle[..])?;
+        }
+
+        graphics::set_color(ctx, self.point_color)?;
+        for ring in &self.rings {
+            graphics::polygon(ctx, DrawMode::Line(2.0), ring)?;
+        }
+        if !self.open.is_empty() {
+            let mut path = self.open.clone();
+            path.push(self.cursor);
+            graphics::line(ctx, &path, 2.0)?;
+        }
+        for point in self.rings.iter().flatten().chain(&self.open) {
+            graphics::circle(ctx, DrawMode::Fill, *point, 2.5, 0.15)?;
+        }
+
+        let font = graphics::Font::default_font().unwrap();
+        let help = match (self.simple, self.mode) {
+            (false, _) => "the polygon is not simple",
+            (true, Mode::Monotone) => "monotone pieces, press m for ear clipping",
+            (true, Mode::EarClipping) => "ear clipping, press m for delaunay refinement",
+            (true, Mode::Quality) => "delaunay refinement, press m for monotone pieces",
+        };
+        let text = graphics::Text::new(ctx, help, &font)?;
+        graphics::draw(ctx, &text, Point2::new(10.0, 10.0), 0.0)?;
+        let text = graphics::Text::new(
+            ctx,
+            "left click adds a vertex, right click closes the ring, return clears",
+            &font,
+        )?;
+        graphics::draw(ctx, &text, Point2::new(10.0, 30.0), 0.0)?;
+        if let Some(quality) = self.quality {
+            let stats = format!(
+                "{} triangles, angles {:.1} to {:.1}, aspect ratio {:.2} mean {:.2} max",
+                self.triangles.len(),
+                quality.min_angle,
+                quality.max_angle,
+                quality.mean_aspect_ratio,
+                quality.max_aspect_ratio
+            );
+            let text = graphics::Text::new(ctx, &stats, &font)?;
+            graphics::draw(ctx, &text, Point2::new(10.0, 50.0), 0.0)?;
+        }
+
+        graphics::present(ctx);
+        Ok(())
+    }
+    fn input(&mut self, _state: &mut SharedState, event: Event, _started: bool) {
+        match event {
+            Event::LeftMouseButton { x, y } => {
+                let point = Point2::new(x as f32, y as f32);
+                if !self.open.contains(&point) {
+                    debug!("Created Vertex: {}", point);
+                    self.open.push(point);
+                }
+            }
+            Event::RightMouseButton { .. } => {
+                if 3 <= self.open.len() {
+                    debug!("Closed Ring: {} vertices", self.open.len());
+                    self.rings.push(self.open.split_off(0));
+                    self.dirty_flag = true;
+                }
+            }
+            Event::MouseMove { x, y } => self.cursor = Point2::new(x as f32, y as f32),
+            Event::Return => {
+                self.rings.clear();
+                self.open.clear();
+                self.dirty_flag = true;
+            }
+            Event::Mode => {
+                self.mode = match self.mode {
+                    Mode::Monotone => Mode::EarClipping,
+                    Mode::EarClipping => Mode::Quality,
+                    Mode::Quality => Mode::Monotone,
+                };
+                self.dirty_flag = true;
+            }
+            Event::Esc => self.close = true,
+            _ => {}
+        }
+    }
+    fn name(&self) -> &str {
+        "polygon"
+    }
+    fn draw_previous(&self) -> bool {
+        false
+    }
+}
@@ -1,23 +1,91 @@
 use ggez::graphics::{DrawMode, Point2};
 use ggez::*;
 
+use crate::convex_hull;
 use crate::kd_tree;
+use crate::priority_search_tree::PrioritySearchTree;
+use crate::range_tree;
+use crate::shape::{Circle, ConvexPolygon, HalfPlane, Shape};
+use crate::spatial_index::{IndexKind, SpatialIndex};
 
 use super::*;
 
-#[derive(Clone)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum QueryShape {
+    Rectangle,
+    Circle,
+    HalfPlane,
+    Triangle,
+    Polygon,
+    // x between the clicks and above the lower one
+    OpenUpward,
+}
+
+impl QueryShape {
+    fn next(self) -> QueryShape {
+        match self {
+            QueryShape::Rectangle => QueryShape::Circle,
+            QueryShape::Circle => QueryShape::HalfPlane,
+            QueryShape::HalfPlane => QueryShape::Triangle,
+            QueryShape::Triangle => QueryShape::Polygon,
+            QueryShape::Polygon => QueryShape::OpenUpward,
+            QueryShape::OpenUpward => QueryShape::Rectangle,
+        }
+    }
+
+    // number of clicks that define the shape, polygons take any number
+    fn clicks(self) -> Option<usize> {
+        match self {
+            QueryShape::Rectangle
+            | QueryShape::Circle
+            | QueryShape::HalfPlane
+            | QueryShape::OpenUpward => Some(2),
+            QueryShape::Triangle => Some(3),
+            QueryShape::Polygon => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            QueryShape::Rectangle => "rectangle",
+            QueryShape::Circle => "circle",
+            QueryShape::HalfPlane => "half-plane",
+            QueryShape::Triangle => "triangle",
+            QueryShape::Polygon => "convex hull",
+            QueryShape::OpenUpward => "open upward",
+        }
+    }
+}
+
 pub struct SearchTreeState {
     points: Vec<Point2>,
     query_points: Vec<(f32, f32)>,
     query: (Option<Point2>, Option<Point2>),
+    // the other query shapes are given by the clicked points
+    query_shape: QueryShape,
+    shape_points: Vec<Point2>,
     point_color: graphics::Color,
     query_color: graphics::Color,
-    dirty_flag_tree: bool,
+    visited_color: graphics::Color,
+    pruned_color: graphics::Color,
     dirty_flag_search: bool,
+    dirty_flag_range_tree: bool,
+    dirty_flag_priority_tree: bool,
     point_mode: bool,
+    // show how many nodes the kd-tree and the range tree visit for the query
+    compare_mode: bool,
+    visited: (usize, usize),
+    // what the kd-tree did for the current query
+    stats: kd_tree::QueryStats,
     query_started: bool,
     close: bool,
-    tree: Option<kd_tree::KdTree>,
+    tree: kd_tree::KdTree,
+    range_tree: range_tree::RangeTree,
+    // holds the points with negated y, so its y >= c queries open upward on screen
+    priority_tree: PrioritySearchTree,
+    // answers the range query, switched at runtime
+    index: Box<dyn SpatialIndex>,
+    index_kind: IndexKind,
     name: String,
 }
 
@@ -29,45 +97,107 @@ impl SearchTreeState {
             points: Vec::new(),
             query_points: Vec::new(),
             query: (None, None),
+            query_shape: QueryShape::Rectangle,
+            shape_points: Vec::new(),
             point_color,
             query_color,
-            dirty_flag_tree: false,
+            visited_color: graphics::Color::from_rgb(0, 70, 0),
+            pruned_color: graphics::Color::from_rgb(80, 0, 0),
             dirty_flag_search: false,
+            dirty_flag_range_tree: false,
+            dirty_flag_priority_tree: false,
             point_mode: true,
+            compare_mode: false,
+            visited: (0, 0),
+            stats: kd_tree::QueryStats::default(),
             query_started: false,
             close: false,
-            tree: None,
+            tree: kd_tree::KdTree::new(&[]),
+            range_tree: range_tree::RangeTree::new(&[]),
+            priority_tree: PrioritySearchTree::new(&[]),
+            index: IndexKind::KdTree.build(&[]),
+            index_kind: IndexKind::KdTree,
             name: name.to_string(),
         }
     }
+
+    fn shape(&self) -> Option<Box<dyn Shape>> {
+        let p: Vec<(f32, f32)> = self.shape_points.iter().map(|x| (x[0], x[1])).collect();
+        match self.query_shape {
+            QueryShape::Circle if p.len() == 2 => Some(box Circle {
+                center: p[0],
+                radius: kd_tree::Bounds::point(p[0]).distance_squared(p[1]).sqrt(),
+            }),
+            QueryShape::HalfPlane if p.len() == 2 => Some(box HalfPlane { a: p[0], b: p[1] }),
+            QueryShape::Triangle if p.len() == 3 => {
+                Some(box ConvexPolygon::triangle(p[0], p[1], p[2]))
+            }
+            QueryShape::Polygon if 3 <= p.len() => {
+                let hull: Vec<(f32, f32)> = convex_hull::grahams_scan(&self.shape_points)
+                    .iter()
+                    .map(|x| (x[0], x[1]))
+                    .collect();
+                Some(box ConvexPolygon::new(&hull))
+            }
+            _ => None,
+        }
+    }
 }
 
 impl Scene<SharedState, Event> for SearchTreeState {
     fn update(&mut self, _state: &mut SharedState) -> SceneSwitch<SharedState, Event> {
-        let points = if self.dirty_flag_search || self.dirty_flag_tree {
-            self.points.iter().map(|x| (x[0], x[1])).collect()
-        } else {
-            Vec::new()
-        };
-
-        // recalc tree
-        if self.dirty_flag_tree {
-            self.dirty_flag_tree = false;
-
-            self.tree = Some(kd_tree::KdTree::new(&points[..]));
+        // the range tree is static and only rebuilt when it is looked at
+        if self.compare_mode && self.dirty_flag_range_tree {
+            self.dirty_flag_range_tree = false;
+            self.dirty_flag_search = true;
+            let points: Vec<(f32, f32)> = self.points.iter().map(|x| (x[0], x[1])).collect();
+            self.range_tree = range_tree::RangeTree::new(&points[..]);
         }
+        if self.query_shape == QueryShape::OpenUpward && self.dirty_flag_priority_tree {
+            self.dirty_flag_priority_tree = false;
+            self.dirty_flag_search = true;
+            let points: Vec<(f32, f32)> = self.points.iter().map(|x| (x[0], -x[1])).collect();
+            self.priority_tree = PrioritySearchTree::new(&points[..]);
+        }
+
         // recalc search result
         if self.dirty_flag_search {
             self.dirty_flag_search = false;
-            if let (Some(t1), Some(t2)) = self.query {
+            if self.query_shape == QueryShape::OpenUpward {
+                let p = &self.shape_points;
+                self.query_points = if p.len() == 2 {
+                    self.priority_tree
+                        .query(p[0].x.min(p[1].x), p[0].x.max(p[1].x), -p[0].y.max(p[1].y))
+                        .into_iter()
+                        .map(|(x, y)| (x, -y))
+                        .collect()
+                } else {
+                    Vec::new()
+                };
+                self.stats = kd_tree::QueryStats::default();
+                self.visited = (0, 0);
+            } else if self.query_shape != QueryShape::Rectangle {
+                self.query_points = match self.shape() {
+                    Some(shape) => self.tree.shape_query(&*shape),
+                    None => Vec::new(),
+                };
+                self.stats = kd_tree::QueryStats::default();
+                self.visited = (0, 0);
+            } else if let (Some(t1), Some(t2)) = self.query {
                 let p1 = { (t1.x.min(t2.x), t1.y.min(t2.y)) };
                 let p2 = { (t1.x.max(t2.x), t1.y.max(t2.y)) };
-                if let Some(tree) = &self.tree {
-                    self.query_points = tree.range_query(p1, p2);
+                self.query_points = self.index.range(p1, p2);
+                let (_, stats) = self.tree.range_query_instrumented(p1, p2);
+                self.stats = stats;
+                if self.compare_mode {
+                    let (_, range_visited) = self.range_tree.range_query_visited(p1, p2);
+                    self.visited = (self.stats.visited, range_visited);
                 }
             } else {
                 // clear queried points
                 self.query_points = Vec::new();
+                self.stats = kd_tree::QueryStats::default();
+                self.visited = (0, 0);
             }
         }
 
@@ -86,29 +216,86 @@ impl Scene<SharedState, Event> for SearchTreeState {
         let color_text = graphics::Color::from_rgb(255, 255, 0);
         graphics::set_color(ctx, color_text)?;
         let text_str = if self.point_mode {
-            "point mode"
+            "point mode".to_string()
         } else {
-            "query mode"
+            format!("query mode, {}", self.query_shape.name())
         };
 
+        // color the cells the kd-tree query visited and the subtrees it pruned
+        if self.index_kind == IndexKind::KdTree {
+            let window = kd_tree::Bounds {
+                min: (0.0, 0.0),
+                max: (
+                    ctx.conf.window_mode.width as f32,
+                    ctx.conf.window_mode.height as f32,
+                ),
+            };
+            let cells = vec![
+                (&self.stats.visited_cells, self.visited_color),
+                (&self.stats.pruned_cells, self.pruned_color),
+            ];
+            for (cells, color) in cells {
+                graphics::set_color(ctx, color)?;
+                for cell in cells {
+                    let rect = graphics::Rect::new(
+                        cell.min.0.max(window.min.0),
+                        cell.min.1.max(window.min.1),
+                        cell.max.0.min(window.max.0) - cell.min.0.max(window.min.0),
+                        cell.max.1.min(window.max.1) - cell.min.1.max(window.min.1),
+                    );
+                    graphics::rectangle(ctx, DrawMode::Fill, rect)?;
+                }
+            }
+            graphics::set_color(ctx, color_text)?;
+        }
+
         // draw tree partioning
-        if let Some(tree) = &self.tree {
-            draw_node(
-                ctx,
-                &*tree.0,
-                0,
-                ctx.conf.window_mode.width,
-                0,
-                ctx.conf.window_mode.height,
-            )?;
+        if self.index_kind != IndexKind::KdTree {
+            for cell in self.index.cells() {
+                let rect = graphics::Rect::new(
+                    cell.min.0,
+                    cell.min.1,
+                    cell.max.0 - cell.min.0,
+                    cell.max.1 - cell.min.1,
+                );
+                graphics::rectangle(ctx, DrawMode::Line(1.0), rect)?;
+            }
+        } else {
+            draw_partition(ctx, &self.tree)?;
         }
 
-        let text = graphics::Text::new(ctx, "press m to change mode", &font)?;
+        let text = graphics::Text::new(
+            ctx,
+            "press m to change mode, c to compare visited nodes, s to switch structure, q to change query shape",
+            &font,
+        )?;
         graphics::draw(ctx, &text, graphics::Point2::new(10.0, 10.0), 0.0)?;
 
-        let text = graphics::Text::new(ctx, text_str, &font)?;
+        let text =
+            graphics::Text::new(ctx, &format!("{}, {}", text_str, self.index.name()), &font)?;
         graphics::draw(ctx, &text, graphics::Point2::new(10.0, 30.0), 0.0)?;
 
+        if self.index_kind == IndexKind::KdTree
+            && self.query_shape == QueryShape::Rectangle
+            && self.query.1.is_some()
+        {
+            let stats_str = format!(
+                "visited nodes {}, pruned subtrees {}, reported leaves {}",
+                self.stats.visited, self.stats.pruned, self.stats.reported
+            );
+            let text = graphics::Text::new(ctx, &stats_str, &font)?;
+            graphics::draw(ctx, &text, graphics::Point2::new(10.0, 50.0), 0.0)?;
+        }
+
+        if self.compare_mode {
+            let compare_str = format!(
+                "visited nodes: kd-tree {}, range tree {}",
+                self.visited.0, self.visited.1
+            );
+            let text = graphics::Text::new(ctx, &compare_str, &font)?;
+            graphics::draw(ctx, &text, graphics::Point2::new(10.0, 70.0), 0.0)?;
+        }
+
         graphics::set_color(ctx, self.point_color)?;
         for point in &self.points {
             graphics::circle(ctx, DrawMode::Fill, point.clone(), 2.5, 0.15)?;
@@ -125,6 +312,45 @@ impl Scene<SharedState, Event> for SearchTreeState {
             let rect = graphics::Rect::new(p1.x, p1.y, p2.x - p1.x, p2.y - p1.y);
             graphics::rectangle(ctx, DrawMode::Line(2.0), rect)?;
         }
+        for point in &self.shape_points {
+            graphics::circle(ctx, DrawMode::Line(1.0), point.clone(), 6.0, 0.15)?;
+        }
+        if self.query_shape == QueryShape::OpenUpward && self.shape_points.len() == 2 {
+            let p = &self.shape_points;
+            let (x_min, x_max) = (p[0].x.min(p[1].x), p[0].x.max(p[1].x));
+            let y = p[0].y.max(p[1].y);
+            let lines = [
+                Point2::new(x_min, 0.0),
+                Point2::new(x_min, y),
+                Point2::new(x_max, y),
+                Point2::new(x_max, 0.0),
+            ];
+            graphics::line(ctx, &lines, 2.0)?;
+        }
+        if self.shape().is_some() {
+            let p = &self.shape_points;
+            match self.query_shape {
+                QueryShape::Circle => {
+                    let radius = ((p[1].x - p[0].x).powi(2) + (p[1].y - p[0].y).powi(2)).sqrt();
+                    graphics::circle(ctx, DrawMode::Line(2.0), p[0], radius, 0.15)?;
+                }
+                QueryShape::HalfPlane => {
+                    // long enough to cross the window
+                    let length = ((p[1].x - p[0].x).powi(2) + (p[1].y - p[0].y).powi(2)).sqrt();
+                    let scale = 4096.0 / length.max(1.0);
+                    let direction = (p[1] - p[0]) * scale;
+                    graphics::line(ctx, &[p[0] - direction, p[0] + direction], 2.0)?;
+                }
+                QueryShape::Triangle => graphics::polygon(ctx, DrawMode::Line(2.0), p)?,
+                _ => {
+                    let hull = convex_hull::grahams_scan(p);
+                    // collinear clicks have no area to draw
+                    if 3 <= hull.len() {
+                        graphics::polygon(ctx, DrawMode::Line(2.0), &hull)?;
+                    }
+                }
+            }
+        }
 
         graphics::present(ctx);
         Ok(())
@@ -133,21 +359,32 @@ impl Scene<SharedState, Event> for SearchTreeState {
         if self.point_mode {
             if let Event::LeftMouseButton { x, y } = event {
                 let point = Point2::new(x as f32, y as f32);
-                self.dirty_flag_tree = true;
                 self.dirty_flag_search = true;
+                self.dirty_flag_range_tree = true;
+                self.dirty_flag_priority_tree = true;
                 if !self.points.contains(&point) {
                     debug!("Created Point: {}", point);
                     self.points.push(point);
+                    self.tree.insert((point.x, point.y));
+                    self.index.insert((point.x, point.y));
                 } else {
                     debug!("Removed Point: {}", point);
                     self.points.remove_item(&point);
+                    self.tree.remove((point.x, point.y));
+                    self.index.remove((point.x, point.y));
                 }
             }
         } else {
             if let Event::LeftMouseButton { x, y } = event {
                 let point = Point2::new(x as f32, y as f32);
                 self.dirty_flag_search = true;
-                if self.query_started {
+                if self.query_shape != QueryShape::Rectangle {
+                    // a complete shape is replaced by the next click
+                    if self.query_shape.clicks() == Some(self.shape_points.len()) {
+                        self.shape_points.clear();
+                    }
+                    self.shape_points.push(point);
+                } else if self.query_started {
                     self.query.1 = Some(point);
                     self.query_started = false;
                 } else {
@@ -159,6 +396,7 @@ impl Scene<SharedState, Event> for SearchTreeState {
                 self.dirty_flag_search = true;
                 self.query = (None, None);
                 self.query_started = false;
+                self.shape_points.clear();
             }
             if let Event::MouseMove { x, y } = event {
                 let point = Point2::new(x as f32, y as f32);
@@ -171,6 +409,23 @@ impl Scene<SharedState, Event> for SearchTreeState {
         if let Event::Mode = event {
             self.point_mode = !self.point_mode;
         }
+        if let Event::Structure = event {
+            self.index_kind = self.index_kind.next();
+            let points: Vec<(f32, f32)> = self.points.iter().map(|x| (x[0], x[1])).collect();
+            self.index = self.index_kind.build(&points[..]);
+            self.dirty_flag_search = true;
+        }
+        if let Event::Shape = event {
+            self.query_shape = self.query_shape.next();
+            self.query = (None, None);
+            self.query_started = false;
+            self.shape_points.clear();
+            self.dirty_flag_search = true;
+        }
+        if let Event::Compare = event {
+            self.compare_mode = !self.compare_mode;
+            self.dirty_flag_search = true;
+        }
         if let Event::Esc = event {
             self.close = true;
         }
@@ -183,74 +438,26 @@ impl Scene<SharedState, Event> for SearchTreeState {
     }
 }
 
-fn draw_node(
-    ctx: &mut ggez::Context,
-    node: &kd_tree::Node,
-    x_off: u32,
-    x_width: u32,
-    y_off: u32,
-    y_width: u32,
-) -> ggez::GameResult<()> {
-    match node {
-        kd_tree::Node::Knot { key, left, right } => {
-            if key.orientation == kd_tree::Orientation::Horizontal {
-                let p1 = Point2::new(x_off as f32, key.value);
-                let p2 = Point2::new((x_off + x_width) as f32, key.value);
-                graphics::line(ctx, &vec![p1, p2][..], 1.0)?;
-
-                let key_with_offset = key.value as u32 - y_off;
-
-                if let Some(left) = left {
-                    draw_node(
-                        ctx,
-                        left,
-                        x_off,
-                        x_width,
-                        y_off,
-                        y_width - (y_width - key_with_offset),
-                    )?;
-                }
-                if let Some(right) = right {
-                    draw_node(
-                        ctx,
-                        right,
-                        x_off,
-                        x_width,
-                        key.value as u32,
-                        y_width - key_with_offset,
-                    )?;
-                }
-            } else {
-                let p1 = Point2::new(key.value, y_off as f32);
-                let p2 = Point2::new(key.value, (y_off + y_width) as f32);
-                graphics::line(ctx, &vec![p1, p2][..], 1.0)?;
-
-                let key_with_offset = key.value as u32 - x_off;
-
-                if let Some(left) = left {
-                    draw_node(
-                        ctx,
-                        left,
-                        x_off,
-                        x_width - (x_width - key_with_offset),
-                        y_off,
-                        y_width,
-                    )?;
-                }
-                if let Some(right) = right {
-                    draw_node(
-                        ctx,
-                        right,
-                        key.value as u32,
-                        x_width - key_with_offset,
-                        y_off,
-                        y_width,
-                    )?;
-                }
-            }
+// the kd-tree partition lines cut to the window
+fn draw_partition(ctx: &mut ggez::Context, tree: &kd_tree::KdTree) -> ggez::GameResult<()> {
+    let window = kd_tree::Bounds {
+        min: (0.0, 0.0),
+        max: (
+            ctx.conf.window_mode.width as f32,
+            ctx.conf.window_mode.height as f32,
+        ),
+    };
+    let clamp = |p: (f32, f32)| {
+        Point2::new(
+            p.0.max(window.min.0).min(window.max.0),
+            p.1.max(window.min.1).min(window.max.1),
+        )
+    };
+    for (p1, p2) in tree.partition_lines() {
+        let line = kd_tree::Bounds::point(p1).union(&kd_tree::Bounds::point(p2));
+        if window.intersects(&line) {
+            graphics::line(ctx, &[clamp(p1), clamp(p2)], 1.0)?;
         }
-        _ => {}
     }
-
     Ok(())
 }
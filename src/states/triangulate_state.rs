@@ -1,14 +1,87 @@
 use ggez::graphics::{DrawMode, Point2};
 use ggez::*;
 
+use crate::cdt::Cdt;
+use crate::kd_tree::Bounds;
+use crate::mesh::Mesh;
+use crate::proximity;
+use crate::triangulation::Triangulation;
+use crate::voronoi::{self, Voronoi};
+
 use super::*;
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Mode {
+    Delaunay,
+    Constrained,
+    // constrained without the triangles outside of the outer constraint loop and inside
+    // of the loops nested in it, which become holes
+    Domain,
+}
+
+impl Mode {
+    fn next(self) -> Self {
+        match self {
+            Mode::Delaunay => Mode::Constrained,
+            Mode::Constrained => Mode::Domain,
+            Mode::Domain => Mode::Delaunay,
+        }
+    }
+}
+
+// proximity graph drawn over the triangulation, s cycles through them
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Graph {
+    Nothing,
+    MinimumSpanningTree,
+    RelativeNeighbourhood,
+    Gabriel,
+    // up and down change k
+    KNearest,
+}
+
+impl Graph {
+    fn next(self) -> Self {
+        match self {
+            Graph::Nothing => Graph::MinimumSpanningTree,
+            Graph::MinimumSpanningTree => Graph::RelativeNeighbourhood,
+            Graph::RelativeNeighbourhood => Graph::Gabriel,
+            Graph::Gabriel => Graph::KNearest,
+            Graph::KNearest => Graph::Nothing,
+        }
+    }
+}
+
+// constraint endpoints snap to points closer than this
+const SNAP_DISTANCE: f32 = 8.0;
+
 #[derive(Clone)]
 pub struct TriangulateState {
     points: Vec<Point2>,
     point_color: graphics::Color,
-    triangles: Vec<[Point2; 3]>,
+    mesh: Mesh,
     triangle_color: graphics::Color,
+    // kept in the delaunay mode to locate the cursor with its history
+    triangulation: Option<Triangulation>,
+    // the triangle under the cursor
+    highlight: Option<usize>,
+    highlight_color: graphics::Color,
+    mode: Mode,
+    // indices into points
+    constraints: Vec<(usize, usize)>,
+    constraint_color: graphics::Color,
+    // start of the constraint drawn by dragging with the right mouse button
+    drag: Option<Point2>,
+    cursor: Point2,
+    // computed on demand, the cells are clipped to the window
+    voronoi: Option<Voronoi>,
+    voronoi_color: graphics::Color,
+    show_voronoi: bool,
+    graph: Graph,
+    k: usize,
+    // computed on demand like the voronoi diagram
+    graph_edges: Option<Vec<(usize, usize)>>,
+    graph_color: graphics::Color,
     dirty_flag: bool,
     close: bool,
 }
@@ -17,6 +90,8 @@ impl TriangulateState {
     pub fn new() -> Self {
         let point_color = graphics::Color::from_rgb(255, 255, 255);
         let triangle_color = graphics::Color::from_rgb(255, 255, 0);
+        let voronoi_color = graphics::Color::from_rgb(0, 200, 255);
+        let constraint_color = graphics::Color::from_rgb(200, 50, 50);
         let mut points = Vec::new();
         // points.push(Point2::new(227.0, 250.0));
         // points.push(Point2::new(370.0, 163.0));
@@ -45,19 +120,76 @@ impl TriangulateState {
         TriangulateState {
             points,
             point_color,
-            triangles: Vec::new(),
+            mesh: Mesh::default(),
             triangle_color,
+            triangulation: None,
+            highlight: None,
+            highlight_color: graphics::Color::from_rgb(80, 80, 0),
+            mode: Mode::Delaunay,
+            constraints: Vec::new(),
+            constraint_color,
+            drag: None,
+            cursor: Point2::new(0.0, 0.0),
+            voronoi: None,
+            voronoi_color,
+            show_voronoi: false,
+            graph: Graph::Nothing,
+            k: 3,
+            graph_edges: None,
+            graph_color: graphics::Color::from_rgb(50, 200, 50),
             dirty_flag: true,
             close: false,
         }
     }
+
+    // the index of the point close to position, a new point if there is none
+    fn snap(&mut self, position: Point2) -> usize {
+        let distance = |p: &Point2| (p.x - position.x).hypot(p.y - position.y);
+        match (0..self.points.len()).min_by(|a, b| {
+            distance(&self.points[*a])
+                .partial_cmp(&distance(&self.points[*b]))
+                .unwrap()
+        }) {
+            Some(i) if distance(&self.points[i]) <= SNAP_DISTANCE => i,
+            _ => {
+                self.points.push(position);
+                self.points.len() - 1
+            }
+        }
+    }
+
+    fn locate_cursor(&mut self) {
+        self.highlight = match &self.triangulation {
+            Some(triangulation) => triangulation.locate(self.cursor),
+            // constrained meshes are not Delaunay and can have holes, the walk may give up
+            None => self
+                .mesh
+                .walk(self.highlight.unwrap_or(0), self.cursor)
+                .or_else(|| (0..self.mesh.len()).find(|t| self.mesh.contains(*t, self.cursor))),
+        };
+    }
 }
 
 impl Scene<SharedState, Event> for TriangulateState {
     fn update(&mut self, _state: &mut SharedState) -> SceneSwitch<SharedState, Event> {
         if self.dirty_flag {
             self.dirty_flag = false;
-            self.triangles = crate::triangulation::delaunay(&self.points);
+            self.triangulation = None;
+            self.mesh = match self.mode {
+                Mode::Delaunay => {
+                    let triangulation = Triangulation::new(&self.points);
+                    let mesh = Mesh::new(self.points.clone(), triangulation.triangles());
+                    self.triangulation = Some(triangulation);
+                    mesh
+                }
+                Mode::Constrained => Cdt::with_constraints(&self.points, &self.constraints).mesh(),
+                Mode::Domain => {
+                    Cdt::with_constraints(&self.points, &self.constraints).mesh_even_odd()
+                }
+            };
+            self.voronoi = None;
+            self.graph_edges = None;
+            self.locate_cursor();
         }
         if self.close {
             SceneSwitch::Pop
@@ -67,14 +199,73 @@ impl Scene<SharedState, Event> for TriangulateState {
     }
     fn draw(&mut self, _state: &mut SharedState, ctx: &mut ggez::Context) -> ggez::GameResult<()> {
         graphics::clear(ctx);
+        if let Some(t) = self.highlight {
+            graphics::set_color(ctx, self.highlight_color)?;
+            graphics::polygon(ctx, DrawMode::Fill, &self.mesh.triangle(t)[..])?;
+        }
         graphics::set_color(ctx, self.point_color)?;
         for point in &self.points {
             graphics::circle(ctx, DrawMode::Fill, point.clone(), 2.5, 0.15)?;
         }
 
         graphics::set_color(ctx, self.triangle_color)?;
-        for triangle in &self.triangles {
-            graphics::polygon(ctx, DrawMode::Line(1.0), &triangle[..])?;
+        for t in 0..self.mesh.len() {
+            graphics::polygon(ctx, DrawMode::Line(1.0), &self.mesh.triangle(t)[..])?;
+        }
+
+        if self.show_voronoi {
+            if self.voronoi.is_none() {
+                let window = Bounds {
+                    min: (0.0, 0.0),
+                    max: (
+                        ctx.conf.window_mode.width as f32,
+                        ctx.conf.window_mode.height as f32,
+                    ),
+                };
+                self.voronoi = Some(voronoi::voronoi(&self.points, &window));
+            }
+            graphics::set_color(ctx, self.voronoi_color)?;
+            if let Some(voronoi) = &self.voronoi {
+                for cell in voronoi.cells() {
+                    if 3 <= cell.len() {
+                        graphics::polygon(ctx, DrawMode::Line(1.0), cell)?;
+                    }
+                }
+            }
+        }
+
+        if self.graph != Graph::Nothing {
+            if self.graph_edges.is_none() {
+                // the graphs come from the delaunay triangulation whatever the mode is
+                let delaunay = match self.mode {
+                    Mode::Delaunay => self.mesh.clone(),
+                    _ => crate::triangulation::delaunay(&self.points),
+                };
+                self.graph_edges = Some(match self.graph {
+                    Graph::MinimumSpanningTree => proximity::euclidean_mst(&delaunay),
+                    Graph::RelativeNeighbourhood => {
+                        proximity::relative_neighbourhood_graph(&delaunay)
+                    }
+                    Graph::Gabriel => proximity::gabriel_graph(&delaunay),
+                    Graph::KNearest | Graph::Nothing => {
+                        proximity::k_nearest_graph(&self.points, self.k)
+                    }
+                });
+            }
+            graphics::set_color(ctx, self.graph_color)?;
+            if let Some(edges) = &self.graph_edges {
+                for (a, b) in edges {
+                    graphics::line(ctx, &[self.points[*a], self.points[*b]], 3.0)?;
+                }
+            }
+        }
+
+        graphics::set_color(ctx, self.constraint_color)?;
+        for (a, b) in &self.constraints {
+            graphics::line(ctx, &[self.points[*a], self.points[*b]], 2.0)?;
+        }
+        if let Some(start) = self.drag {
+            graphics::line(ctx, &[start, self.cursor], 2.0)?;
         }
 
         graphics::present(ctx);
@@ -89,10 +280,59 @@ impl Scene<SharedState, Event> for TriangulateState {
                 self.dirty_flag = true;
             } else {
                 debug!("Removed Point: {}", point);
-                self.points.remove_item(&point);
+                let removed = self.points.iter().position(|p| *p == point).unwrap();
+                self.points.remove(removed);
+                // constraints of the point go with it, the later indices move down
+                self.constraints
+                    .retain(|(a, b)| *a != removed && *b != removed);
+                for (a, b) in &mut self.constraints {
+                    if removed < *a {
+                        *a -= 1;
+                    }
+                    if removed < *b {
+                        *b -= 1;
+                    }
+                }
                 self.dirty_flag = true;
             }
         }
+        if let Event::RightMouseButtonDown { x, y } = event {
+            self.drag = Some(Point2::new(x as f32, y as f32));
+        }
+        if let Event::MouseMove { x, y } = event {
+            self.cursor = Point2::new(x as f32, y as f32);
+            self.locate_cursor();
+        }
+        if let Event::RightMouseButton { x, y } = event {
+            if let Some(start) = self.drag.take() {
+                let end = Point2::new(x as f32, y as f32);
+                let (a, b) = (self.snap(start), self.snap(end));
+                if a != b {
+                    debug!("Created Constraint: {} {}", self.points[a], self.points[b]);
+                    self.constraints.push((a, b));
+                }
+                self.dirty_flag = true;
+            }
+        }
+        if let Event::Mode = event {
+            self.mode = self.mode.next();
+            self.dirty_flag = true;
+        }
+        if let Event::Voronoi = event {
+            self.show_voronoi = !self.show_voronoi;
+        }
+        if let Event::Structure = event {
+            self.graph = self.graph.next();
+            self.graph_edges = None;
+        }
+        if let Event::ArrowUp = event {
+            self.k += 1;
+            self.graph_edges = None;
+        }
+        if let Event::ArrowDown = event {
+            self.k = (self.k - 1).max(1);
+            self.graph_edges = None;
+        }
         if let Event::Esc = event {
             self.close = true;
         }
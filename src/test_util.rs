@@ -0,0 +1,45 @@
+use ggez::graphics::Point2;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaChaRng;
+
+// Helpers shared by the tests of all modules. Random input is seeded, so failures can be
+// reproduced.
+
+// coordinates in [0, 1000)
+pub fn random_coordinates(n: usize, seed: u64) -> Vec<(f32, f32)> {
+    let mut rng = ChaChaRng::seed_from_u64(seed);
+    (0..n)
+        .map(|_| (rng.gen::<f32>() * 1000.0, rng.gen::<f32>() * 1000.0))
+        .collect()
+}
+
+// integer coordinates in [0, 1000), so equal coordinates and equal points do occur
+pub fn random_lattice(n: usize, seed: u64) -> Vec<(f32, f32)> {
+    let mut rng = ChaChaRng::seed_from_u64(seed);
+    (0..n)
+        .map(|_| (rng.gen_range(0, 1000) as f32, rng.gen_range(0, 1000) as f32))
+        .collect()
+}
+
+pub fn random_points(n: usize, seed: u64) -> Vec<Point2> {
+    random_coordinates(n, seed)
+        .into_iter()
+        .map(|(x, y)| Point2::new(x, y))
+        .collect()
+}
+
+pub fn sorted<T: PartialOrd>(mut v: Vec<T>) -> Vec<T> {
+    v.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    v
+}
+
+// positive for counter clockwise polygons in a y-up coordinate system
+pub fn signed_area(polygon: &[Point2]) -> f64 {
+    (0..polygon.len())
+        .map(|i| {
+            let (a, b) = (polygon[i], polygon[(i + 1) % polygon.len()]);
+            f64::from(a.x) * f64::from(b.y) - f64::from(b.x) * f64::from(a.y)
+        })
+        .sum::<f64>()
+        / 2.0
+}
@@ -1,210 +1,581 @@
+use crate::math::{in_circle, orient};
+use crate::mesh::Mesh;
 use ggez::graphics::Point2;
-use rand::thread_rng;
-use rand::seq::SliceRandom;
-use crate::math;
-
-pub fn delaunay(points: &[Point2]) -> Vec<[Point2; 3]> {
-    // https://github.com/tynril/rtriangulate/blob/master/src/lib.rs
-    if points.len() < 3 {
-        return Vec::new()
-    }
-    // shuffle points
-    let mut shuffeld: Vec<&Point2> = points.iter().collect();
-    let mut rng = thread_rng();
-    shuffeld.shuffle(&mut rng);
-
-    // let mut points = points.to_vec();
-    // points.sort_by(|a, b| {
-    //     a[0].partial_cmp(&b[0])
-    //         .unwrap()
-    //         .then_with(|| a[1].partial_cmp(&b[1]).unwrap())
-    // });
-
-    let initial_triangle = initial_triangle(&points);
-    let mut triangles = Vec::new();
-    triangles.push(initial_triangle);
-
-    // return triangles;
-
-    for (i, p) in points.iter().enumerate() {
-        // let triangle = triangles.iter().find(|&& x| math::point_in_triangle(*p, &x)).expect(&format!("Point({}) was not inside any triangle, this can't happen", i));
-
-        // collect triangles to remove
-        let violating_triangles: Vec<[Point2; 3]> = triangles.clone().into_iter().filter(|x| {
-            math::point_in_triangle_circle(*p, &x[..])
-        }).collect();
-
-
-        // collect edges of violating triangles
-        let mut edges: Vec<[Point2; 2]> = Vec::with_capacity(violating_triangles.len() * 3);
-        for triangle in violating_triangles.iter() {
-            for edge in [[triangle[0], triangle[1]], [triangle[1], triangle[2]], [triangle[2], triangle[0]]].into_iter() {
-                if !edges.contains(edge) && !edges.contains(&[edge[1], edge[0]]) {
-                    edges.push(*edge);
+use rand::{thread_rng, RngCore, SeedableRng};
+use rand_chacha::ChaChaRng;
+
+// Randomized incremental Delaunay triangulation.
+// The points are inserted in random order into a triangle whose three vertices lie at
+// infinity. Replaced triangles are kept and point to the triangles that replaced them,
+// this history is searched to find the triangle a new point falls into. After the
+// insertion the triangles around the new point are made Delaunay by flipping edges.
+// Expected O(n log n) time and O(n) triangles in the history.
+//
+// The vertices at infinity are handled symbolically: their predicates are the limits
+// of the ones for a bounding triangle that grows to infinity, so the finite triangles
+// are exactly the Delaunay triangulation of the input and cover its convex hull.
+
+const NONE: u32 = std::u32::MAX;
+
+#[derive(Clone, Debug)]
+struct Triangle {
+    // counter clockwise in a y-up coordinate system
+    v: [u32; 3],
+    // neighbour across the edge opposite of v[i], NONE on the outer boundary
+    n: [u32; 3],
+    // triangles that replaced this one in the history, alive while there are none
+    children: [u32; 3],
+    child_count: u8,
+}
+
+impl Triangle {
+    fn is_alive(&self) -> bool {
+        self.child_count == 0
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Triangulation {
+    // the input points followed by the directions of the three vertices at infinity
+    points: Vec<(f64, f64)>,
+    finite: usize,
+    triangles: Vec<Triangle>,
+    // position of every finite alive triangle in triangles(), NONE for the others
+    index: Vec<u32>,
+}
+
+impl Triangulation {
+    pub fn new(points: &[Point2]) -> Self {
+        Self::with_rng(points, &mut thread_rng())
+    }
+
+    // the same seed always gives the same insertion order and so the same triangles,
+    // which matters for cocircular points. ChaCha is a fixed algorithm, unlike StdRng its
+    // output does not change with the rand version or the platform.
+    pub fn with_seed(points: &[Point2], seed: u64) -> Self {
+        Self::with_rng(points, &mut ChaChaRng::seed_from_u64(seed))
+    }
+
+    // the insertion order is shuffled with rng
+    pub fn with_rng<R: RngCore + ?Sized>(points: &[Point2], rng: &mut R) -> Self {
+        let finite = points.len();
+        let mut coordinates: Vec<(f64, f64)> = points
+            .iter()
+            .map(|p| (f64::from(p.x), f64::from(p.y)))
+            .collect();
+        // unit directions 120 degree apart, not axis aligned so no input edge is parallel
+        for k in 0..3 {
+            let angle = 0.3 + k as f64 * 2.0 * std::f64::consts::PI / 3.0;
+            coordinates.push((angle.cos(), angle.sin()));
+        }
+        let infinite = finite as u32;
+        let mut triangulation = Triangulation {
+            points: coordinates,
+            finite,
+            triangles: Vec::with_capacity(9 * finite + 1),
+            index: Vec::new(),
+        };
+        triangulation.triangles.push(Triangle {
+            v: [infinite, infinite + 1, infinite + 2],
+            n: [NONE; 3],
+            children: [NONE; 3],
+            child_count: 0,
+        });
+
+        // Fisher-Yates on the raw output, SliceRandom::shuffle is free to change how it
+        // samples between rand versions
+        let mut order: Vec<u32> = (0..finite as u32).collect();
+        for i in (1..order.len()).rev() {
+            let j = (rng.next_u64() % (i as u64 + 1)) as usize;
+            order.swap(i, j);
+        }
+        for p in order {
+            triangulation.insert(p);
+        }
+        let mut next = 0;
+        triangulation.index = (0..triangulation.triangles.len())
+            .map(|t| {
+                if triangulation.is_finite_alive(t as u32) {
+                    next += 1;
+                    next - 1
+                } else {
+                    NONE
+                }
+            })
+            .collect();
+        triangulation
+    }
+
+    // the Delaunay triangles as indices into the input, counter clockwise in a y-up system.
+    // Of equal input points only one is used.
+    pub fn triangles(&self) -> Vec<[usize; 3]> {
+        (0..self.triangles.len() as u32)
+            .filter(|t| self.is_finite_alive(*t))
+            .map(|t| {
+                let v = self.triangles[t as usize].v;
+                [v[0] as usize, v[1] as usize, v[2] as usize]
+            })
+            .collect()
+    }
+
+    // the position in triangles() of the triangle containing q, None outside of the
+    // convex hull. Descends the history in expected O(log n) time.
+    pub fn locate(&self, q: Point2) -> Option<usize> {
+        if self.index.is_empty() {
+            return None;
+        }
+        let q = (f64::from(q.x), f64::from(q.y));
+        let t = self.descend(q);
+        if self.index[t as usize] != NONE {
+            return Some(self.index[t as usize] as usize);
+        }
+        // a point on the convex hull may end up in an infinite triangle touching it, the
+        // finite one is among the triangles around its finite vertices
+        let finite: Vec<u32> = self.triangles[t as usize]
+            .v
+            .iter()
+            .cloned()
+            .filter(|v| !self.is_infinite(*v))
+            .collect();
+        let mut seen = vec![t];
+        let mut stack = vec![t];
+        while let Some(t) = stack.pop() {
+            let triangle = &self.triangles[t as usize];
+            let v = triangle.v;
+            if self.index[t as usize] != NONE
+                && (0..3).all(|i| 0.0 <= self.orient_point(v[i], v[(i + 1) % 3], q))
+            {
+                return Some(self.index[t as usize] as usize);
+            }
+            for n in &triangle.n {
+                let around = *n != NONE
+                    && self.triangles[*n as usize]
+                        .v
+                        .iter()
+                        .any(|v| finite.contains(v));
+                if around && !seen.contains(n) {
+                    seen.push(*n);
+                    stack.push(*n);
                 }
             }
         }
+        None
+    }
 
-        // remove edges that are part of more then two violating triangles
-        let edges: Vec<[Point2; 2]> = edges.into_iter().filter(|edge| {
-            let mut count = 0;
-            for triangle in violating_triangles.iter() {
-                if triangle_has_edge(*triangle, *edge) {
-                    count += 1;
-                }
+    fn is_finite_alive(&self, t: u32) -> bool {
+        let triangle = &self.triangles[t as usize];
+        triangle.is_alive() && triangle.v.iter().all(|v| !self.is_infinite(*v))
+    }
+
+    fn is_infinite(&self, v: u32) -> bool {
+        self.finite <= v as usize
+    }
+
+    fn point(&self, v: u32) -> (f64, f64) {
+        self.points[v as usize]
+    }
+
+    fn insert(&mut self, p: u32) {
+        let t = self.descend(self.point(p));
+        let v = self.triangles[t as usize].v;
+        if v.iter()
+            .any(|x| !self.is_infinite(*x) && self.point(*x) == self.point(p))
+        {
+            return;
+        }
+        let on_edge = (0..3).find(|i| self.orient([v[(i + 1) % 3], v[(i + 2) % 3], p]) == 0.0);
+        match on_edge {
+            Some(i) => self.split_edge(t, i, p),
+            None => self.split_triangle(t, p),
+        }
+    }
+
+    // the alive triangle containing point, found by descending the history
+    fn descend(&self, point: (f64, f64)) -> u32 {
+        let mut t = 0;
+        loop {
+            let triangle = &self.triangles[t as usize];
+            if triangle.is_alive() {
+                return t;
             }
-            count == 1
-        }).collect();
-
-        // remove violating triangles
-        for triangle in violating_triangles.into_iter() {
-            triangles.remove_item(&triangle);
-        }
-
-
-        // create new triangles by connecting point to edges
-        for edge in edges {
-            triangles.push([edge[0], edge[1], *p]);
-        }
-
-    }
-
-    // for every point of bounding triangle
-    for p in &[initial_triangle[0], initial_triangle[1], initial_triangle[2]] {
-        let mut flipped_edge = true;
-        // as long as we found one triangle pair where we needed to flip an edge we are trying again
-        'outer: while flipped_edge {
-            // get all triangles that contain p
-            let p_triangles: Vec<[Point2; 3]> = triangles.clone().into_iter().filter(|x| {
-                *p == x[0] || *p == x[1] || *p == x[2]
-            }).collect();
-
-            for p_triangle in p_triangles.iter() {
-                // search triangle that shares edge with p_triangle
-                let copy = p_triangles.clone();
-                for triangle in copy.into_iter().filter(|x| *x != *p_triangle) {
-                    // unique point of p_triangle
-                    let l1 = p_triangle.iter().filter(|x| !triangle.contains(x)).next();
-                    // unique point of triangle
-                    let l2 = triangle.iter().filter(|x| !p_triangle.contains(x)).next();
-                    // point1 of shared edge = p
-                    let p1 = triangle.iter().filter(|x| *x==p).next();
-                    // point2 of shared edge
-                    let p2 = p_triangle.iter().filter(|x| triangle.contains(x) && *x != p).next();
-
-                    match (l1, l2, p1, p2) {
-                        (Some(l1), Some(l2), Some(p1), Some(p2)) => {
-                            let t1 = [*l1, *l2, *p2];
-                            let t2 = [*l1, *l2, *p1];
-                            // check delauney condition, flip edge if violated
-                            if math::point_in_triangle_circle(**&p1, &t1[..]) {
-                                triangles.remove_item(&triangle).expect("tried to remove triangle but didnt find one");
-                                triangles.remove_item(&p_triangle).expect("tried to remove p_triangle but didnt find one");
-                                triangles.push(t1);
-                                triangles.push(t2);
-                                // repeat from the beginning
-                                continue 'outer;
-                            }
-                        }
-                        // triangle doesn't share edge with p_triangle, check next one
-                        _ => {}
-                    }
+            // the children cover their parents, rounding can make the point miss all of
+            // them by a tiny bit, then the least violated child is taken
+            let mut best = (std::f64::NEG_INFINITY, triangle.children[0]);
+            for child in &triangle.children[..triangle.child_count as usize] {
+                let v = self.triangles[*child as usize].v;
+                let margin = (0..3)
+                    .map(|i| self.orient_point(v[i], v[(i + 1) % 3], point))
+                    .fold(std::f64::INFINITY, f64::min);
+                if 0.0 <= margin {
+                    best = (margin, *child);
+                    break;
+                }
+                if best.0 < margin {
+                    best = (margin, *child);
                 }
             }
-            flipped_edge = false;
+            t = best.1;
         }
     }
 
-    // remove triangles that contain point of initial triangle
-    for p in &[initial_triangle[0], initial_triangle[1], initial_triangle[2]] {
-        let removed_triangles: Vec<[Point2; 3]> = triangles.drain_filter(|x| {
-            x[0] == *p || x[1] == *p || x[2] == *p
-        }).collect();
+    fn add(&mut self, v: [u32; 3], n: [u32; 3]) -> u32 {
+        self.triangles.push(Triangle {
+            v,
+            n,
+            children: [NONE; 3],
+            child_count: 0,
+        });
+        (self.triangles.len() - 1) as u32
     }
 
-    // remove all triangles that don't contain point of initial
-    // let it = initial_triangle;
-    // let removed_triangles: Vec<[Point2; 3]> = triangles.drain_filter(|x| {
-    //     !((x[0] == it[0] || x[1] == it[0] || x[2] == it[0]) 
-    //     || (x[0] == it[1] || x[1] == it[1] || x[2] == it[1]) 
-    //     || (x[0] == it[2] || x[1] == it[2] || x[2] == it[2])) 
-    // }).collect();
+    fn replace(&mut self, t: u32, children: &[u32]) {
+        let triangle = &mut self.triangles[t as usize];
+        triangle.children[..children.len()].copy_from_slice(children);
+        triangle.child_count = children.len() as u8;
+    }
 
-    triangles
-}
+    // the triangle t that had old as neighbour has new in its place now
+    fn replace_neighbour(&mut self, t: u32, old: u32, new: u32) {
+        if t != NONE {
+            for n in self.triangles[t as usize].n.iter_mut() {
+                if *n == old {
+                    *n = new;
+                }
+            }
+        }
+    }
 
-fn triangle_has_edge(triangle: [Point2; 3], edge: [Point2; 2]) -> bool {
-    let edges: Vec<[Point2; 2]> = vec![[triangle[0], triangle[1]], [triangle[1], triangle[2]], [triangle[2], triangle[0]]];
+    // new triangles have p as v[0]
+    fn split_triangle(&mut self, t: u32, p: u32) {
+        let Triangle {
+            v: [a, b, c],
+            n: [na, nb, nc],
+            ..
+        } = self.triangles[t as usize];
+        let first = self.triangles.len() as u32;
+        let (t0, t1, t2) = (first, first + 1, first + 2);
+        self.add([p, b, c], [na, t1, t2]);
+        self.add([p, c, a], [nb, t2, t0]);
+        self.add([p, a, b], [nc, t0, t1]);
+        self.replace_neighbour(na, t, t0);
+        self.replace_neighbour(nb, t, t1);
+        self.replace_neighbour(nc, t, t2);
+        self.replace(t, &[t0, t1, t2]);
+        self.legalize(vec![t0, t1, t2]);
+    }
 
-    if edges.contains(&edge) || edges.contains(&[edge[1], edge[0]]) {
-        true
-    } else {
-        false
+    // p lies on the edge opposite of vertex i of t, t and the triangle on the other side
+    // of the edge are split in two each
+    fn split_edge(&mut self, t: u32, i: usize, p: u32) {
+        let triangle = self.triangles[t as usize].clone();
+        let (a, b, c) = (
+            triangle.v[i],
+            triangle.v[(i + 1) % 3],
+            triangle.v[(i + 2) % 3],
+        );
+        let (nb, nc) = (triangle.n[(i + 1) % 3], triangle.n[(i + 2) % 3]);
+        let u = triangle.n[i];
+        // only edges between two vertices at infinity have no neighbour
+        let other = self.triangles[u as usize].clone();
+        let j = (0..3).find(|j| other.n[*j] == t).unwrap();
+        let d = other.v[j];
+        let (uc, ub) = (other.n[(j + 1) % 3], other.n[(j + 2) % 3]);
+
+        let first = self.triangles.len() as u32;
+        let (t1, t2, u1, u2) = (first, first + 1, first + 2, first + 3);
+        self.add([p, c, a], [nb, t2, u2]);
+        self.add([p, a, b], [nc, u1, t1]);
+        self.add([p, b, d], [uc, u2, t2]);
+        self.add([p, d, c], [ub, t1, u1]);
+        self.replace_neighbour(nb, t, t1);
+        self.replace_neighbour(nc, t, t2);
+        self.replace_neighbour(uc, u, u1);
+        self.replace_neighbour(ub, u, u2);
+        self.replace(t, &[t1, t2]);
+        self.replace(u, &[u1, u2]);
+        self.legalize(vec![t1, t2, u1, u2]);
     }
-}
 
+    // the triangles on the stack have the new point as v[0], the edges opposite of it
+    // are flipped while they are not Delaunay
+    fn legalize(&mut self, mut stack: Vec<u32>) {
+        while let Some(t) = stack.pop() {
+            let triangle = self.triangles[t as usize].clone();
+            let u = triangle.n[0];
+            if u == NONE || !triangle.is_alive() {
+                continue;
+            }
+            let other = self.triangles[u as usize].clone();
+            let j = (0..3).find(|j| other.n[*j] == t).unwrap();
+            let d = other.v[j];
+            if !self.in_circle(triangle.v, d) {
+                continue;
+            }
+
+            // t = [p, b, c] and u = [d, c, b] become [p, b, d] and [p, d, c]
+            let [p, b, c] = triangle.v;
+            let (tb, tc) = (triangle.n[1], triangle.n[2]);
+            let (uc, ub) = (other.n[(j + 1) % 3], other.n[(j + 2) % 3]);
+            let first = self.triangles.len() as u32;
+            let (n1, n2) = (first, first + 1);
+            self.add([p, b, d], [uc, n2, tc]);
+            self.add([p, d, c], [ub, tb, n1]);
+            self.replace_neighbour(uc, u, n1);
+            self.replace_neighbour(tc, t, n1);
+            self.replace_neighbour(ub, u, n2);
+            self.replace_neighbour(tb, t, n2);
+            self.replace(t, &[n1, n2]);
+            self.replace(u, &[n1, n2]);
+            stack.push(n1);
+            stack.push(n2);
+        }
+    }
 
-fn initial_triangle(points: &[Point2]) -> [Point2; 3] {
-    let mut x_min = points[0].x;
-    let mut x_max = points[0].x;
-    let mut y_min = points[0].y;
-    let mut y_max = points[0].y;
+    // v rotated so that it starts at index r, keeps the orientation
+    fn rotated(v: [u32; 3], r: usize) -> [u32; 3] {
+        [v[r], v[(r + 1) % 3], v[(r + 2) % 3]]
+    }
 
-    for point in &points[..] {
-        if point.x < x_min {
-            x_min = point.x;
+    // positive if v is counter clockwise, only the sign is meaningful
+    fn orient(&self, v: [u32; 3]) -> f64 {
+        let infinite = v.iter().filter(|x| self.is_infinite(**x)).count();
+        match infinite {
+            0 => orient(self.point(v[0]), self.point(v[1]), self.point(v[2])),
+            // a, b and a far point in direction d: the side of ab d points to
+            1 => {
+                let r = (0..3).find(|r| self.is_infinite(v[(r + 2) % 3])).unwrap();
+                let [a, b, d] = Self::rotated(v, r);
+                let (a, b, d) = (self.point(a), self.point(b), self.point(d));
+                (b.0 - a.0) * d.1 - (b.1 - a.1) * d.0
+            }
+            // two far points dominate the finite one
+            2 => {
+                let r = (0..3).find(|r| !self.is_infinite(v[*r])).unwrap();
+                let [_, d1, d2] = Self::rotated(v, r);
+                let (d1, d2) = (self.point(d1), self.point(d2));
+                d1.0 * d2.1 - d1.1 * d2.0
+            }
+            _ => orient(self.point(v[0]), self.point(v[1]), self.point(v[2])),
         }
-        if x_max < point.x {
-            x_max = point.x;
+    }
+
+    // orientation of a, b and a finite point that is not part of the triangulation
+    fn orient_point(&self, a: u32, b: u32, point: (f64, f64)) -> f64 {
+        match (self.is_infinite(a), self.is_infinite(b)) {
+            (false, false) => orient(self.point(a), self.point(b), point),
+            // rotated to point, a, far point in direction b
+            (false, true) => {
+                let (a, d) = (self.point(a), self.point(b));
+                (a.0 - point.0) * d.1 - (a.1 - point.1) * d.0
+            }
+            // rotated to b, point, far point in direction a
+            (true, false) => {
+                let (b, d) = (self.point(b), self.point(a));
+                (point.0 - b.0) * d.1 - (point.1 - b.1) * d.0
+            }
+            (true, true) => {
+                let (d1, d2) = (self.point(a), self.point(b));
+                d1.0 * d2.1 - d1.1 * d2.0
+            }
         }
-        if point.y < y_min {
-            y_min = point.y;
+    }
+
+    // whether q lies strictly inside the circumcircle of the counter clockwise triangle v
+    fn in_circle(&self, v: [u32; 3], q: u32) -> bool {
+        let infinite = v.iter().filter(|x| self.is_infinite(**x)).count();
+        if self.is_infinite(q) {
+            if infinite != 1 {
+                // a finite circle or one through two far points never reaches another one
+                return false;
+            }
+            // the circle through a, b and a far point in direction d1 reaches the far
+            // point in direction d2 if d2 points further away from ab
+            let r = (0..3).find(|r| self.is_infinite(v[(r + 2) % 3])).unwrap();
+            let [a, b, d1] = Self::rotated(v, r);
+            let (a, b, d1, d2) = (self.point(a), self.point(b), self.point(d1), self.point(q));
+            let normal = (a.1 - b.1, b.0 - a.0);
+            return normal.0 * d1.0 + normal.1 * d1.1 < normal.0 * d2.0 + normal.1 * d2.1;
         }
-        if y_max < point.y {
-            y_max = point.y;
+
+        let q = self.point(q);
+        match infinite {
+            0 => in_circle(self.point(v[0]), self.point(v[1]), self.point(v[2]), q),
+            // the circle becomes the half-plane left of ab, on the line it is the segment
+            1 => {
+                let r = (0..3).find(|r| self.is_infinite(v[(r + 2) % 3])).unwrap();
+                let [a, b, _] = Self::rotated(v, r);
+                let (a, b) = (self.point(a), self.point(b));
+                let side = orient(a, b, q);
+                0.0 < side
+                    || (side == 0.0
+                        && 0.0 < (q.0 - a.0) * (b.0 - a.0) + (q.1 - a.1) * (b.1 - a.1)
+                        && 0.0 < (q.0 - b.0) * (a.0 - b.0) + (q.1 - b.1) * (a.1 - b.1))
+            }
+            // the circle through a and two far points becomes a half-plane through a,
+            // its normal points to the center of the circle through the origin and the
+            // two directions
+            2 => {
+                let r = (0..3).find(|r| !self.is_infinite(v[*r])).unwrap();
+                let [a, d1, d2] = Self::rotated(v, r);
+                let (a, d1, d2) = (self.point(a), self.point(d1), self.point(d2));
+                let det = d1.0 * d2.1 - d1.1 * d2.0;
+                let (s1, s2) = (
+                    (d1.0 * d1.0 + d1.1 * d1.1) / 2.0,
+                    (d2.0 * d2.0 + d2.1 * d2.1) / 2.0,
+                );
+                let center = ((s1 * d2.1 - s2 * d1.1) / det, (d1.0 * s2 - d2.0 * s1) / det);
+                0.0 < center.0 * (q.0 - a.0) + center.1 * (q.1 - a.1)
+            }
+            // the whole plane
+            _ => true,
         }
     }
+}
 
-    x_min *= 0.99;
-    y_min *= 0.99;
-
-    let x_len = (x_max - x_min) * 1.05;
-    let y_len = (y_max - y_min) * 1.05;
-    let bottem_left = Point2::new(x_min, y_min);
-    let bottem_right = Point2::new(x_max+x_len, y_min);
-    let top_left = Point2::new(x_min, y_max+y_len);
+pub fn delaunay(points: &[Point2]) -> Mesh {
+    Mesh::new(points.to_vec(), Triangulation::new(points).triangles())
+}
 
-    [bottem_left, bottem_right, top_left]
+pub fn delaunay_with_seed(points: &[Point2], seed: u64) -> Mesh {
+    Mesh::new(
+        points.to_vec(),
+        Triangulation::with_seed(points, seed).triangles(),
+    )
+}
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::convex_hull;
+    use crate::test_util::{random_points, signed_area};
+
+    // the triangles have positive area and cover the convex hull exactly
+    fn check_cover(points: &[Point2], triangles: &[[usize; 3]]) {
+        let mut total = 0.0;
+        for t in triangles {
+            let triangle = [points[t[0]], points[t[1]], points[t[2]]];
+            let a = signed_area(&triangle);
+            assert!(0.0 < a);
+            total += a;
+        }
+        let mut unique = points.to_vec();
+        unique.sort_by(|a, b| (a.x, a.y).partial_cmp(&(b.x, b.y)).unwrap());
+        unique.dedup();
+        let hull = signed_area(&convex_hull::grahams_scan(&unique)).abs();
+        assert!((total - hull).abs() <= 1e-6 * hull, "{} != {}", total, hull);
+    }
 
+    #[test]
+    fn triangles_are_delaunay() {
+        let points = random_points(400, 7);
+        let triangles = Triangulation::new(&points).triangles();
+        check_cover(&points, &triangles);
+        for t in &triangles {
+            let (a, b, c) = (points[t[0]], points[t[1]], points[t[2]]);
+            // circumcircle in f64, relative to a
+            let f = |p: Point2| {
+                (
+                    f64::from(p.x) - f64::from(a.x),
+                    f64::from(p.y) - f64::from(a.y),
+                )
+            };
+            let (b, c) = (f(b), f(c));
+            let d = 2.0 * (b.0 * c.1 - b.1 * c.0);
+            let (sb, sc) = (b.0 * b.0 + b.1 * b.1, c.0 * c.0 + c.1 * c.1);
+            let center = ((c.1 * sb - b.1 * sc) / d, (b.0 * sc - c.0 * sb) / d);
+            let distance = |p: Point2| {
+                let p = f(p);
+                (p.0 - center.0).hypot(p.1 - center.1)
+            };
+            let radius = distance(a);
+            for p in &points {
+                assert!(radius * (1.0 - 1e-7) <= distance(*p));
+            }
+        }
+    }
 
-    // let (min_point, max_point) = points.iter().fold(
-    //     (
-    //         Point2::new(std::f32::INFINITY, std::f32::INFINITY),
-    //         Point2::new(std::f32::NEG_INFINITY, std::f32::NEG_INFINITY),
-    //     ),
-    //     |acc, p| {
-    //         (
-    //             Point2::new(acc.0[0].min(p[0]), acc.0[1].min(p[1])),
-    //             Point2::new(acc.1[0].max(p[0]), acc.1[1].max(p[1])),
-    //         )
-    //     },
-    // );
+    #[test]
+    fn degenerate_inputs() {
+        // a grid is full of cocircular and collinear points
+        let grid: Vec<Point2> = (0..100)
+            .map(|i| Point2::new((i % 10) as f32 * 10.0, (i / 10) as f32 * 10.0))
+            .collect();
+        let triangles = Triangulation::new(&grid).triangles();
+        check_cover(&grid, &triangles);
+        // 2n - 2 - h triangles for h points on the hull boundary
+        assert_eq!(triangles.len(), 2 * 100 - 2 - 36);
+
+        let mut duplicates = random_points(50, 3);
+        duplicates.extend(duplicates.clone());
+        let triangles = Triangulation::new(&duplicates).triangles();
+        check_cover(&duplicates, &triangles);
+
+        let line: Vec<Point2> = (0..10)
+            .map(|i| Point2::new(i as f32, 2.0 * i as f32))
+            .collect();
+        assert!(delaunay(&line).is_empty());
+        assert!(delaunay(&line[..2]).is_empty());
+        assert!(delaunay(&[]).is_empty());
+    }
 
-    // let half = 0.5;
-    // let two = 2.0;
+    #[test]
+    fn locate_by_history_and_walk() {
+        let points = random_points(400, 11);
+        let triangulation = Triangulation::new(&points);
+        let mesh = Mesh::new(points.clone(), triangulation.triangles());
+        // the queries reach beyond the convex hull, the input points are on edges
+        let queries = random_points(300, 12)
+            .into_iter()
+            .map(|q| Point2::new(q.x * 1.2 - 100.0, q.y * 1.2 - 100.0))
+            .chain(points.iter().cloned());
+        let mut hint = 0;
+        for q in queries {
+            let found = triangulation.locate(q);
+            match found {
+                Some(t) => assert!(mesh.contains(t, q)),
+                None => assert!((0..mesh.len()).all(|t| !mesh.contains(t, q))),
+            }
+            let walked = mesh.walk(hint, q);
+            assert_eq!(walked.is_some(), found.is_some());
+            if let Some(t) = walked {
+                assert!(mesh.contains(t, q));
+                hint = t;
+            }
+        }
+        assert_eq!(Triangulation::new(&[]).locate(Point2::new(0.0, 0.0)), None);
+    }
 
-    // let delta_point =
-    //     Point2::new(max_point[0] - min_point[0], max_point[1] - min_point[1]);
-    // let delta_max = delta_point.x.max(delta_point.y);
-    // let mid_point = Point2::new(
-    //     (max_point[0] + min_point[0]) * half,
-    //     (max_point[1] + min_point[1]) * half,
-    // );
+    #[test]
+    fn seeds_are_reproducible() {
+        // the diagonal of every grid square depends on the insertion order
+        let grid: Vec<Point2> = (0..100)
+            .map(|i| Point2::new((i % 10) as f32 * 10.0, (i / 10) as f32 * 10.0))
+            .collect();
+        let first = delaunay_with_seed(&grid, 42);
+        assert_eq!(first.triangles(), delaunay_with_seed(&grid, 42).triangles());
+        check_cover(&grid, first.triangles());
+        assert!((0..10).any(|seed| {
+            let mut other = delaunay_with_seed(&grid, seed).triangles().to_vec();
+            let mut first = first.triangles().to_vec();
+            for triangle in first.iter_mut().chain(other.iter_mut()) {
+                triangle.sort();
+            }
+            first.sort();
+            other.sort();
+            first != other
+        }));
+    }
+}
 
-    // let p1 = Point2::new(mid_point[0] - two * delta_max, mid_point[1] - delta_max);
-    // let p2 = Point2::new(mid_point[0], mid_point[1] + two * delta_max);
-    // let p3 = Point2::new(mid_point[0] + two * delta_max, mid_point.y - delta_max);
+#[cfg(test)]
+mod benches {
+    use super::*;
+    use crate::test_util::random_points;
+    use test::{black_box, Bencher};
 
-    // [p1, p2, p3]
-}
\ No newline at end of file
+    #[bench]
+    fn delaunay_10k(b: &mut Bencher) {
+        let points = random_points(10_000, 1);
+        b.iter(|| black_box(delaunay(&points).len()));
+    }
+}
@@ -0,0 +1,233 @@
+use crate::kd_tree::Bounds;
+use crate::math;
+use crate::mesh::Mesh;
+use crate::triangulation;
+use ggez::graphics::Point2;
+
+// Voronoi diagram as the dual of a Delaunay triangulation. Its vertices are the
+// circumcenters of the triangles, every Delaunay edge between two triangles becomes a
+// segment between their circumcenters and every hull edge a ray leaving the hull.
+// The cells of the sites are clipped to a rectangle, so hull sites get closed polygons.
+#[derive(Clone, Debug)]
+pub struct Voronoi {
+    // vertex t is the circumcenter of triangle t of the mesh
+    vertices: Vec<Point2>,
+    edges: Vec<Edge>,
+    cells: Vec<Vec<Point2>>,
+}
+
+// sites are the two points the edge separates
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Edge {
+    Segment {
+        sites: (usize, usize),
+        from: usize,
+        to: usize,
+    },
+    Ray {
+        sites: (usize, usize),
+        from: usize,
+        direction: (f32, f32),
+    },
+}
+
+impl Voronoi {
+    pub fn new(mesh: &Mesh, bounds: &Bounds) -> Self {
+        let vertices = (0..mesh.len())
+            .map(|t| circumcenter(&mesh.triangle(t)))
+            .collect();
+
+        let edges = mesh
+            .edges()
+            .into_iter()
+            .map(|e| {
+                let sites = (mesh.origin(e), mesh.destination(e));
+                match mesh.twin(e) {
+                    Some(twin) => Edge::Segment {
+                        sites,
+                        from: mesh.face(e),
+                        to: mesh.face(twin),
+                    },
+                    // triangles are counter clockwise, the outside is right of the edge
+                    None => {
+                        let (a, b) = (mesh.points()[sites.0], mesh.points()[sites.1]);
+                        Edge::Ray {
+                            sites,
+                            from: mesh.face(e),
+                            direction: (b.y - a.y, a.x - b.x),
+                        }
+                    }
+                }
+            })
+            .collect();
+
+        let points = mesh.points();
+        let cells = (0..points.len())
+            .map(|site| {
+                // without triangles the input is collinear and every point is a neighbour
+                if mesh.is_empty() {
+                    if points[..site].contains(&points[site]) {
+                        return Vec::new();
+                    }
+                    let neighbours: Vec<usize> = (0..points.len()).collect();
+                    clip_cell(points, site, &neighbours, bounds)
+                } else {
+                    clip_cell(points, site, &mesh.vertex_neighbours(site), bounds)
+                }
+            })
+            .collect();
+
+        Voronoi {
+            vertices,
+            edges,
+            cells,
+        }
+    }
+
+    pub fn vertices(&self) -> &[Point2] {
+        &self.vertices
+    }
+
+    pub fn edges(&self) -> &[Edge] {
+        &self.edges
+    }
+
+    // the cell of every input point clipped to the bounds, counter clockwise in a y-up
+    // system, empty for duplicates
+    pub fn cells(&self) -> &[Vec<Point2>] {
+        &self.cells
+    }
+
+    pub fn cell(&self, site: usize) -> &[Point2] {
+        &self.cells[site]
+    }
+}
+
+pub fn voronoi(points: &[Point2], bounds: &Bounds) -> Voronoi {
+    Voronoi::new(&triangulation::delaunay(points), bounds)
+}
+
+// in f64 to keep the precision for thin triangles
+fn circumcenter(triangle: &[Point2; 3]) -> Point2 {
+    let f = |p: Point2| (f64::from(p.x), f64::from(p.y));
+    let center = math::circumcenter(f(triangle[0]), f(triangle[1]), f(triangle[2]));
+    Point2::new(center.0 as f32, center.1 as f32)
+}
+
+// the part of the bounds closer to the site than to any of its neighbours, empty
+// without neighbours
+pub fn clip_cell(
+    points: &[Point2],
+    site: usize,
+    neighbours: &[usize],
+    bounds: &Bounds,
+) -> Vec<Point2> {
+    if neighbours.is_empty() {
+        return Vec::new();
+    }
+    let rectangle = vec![
+        Point2::new(bounds.min.0, bounds.min.1),
+        Point2::new(bounds.max.0, bounds.min.1),
+        Point2::new(bounds.max.0, bounds.max.1),
+        Point2::new(bounds.min.0, bounds.max.1),
+    ];
+    neighbours
+        .iter()
+        .filter(|n| points[**n] != points[site])
+        .fold(rectangle, |cell, n| {
+            clip_closer(&cell, points[site], points[*n])
+        })
+}
+
+// the part of the convex polygon closer to site than to other (Sutherland-Hodgman)
+fn clip_closer(polygon: &[Point2], site: Point2, other: Point2) -> Vec<Point2> {
+    // positive on the side of site
+    let side = |p: Point2| {
+        let (dx, dy) = (f64::from(other.x - site.x), f64::from(other.y - site.y));
+        let (mx, my) = (
+            (f64::from(site.x) + f64::from(other.x)) / 2.0,
+            (f64::from(site.y) + f64::from(other.y)) / 2.0,
+        );
+        dx * (mx - f64::from(p.x)) + dy * (my - f64::from(p.y))
+    };
+    let mut clipped = Vec::with_capacity(polygon.len() + 1);
+    for i in 0..polygon.len() {
+        let (a, b) = (polygon[i], polygon[(i + 1) % polygon.len()]);
+        let (sa, sb) = (side(a), side(b));
+        if 0.0 <= sa {
+            clipped.push(a);
+        }
+        if (0.0 <= sa) != (0.0 <= sb) {
+            let t = (sa / (sa - sb)) as f32;
+            clipped.push(Point2::new(a.x + t * (b.x - a.x), a.y + t * (b.y - a.y)));
+        }
+    }
+    clipped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::signed_area;
+
+    fn distance_squared(a: Point2, b: Point2) -> f32 {
+        (a.x - b.x) * (a.x - b.x) + (a.y - b.y) * (a.y - b.y)
+    }
+
+    #[test]
+    fn cells_tile_the_bounds() {
+        let mut points: Vec<Point2> = (0..200)
+            .map(|i| Point2::new(((i * 37) % 101) as f32 * 5.0, ((i * 53) % 97) as f32 * 5.0))
+            .collect();
+        points.push(points[3]);
+        let bounds = Bounds {
+            min: (-20.0, -20.0),
+            max: (520.0, 500.0),
+        };
+        let diagram = voronoi(&points, &bounds);
+        // only one of the equal points gets a cell
+        assert!(diagram.cell(3).is_empty() != diagram.cell(points.len() - 1).is_empty());
+
+        let total: f64 = diagram.cells().iter().map(|c| signed_area(c)).sum();
+        assert!((total - 540.0 * 520.0).abs() < 1.0, "{}", total);
+        // the vertices of a cell are at least as close to its site as to any other
+        for (site, cell) in diagram.cells().iter().enumerate() {
+            for v in cell {
+                let d = distance_squared(*v, points[site]);
+                assert!(points
+                    .iter()
+                    .all(|p| d <= distance_squared(*v, *p) * 1.001 + 0.01));
+            }
+        }
+        // circumcenters are equally far from both sites of their edges
+        for edge in diagram.edges() {
+            let (sites, from) = match *edge {
+                Edge::Segment { sites, from, .. } => (sites, from),
+                Edge::Ray { sites, from, .. } => (sites, from),
+            };
+            let v = diagram.vertices()[from];
+            let (a, b) = (
+                distance_squared(v, points[sites.0]),
+                distance_squared(v, points[sites.1]),
+            );
+            assert!((a - b).abs() <= 1e-3 * a.max(1.0));
+        }
+    }
+
+    #[test]
+    fn collinear_sites_get_strips() {
+        let points: Vec<Point2> = (0..4).map(|i| Point2::new(i as f32 * 10.0, 0.0)).collect();
+        let bounds = Bounds {
+            min: (-10.0, -10.0),
+            max: (40.0, 10.0),
+        };
+        let diagram = voronoi(&points, &bounds);
+        assert!(diagram.edges().is_empty());
+        let areas: Vec<f64> = diagram
+            .cells()
+            .iter()
+            .map(|c| signed_area(c).round())
+            .collect();
+        assert_eq!(areas, vec![300.0, 200.0, 200.0, 300.0]);
+    }
+}